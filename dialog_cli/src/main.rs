@@ -1,5 +1,5 @@
 use clap::{Arg, Command};
-use dialog_lib::{DialogLib, StorageBackend, Keys, PublicKey, GroupId, hex, DialogConfig};
+use dialog_lib::{DialogLib, StorageBackend, Keys, PublicKey, hex, DialogConfig, BackupArchive, Keystore, DeliveryStatus, TimelineEntry};
 use dotenv::{dotenv, from_path};
 use nostr_sdk::prelude::*;
 use std::{env, path::PathBuf, fs};
@@ -63,27 +63,59 @@ fn get_secret_key(key_arg: &str) -> Result<String, DialogError> {
             find_and_load_env();
             Ok(env::var("ALICE_SK_HEX")?)
         }
-        hex_key => {
-            // Validate that it looks like a hex string
-            if hex_key.len() == 64 && hex_key.chars().all(|c| c.is_ascii_hexdigit()) {
-                Ok(hex_key.to_string())
-            } else {
-                Err(DialogError::General("Key must be either 'bob', 'alice', or a 64-character hex string".into()))
+        hex_key if hex_key.len() == 64 && hex_key.chars().all(|c| c.is_ascii_hexdigit()) => {
+            eprintln!("Warning: raw secret keys on the command line end up in shell history; consider `dialog_cli keys import`");
+            Ok(hex_key.to_string())
+        }
+        name => {
+            // Anything else names an identity in the keystore
+            let keystore = Keystore::open_default()?;
+            if !keystore.contains(name) {
+                return Err(DialogError::General(format!(
+                    "No identity named '{}' in {} (key must be a keystore name, 'bob', 'alice', or a 64-character hex string)",
+                    name,
+                    keystore.path().display()
+                )));
             }
+            let passphrase = read_passphrase(&format!("Passphrase for '{}'", name))?;
+            let keys = keystore.unlock(name, &passphrase)?;
+            Ok(keys.secret_key().to_secret_hex())
         }
     }
 }
 
+/// Read a secret from `env_var`, or prompt for it on the terminal without echo
+fn read_secret(env_var: &str, prompt: &str) -> Result<String, DialogError> {
+    if let Ok(secret) = env::var(env_var) {
+        return Ok(secret);
+    }
+
+    let secret = rpassword::prompt_password(format!("{}: ", prompt))?;
+    if secret.is_empty() {
+        return Err(DialogError::General(format!("{} cannot be empty", prompt)));
+    }
+    Ok(secret)
+}
+
+/// Read a passphrase from DIALOG_PASSPHRASE, or prompt for it on stdin
+fn read_passphrase(prompt: &str) -> Result<String, DialogError> {
+    read_secret("DIALOG_PASSPHRASE", prompt)
+}
+
+fn identity_dir(pubkey: &PublicKey) -> Result<PathBuf, DialogError> {
+    Ok(env::current_dir()?.join(".dialog_cli_data").join(pubkey.to_hex()))
+}
+
 async fn create_dialog_lib(sk_hex: &str, relay_url: &str) -> Result<DialogLib, DialogError> {
     let keys = Keys::parse(sk_hex)?;
-    let data_dir = env::current_dir()?.join(".dialog_cli_data");
-    let identity_dir = data_dir.join(keys.public_key().to_hex());
+    let identity_dir = identity_dir(&keys.public_key())?;
     fs::create_dir_all(&identity_dir)?;
     let db_path = identity_dir.join("mls.db");
     
     let storage_backend = StorageBackend::Sqlite { path: db_path };
+    let config = DialogConfig::load(&identity_dir.join("config.json"))?;
     
-    Ok(DialogLib::new_with_storage(keys, relay_url, storage_backend).await?)
+    Ok(DialogLib::new_with_config(keys, relay_url, storage_backend, config).await?)
 }
 
 #[tokio::main]
@@ -107,7 +139,7 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for identity: 'bob', 'alice', or hex string")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 ),
         )
@@ -118,7 +150,7 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for your identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 )
                 .arg(
@@ -141,13 +173,13 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for your identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 )
                 .arg(
                     Arg::new("group-id")
                         .long("group-id")
-                        .help("Hex-encoded ID of the group")
+                        .help("Group ID (MLS or Nostr, hex), name, or unique prefix")
                         .required(true),
                 )
                 .arg(
@@ -164,7 +196,7 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for your identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 ),
         )
@@ -175,13 +207,13 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for your identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 )
                 .arg(
                     Arg::new("group-id")
                         .long("group-id")
-                        .help("Hex-encoded ID of the group to join")
+                        .help("Group ID (MLS or Nostr, hex), name, or unique prefix of the invite")
                         .required(true),
                 ),
         )
@@ -192,7 +224,7 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for the identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 ),
         )
@@ -203,14 +235,36 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for your identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 )
                 .arg(
                     Arg::new("group-id")
                         .long("group-id")
-                        .help("Hex-encoded Nostr group ID")
+                        .help("Group ID (MLS or Nostr, hex), name, or unique prefix")
                         .required(true),
+                )
+                .arg(
+                    Arg::new("mark-read")
+                        .long("mark-read")
+                        .help("Mark the fetched messages as read (sends a read receipt if enabled)")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("read-receipts")
+                .about("Turns sending read receipts on or off for an identity")
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .value_name("KEY")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("state")
+                        .value_parser(["on", "off"])
+                        .help("New setting; omit to show the current one"),
                 ),
         )
         .subcommand(
@@ -220,10 +274,78 @@ async fn main() -> Result<(), DialogError> {
                     Arg::new("key")
                         .long("key")
                         .value_name("KEY")
-                        .help("Secret key for your identity")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                         .required(true),
                 ),
         )
+        .subcommand(
+            Command::new("backup")
+                .about("Writes a passphrase-encrypted backup of the identity and MLS state")
+                .arg(
+                    Arg::new("key")
+                        .long("key")
+                        .value_name("KEY")
+                        .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Path to write the backup archive to")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restores an identity and its MLS state from an encrypted backup")
+                .arg(
+                    Arg::new("input")
+                        .long("input")
+                        .value_name("FILE")
+                        .help("Path of the backup archive")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Overwrite existing MLS storage for this identity")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("profile")
+                        .long("profile")
+                        .value_name("NAME")
+                        .help("Also import the restored key into the keystore under this name (prompts for its own passphrase)"),
+                )
+                .arg(
+                    Arg::new("print-secret")
+                        .long("print-secret")
+                        .help("Print the restored secret key (hex) to stdout")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("keys")
+                .about("Manages named identities in the encrypted keystore")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("generate")
+                        .about("Generates a new identity")
+                        .arg(Arg::new("name").long("name").help("Name for the identity").required(true)),
+                )
+                .subcommand(
+                    Command::new("import")
+                        .about("Imports an nsec or hex secret key (read from DIALOG_NSEC or stdin)")
+                        .arg(Arg::new("name").long("name").help("Name for the identity").required(true)),
+                )
+                .subcommand(Command::new("list").about("Lists stored identities"))
+                .subcommand(
+                    Command::new("remove")
+                        .about("Deletes a stored identity")
+                        .arg(Arg::new("name").long("name").help("Name of the identity").required(true)),
+                ),
+        )
         .get_matches();
 
     // Use DialogConfig to get relay URLs, respecting environment variables
@@ -277,33 +399,30 @@ async fn main() -> Result<(), DialogError> {
             // Connect to relay
             dialog_lib.connect().await?;
 
-            let group_id_hex = sub_matches.get_one::<String>("group-id").unwrap();
+            let group_arg = sub_matches.get_one::<String>("group-id").unwrap();
             let message = sub_matches.get_one::<String>("message").unwrap();
-
-            // Parse group ID (supporting both 32 and 64 char hex)
-            let group_id = if group_id_hex.len() == 32 {
-                let group_id_bytes = hex::decode(group_id_hex)
-                    .map_err(|e| DialogError::General(format!("Invalid group ID: {}", e)))?;
-                GroupId::from_slice(&group_id_bytes)
-            } else if group_id_hex.len() == 64 {
-                // Need to find the group by Nostr ID
-                let conversations = dialog_lib.get_conversations().await?;
-                conversations.iter()
-                    .find(|c| &c.id == group_id_hex)
-                    .ok_or(DialogError::General("Group not found".into()))?
-                    .group_id
-                    .clone()
-                    .ok_or(DialogError::General("Group has no MLS group ID".into()))?
-            } else {
-                return Err(DialogError::General("Invalid group ID length".into()));
-            };
+            let group_id = dialog_lib.resolve_group(group_arg).await?;
 
             // Sync group state before sending
             dialog_lib.fetch_and_process_group_events(&group_id).await?;
 
             println!("Sending message to group...");
-            dialog_lib.send_message(&group_id, message).await?;
-            println!("Message sent successfully!");
+            let report = dialog_lib.send_message(&group_id, message).await?;
+            for relay in report.accepted() {
+                println!("  ✓ {} accepted", relay);
+            }
+            for (relay, _, reason) in report.rejected() {
+                println!("  ✗ {} rejected: {}", relay, reason);
+            }
+            match report.status {
+                DeliveryStatus::Sent => println!("Message sent successfully!"),
+                DeliveryStatus::Pending => {
+                    println!("Message queued: no relay accepted it yet, it will be retried on the next connection.")
+                }
+                DeliveryStatus::Failed(reason) => {
+                    return Err(DialogError::General(format!("Message was not delivered: {}", reason)));
+                }
+            }
         }
         Some(("list-invites", sub_matches)) => {
             let key_arg = sub_matches.get_one::<String>("key").unwrap();
@@ -347,9 +466,9 @@ async fn main() -> Result<(), DialogError> {
             // Connect to relay
             dialog_lib.connect().await?;
 
-            let group_id_hex = sub_matches.get_one::<String>("group-id").unwrap();
+            let group_arg = sub_matches.get_one::<String>("group-id").unwrap();
             
-            dialog_lib.accept_invite(group_id_hex).await?;
+            dialog_lib.accept_invite(group_arg).await?;
             println!("Successfully joined group!");
         }
         Some(("get-pubkey", sub_matches)) => {
@@ -367,25 +486,9 @@ async fn main() -> Result<(), DialogError> {
             // Connect to relay
             dialog_lib.connect().await?;
 
-            let group_id_hex = sub_matches.get_one::<String>("group-id").unwrap();
-            
-            // Parse group ID
-            let group_id = if group_id_hex.len() == 32 {
-                let group_id_bytes = hex::decode(group_id_hex)
-                    .map_err(|e| DialogError::General(format!("Invalid group ID: {}", e)))?;
-                GroupId::from_slice(&group_id_bytes)
-            } else if group_id_hex.len() == 64 {
-                // Need to find the group by Nostr ID
-                let conversations = dialog_lib.get_conversations().await?;
-                conversations.iter()
-                    .find(|c| &c.id == group_id_hex)
-                    .ok_or(DialogError::General("Group not found".into()))?
-                    .group_id
-                    .clone()
-                    .ok_or(DialogError::General("Group has no MLS group ID".into()))?
-            } else {
-                return Err(DialogError::General("Invalid group ID length".into()));
-            };
+            let group_arg = sub_matches.get_one::<String>("group-id").unwrap();
+            let group_id = dialog_lib.resolve_group(group_arg).await?;
+            let group_id_hex = hex::encode(group_id.as_slice());
 
             let result = dialog_lib.fetch_messages(&group_id).await?;
             
@@ -396,15 +499,49 @@ async fn main() -> Result<(), DialogError> {
                 }
             }
             
-            if result.messages.is_empty() {
+            if result.timeline.is_empty() {
                 println!("\nNo messages found in group.");
             } else {
                 println!("\n--- Messages for group {} ---", group_id_hex);
-                for message in result.messages {
+                for entry in result.timeline {
+                    let message = match entry {
+                        TimelineEntry::Message(message) => message,
+                        TimelineEntry::System(event) => {
+                            println!("* {}", event.describe(|pk| pk.to_hex()));
+                            println!("--------------------");
+                            continue;
+                        }
+                    };
                     println!("From: {}", message.sender.to_hex());
-                    println!("Content: {}", message.content);
+                    println!("Content: {}", message.body);
+                    if !message.seen_by.is_empty() {
+                        let readers: Vec<String> = message.seen_by.iter().map(|pk| pk.to_hex()).collect();
+                        println!("Seen by: {}", readers.join(", "));
+                    }
                     println!("--------------------");
                 }
+                if sub_matches.get_flag("mark-read") {
+                    dialog_lib.mark_read(&group_id).await?;
+                }
+            }
+        }
+        Some(("read-receipts", sub_matches)) => {
+            let key_arg = sub_matches.get_one::<String>("key").unwrap();
+            let sk_hex = get_secret_key(key_arg)?;
+            let keys = Keys::parse(&sk_hex)?;
+            let config_path = identity_dir(&keys.public_key())?.join("config.json");
+            let mut config = DialogConfig::load(&config_path)?;
+
+            match sub_matches.get_one::<String>("state").map(String::as_str) {
+                Some(state) => {
+                    config.read_receipts = state == "on";
+                    config.save(&config_path)?;
+                    println!("Read receipts turned {} for {}", state, key_arg);
+                }
+                None => {
+                    let state = if config.read_receipts { "on" } else { "off" };
+                    println!("Read receipts are {} for {}", state, key_arg);
+                }
             }
         }
         Some(("list-groups", sub_matches)) => {
@@ -430,6 +567,94 @@ async fn main() -> Result<(), DialogError> {
                 }
             }
         }
+        Some(("backup", sub_matches)) => {
+            let key_arg = sub_matches.get_one::<String>("key").unwrap();
+            let sk_hex = get_secret_key(key_arg)?;
+            let output = PathBuf::from(sub_matches.get_one::<String>("output").unwrap());
+            if output.exists() {
+                return Err(DialogError::General(format!("{} already exists", output.display())));
+            }
+
+            let passphrase = read_passphrase("Backup passphrase")?;
+            let dialog_lib = create_dialog_lib(&sk_hex, &relay_url).await?;
+            println!("Creating backup for: {}", key_arg);
+
+            let archive = dialog_lib.export_backup(&passphrase).await?;
+            fs::write(&output, archive)?;
+            println!("Backup written to {}", output.display());
+        }
+        Some(("restore", sub_matches)) => {
+            let input = PathBuf::from(sub_matches.get_one::<String>("input").unwrap());
+            let force = sub_matches.get_flag("force");
+
+            let bytes = fs::read(&input)?;
+            let passphrase = read_passphrase("Backup passphrase")?;
+            let archive = BackupArchive::decrypt(&bytes, &passphrase)?;
+            let keys = archive.keys()?;
+
+            let target_dir = identity_dir(&keys.public_key())?;
+            let restored = archive.restore_files(&target_dir, force)?;
+
+            // Settings and contacts are kept beside the restored database
+            let config_path = target_dir.join("config.json");
+            if force || !config_path.exists() {
+                archive.config.save(&config_path)?;
+            }
+            let dialog_lib = create_dialog_lib(&keys.secret_key().to_secret_hex(), &relay_url).await?;
+            let imported = dialog_lib.import_contacts(archive.contacts.clone()).await?;
+
+            println!("Restored identity: {}", keys.public_key().to_hex());
+            println!("  Backup created: {}", archive.created_at);
+            println!("  Created by dialog_lib {}", archive.app_version);
+            println!("  Relays: {}", archive.config.relay_urls.join(", "));
+            println!("  Contacts: {} ({} imported)", archive.contacts.len(), imported);
+            for path in restored {
+                println!("  Restored {}", path.display());
+            }
+            if let Some(profile) = sub_matches.get_one::<String>("profile") {
+                let keystore_passphrase = read_passphrase(&format!("New passphrase for '{}'", profile))?;
+                let mut keystore = Keystore::open_default()?;
+                keystore.import_keys(profile, &keys, &keystore_passphrase)?;
+                println!("  Imported into keystore as '{}'", profile);
+            }
+            if sub_matches.get_flag("print-secret") {
+                println!("{}", keys.secret_key().to_secret_hex());
+            }
+        }
+        Some(("keys", sub_matches)) => {
+            let mut keystore = Keystore::open_default()?;
+            match sub_matches.subcommand() {
+                Some(("generate", args)) => {
+                    let name = args.get_one::<String>("name").unwrap();
+                    let passphrase = read_passphrase(&format!("New passphrase for '{}'", name))?;
+                    let keys = keystore.generate(name, &passphrase)?;
+                    println!("Generated identity '{}': {}", name, keys.public_key().to_hex());
+                }
+                Some(("import", args)) => {
+                    let name = args.get_one::<String>("name").unwrap();
+                    let secret = read_secret("DIALOG_NSEC", "nsec or hex secret key")?;
+                    let passphrase = read_passphrase(&format!("New passphrase for '{}'", name))?;
+                    let pubkey = keystore.import_nsec(name, &secret, &passphrase)?;
+                    println!("Imported identity '{}': {}", name, pubkey.to_hex());
+                }
+                Some(("list", _)) => {
+                    let profiles = keystore.list();
+                    if profiles.is_empty() {
+                        println!("No identities in {}", keystore.path().display());
+                    } else {
+                        for profile in profiles {
+                            println!("  {}: {}", profile.name, profile.pubkey.to_hex());
+                        }
+                    }
+                }
+                Some(("remove", args)) => {
+                    let name = args.get_one::<String>("name").unwrap();
+                    keystore.remove(name)?;
+                    println!("Removed identity '{}'", name);
+                }
+                _ => unreachable!(),
+            }
+        }
         _ => unreachable!(),
     }
 
@@ -0,0 +1,451 @@
+//! Passphrase-encrypted backups of a dialog identity.
+//!
+//! A backup bundles the Nostr secret key, a snapshot of the MLS database, contacts and
+//! relay configuration into a single archive:
+//!
+//! ```text
+//! magic "DIALOGBK" | format version (u8) | scrypt log_n (u8) | salt (16) | nonce (24) | ciphertext
+//! ```
+//!
+//! The payload is JSON encrypted with XChaCha20-Poly1305 under a key derived
+//! from the passphrase with scrypt. The header is bound as associated data, so
+//! any tampering with it or the ciphertext fails decryption. Each stored file
+//! additionally carries a SHA-256 checksum that is verified on restore.
+
+use crate::config::DialogConfig;
+use crate::errors::{DialogError, Result};
+use crate::types::Contact;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use nostr_mls::prelude::Keys;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::Connection;
+use std::path::{Path, PathBuf};
+
+/// Magic bytes at the start of every backup archive
+const MAGIC: &[u8; 8] = b"DIALOGBK";
+/// Current archive format version
+pub const BACKUP_FORMAT_VERSION: u8 = 1;
+/// Default scrypt cost (same as the NIP-49 recommendation)
+const DEFAULT_LOG_N: u8 = 16;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 2 + SALT_LEN + NONCE_LEN;
+
+/// A file captured from the storage directory
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BackupFile {
+    /// File name relative to the identity's storage directory
+    pub name: String,
+    /// Hex-encoded SHA-256 of the file contents
+    pub sha256: String,
+    /// Hex-encoded file contents
+    data: String,
+}
+
+impl BackupFile {
+    fn from_bytes(name: impl Into<String>, bytes: &[u8]) -> Self {
+        Self {
+            name: name.into(),
+            sha256: hex::encode(Sha256::digest(bytes)),
+            data: hex::encode(bytes),
+        }
+    }
+
+    /// Decode the contents and verify them against the stored checksum
+    pub fn contents(&self) -> Result<Vec<u8>> {
+        let bytes = hex::decode(&self.data)
+            .map_err(|e| DialogError::Backup(format!("Corrupt data for {}: {}", self.name, e)))?;
+        if hex::encode(Sha256::digest(&bytes)) != self.sha256 {
+            return Err(DialogError::Backup(format!("Checksum mismatch for {}", self.name)));
+        }
+        Ok(bytes)
+    }
+}
+
+impl std::fmt::Debug for BackupFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackupFile")
+            .field("name", &self.name)
+            .field("sha256", &self.sha256)
+            .field("len", &(self.data.len() / 2))
+            .finish()
+    }
+}
+
+/// Decrypted contents of a backup archive
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BackupArchive {
+    /// Archive format version this payload was written with
+    pub format_version: u8,
+    /// Version of dialog_lib that created the backup
+    pub app_version: String,
+    /// Creation time (Unix timestamp in seconds)
+    pub created_at: i64,
+    /// Hex-encoded public key of the backed up identity
+    pub pubkey: String,
+    /// Hex-encoded secret key (never printed by `Debug`)
+    secret_key: String,
+    /// Relay configuration
+    pub config: DialogConfig,
+    /// Known contacts
+    pub contacts: Vec<Contact>,
+    /// MLS storage files
+    pub files: Vec<BackupFile>,
+}
+
+impl std::fmt::Debug for BackupArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackupArchive")
+            .field("format_version", &self.format_version)
+            .field("app_version", &self.app_version)
+            .field("created_at", &self.created_at)
+            .field("pubkey", &self.pubkey)
+            .field("secret_key", &"<redacted>")
+            .field("config", &self.config)
+            .field("contacts", &self.contacts.len())
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+impl BackupArchive {
+    /// Start a new archive for the given identity and configuration
+    pub fn new(keys: &Keys, config: DialogConfig) -> Self {
+        Self {
+            format_version: BACKUP_FORMAT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            pubkey: keys.public_key().to_hex(),
+            secret_key: keys.secret_key().to_secret_hex(),
+            config,
+            contacts: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Include contacts in the archive
+    pub fn with_contacts(mut self, contacts: Vec<Contact>) -> Self {
+        self.contacts = contacts;
+        self
+    }
+
+    /// Add an in-memory file to the archive
+    pub fn add_file_bytes(&mut self, name: impl Into<String>, bytes: &[u8]) {
+        self.files.push(BackupFile::from_bytes(name, bytes));
+    }
+
+    /// Add a consistent snapshot of a SQLite database to the archive.
+    ///
+    /// The snapshot is taken with `VACUUM INTO`, so it includes everything
+    /// committed to the write-ahead log and needs no `-wal`/`-shm` files.
+    pub async fn add_sqlite_database(&mut self, db_path: &Path) -> Result<()> {
+        let name = db_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| DialogError::Backup(format!("Invalid database path: {}", db_path.display())))?
+            .to_string();
+
+        let snapshot_path = db_path.with_file_name(format!("{}.backup-{}", name, std::process::id()));
+        let _ = tokio::fs::remove_file(&snapshot_path).await;
+        let snapshot = snapshot_sqlite(db_path, &snapshot_path).await;
+        let bytes = match snapshot {
+            Ok(()) => tokio::fs::read(&snapshot_path).await.map_err(DialogError::from),
+            Err(e) => Err(e),
+        };
+        let _ = tokio::fs::remove_file(&snapshot_path).await;
+
+        self.add_file_bytes(name, &bytes?);
+        Ok(())
+    }
+
+    /// The identity keys stored in the archive
+    pub fn keys(&self) -> Result<Keys> {
+        let keys = Keys::parse(&self.secret_key)
+            .map_err(|e| DialogError::Backup(format!("Invalid secret key in backup: {}", e)))?;
+        if keys.public_key().to_hex() != self.pubkey {
+            return Err(DialogError::Backup("Secret key does not match backup public key".into()));
+        }
+        Ok(keys)
+    }
+
+    /// Check the archive is internally consistent: supported version,
+    /// matching key pair and intact file checksums
+    pub fn verify(&self) -> Result<()> {
+        if self.format_version > BACKUP_FORMAT_VERSION {
+            return Err(DialogError::Backup(format!(
+                "Backup format version {} is newer than supported version {}",
+                self.format_version, BACKUP_FORMAT_VERSION
+            )));
+        }
+        self.keys()?;
+        for file in &self.files {
+            file.contents()?;
+        }
+        Ok(())
+    }
+
+    /// Write the stored files into `dir`.
+    ///
+    /// Only plain database names such as `mls.db` are accepted.
+    /// Refuses to replace existing files unless `overwrite` is set.
+    pub fn restore_files(&self, dir: &Path, overwrite: bool) -> Result<Vec<PathBuf>> {
+        // Validate everything before touching the filesystem
+        let mut contents = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            if !is_restorable_name(&file.name) {
+                return Err(DialogError::Backup(format!("Refusing to restore unsafe file name: {:?}", file.name)));
+            }
+            let target = dir.join(&file.name);
+            if target.exists() && !overwrite {
+                return Err(DialogError::Backup(format!(
+                    "{} already exists (use overwrite to replace it)",
+                    target.display()
+                )));
+            }
+            contents.push((target, file.contents()?));
+        }
+
+        std::fs::create_dir_all(dir)?;
+
+        // A stale WAL or shared-memory index next to a restored database
+        // would be applied to it on open, whether or not the database existed
+        for file in &self.files {
+            for suffix in ["-wal", "-shm"] {
+                let stale = dir.join(format!("{}{}", file.name, suffix));
+                if stale.exists() {
+                    std::fs::remove_file(&stale)?;
+                }
+            }
+        }
+
+        let mut written = Vec::with_capacity(contents.len());
+        for (target, bytes) in contents {
+            std::fs::write(&target, bytes)?;
+            written.push(target);
+        }
+        Ok(written)
+    }
+
+    /// Encrypt the archive with a passphrase
+    pub fn encrypt(&self, passphrase: &str) -> Result<Vec<u8>> {
+        self.encrypt_with_log_n(passphrase, DEFAULT_LOG_N)
+    }
+
+    fn encrypt_with_log_n(&self, passphrase: &str, log_n: u8) -> Result<Vec<u8>> {
+        if passphrase.is_empty() {
+            return Err(DialogError::Backup("Passphrase cannot be empty".into()));
+        }
+
+        let payload = serde_json::to_vec(self)
+            .map_err(|e| DialogError::Serialization(e.to_string()))?;
+
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(BACKUP_FORMAT_VERSION);
+        header.push(log_n);
+        header.extend_from_slice(&salt);
+        header.extend_from_slice(&nonce);
+
+        let cipher = cipher_for(passphrase, &salt, log_n)?;
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: &payload, aad: &header })
+            .map_err(|_| DialogError::Backup("Encryption failed".into()))?;
+
+        let mut out = header;
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Decrypt and verify an archive produced by [`BackupArchive::encrypt`]
+    pub fn decrypt(bytes: &[u8], passphrase: &str) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(DialogError::Backup("Not a dialog backup file".into()));
+        }
+
+        let version = bytes[MAGIC.len()];
+        if version > BACKUP_FORMAT_VERSION {
+            return Err(DialogError::Backup(format!(
+                "Backup format version {} is newer than supported version {}",
+                version, BACKUP_FORMAT_VERSION
+            )));
+        }
+
+        let log_n = bytes[MAGIC.len() + 1];
+        let salt_start = MAGIC.len() + 2;
+        let salt = &bytes[salt_start..salt_start + SALT_LEN];
+        let nonce = &bytes[salt_start + SALT_LEN..HEADER_LEN];
+        let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+
+        let cipher = cipher_for(passphrase, salt, log_n)?;
+        let payload = cipher
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+            .map_err(|_| DialogError::Backup("Wrong passphrase or corrupted backup".into()))?;
+
+        let archive: BackupArchive = serde_json::from_slice(&payload)
+            .map_err(|e| DialogError::Serialization(e.to_string()))?;
+        archive.verify()?;
+        Ok(archive)
+    }
+}
+
+/// Decrypt a backup and restore its storage files into `dir`
+pub fn restore_backup(bytes: &[u8], passphrase: &str, dir: &Path, overwrite: bool) -> Result<BackupArchive> {
+    let archive = BackupArchive::decrypt(bytes, passphrase)?;
+    archive.restore_files(dir, overwrite)?;
+    Ok(archive)
+}
+
+/// Write a transactionally consistent copy of a SQLite database to `target`
+async fn snapshot_sqlite(db_path: &Path, target: &Path) -> Result<()> {
+    let options = SqliteConnectOptions::new().filename(db_path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .map_err(|e| DialogError::Backup(format!("Failed to open {}: {}", db_path.display(), e)))?;
+    let target = target
+        .to_str()
+        .ok_or_else(|| DialogError::Backup(format!("Invalid backup path: {}", target.display())))?;
+    let result = sqlx::query("VACUUM INTO ?").bind(target).execute(&mut conn).await;
+    let _ = conn.close().await;
+    result
+        .map(|_| ())
+        .map_err(|e| DialogError::Backup(format!("Failed to snapshot {}: {}", db_path.display(), e)))
+}
+
+fn cipher_for(passphrase: &str, salt: &[u8], log_n: u8) -> Result<XChaCha20Poly1305> {
+    let params = scrypt::Params::new(log_n, 8, 1, 32)
+        .map_err(|e| DialogError::Backup(format!("Invalid key derivation parameters: {}", e)))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| DialogError::Backup(format!("Key derivation failed: {}", e)))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Whether an archived file may be written on restore: a plain SQLite
+/// database name such as `mls.db`, never a path, a hidden file or `.`/`..`
+fn is_restorable_name(name: &str) -> bool {
+    let Some(stem) = name.strip_suffix(".db") else {
+        return false;
+    };
+    !stem.is_empty()
+        && !stem.starts_with('.')
+        && stem.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Low scrypt cost keeps the tests fast
+    const TEST_LOG_N: u8 = 4;
+
+    fn sample_archive() -> (Keys, BackupArchive) {
+        let keys = Keys::generate();
+        let mut archive = BackupArchive::new(&keys, DialogConfig::with_relay_url("ws://custom.relay"));
+        archive.add_file_bytes("mls.db", b"sqlite bytes");
+        (keys, archive)
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let (keys, archive) = sample_archive();
+        let encrypted = archive.encrypt_with_log_n("correct horse", TEST_LOG_N).unwrap();
+
+        let restored = BackupArchive::decrypt(&encrypted, "correct horse").unwrap();
+        assert_eq!(restored.keys().unwrap().public_key(), keys.public_key());
+        assert_eq!(restored.config.relay_urls, vec!["ws://custom.relay".to_string()]);
+        assert_eq!(restored.files[0].contents().unwrap(), b"sqlite bytes");
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let (_, archive) = sample_archive();
+        let encrypted = archive.encrypt_with_log_n("correct horse", TEST_LOG_N).unwrap();
+        assert!(BackupArchive::decrypt(&encrypted, "battery staple").is_err());
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let (_, archive) = sample_archive();
+        let mut encrypted = archive.encrypt_with_log_n("correct horse", TEST_LOG_N).unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 0x01;
+        assert!(BackupArchive::decrypt(&encrypted, "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_restore_refuses_overwrite() {
+        let (_, archive) = sample_archive();
+        let dir = std::env::temp_dir().join(format!("dialog_backup_test_{}", archive.pubkey));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        archive.restore_files(&dir, false).unwrap();
+        assert!(archive.restore_files(&dir, false).is_err());
+        assert!(archive.restore_files(&dir, true).is_ok());
+        assert_eq!(std::fs::read(dir.join("mls.db")).unwrap(), b"sqlite bytes");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_restore_rejects_unknown_names_and_clears_stale_wal() {
+        for name in ["", ".", "..", ".db", "../mls.db", "dir/mls.db", "config.json", ".hidden.db"] {
+            let (_, mut archive) = sample_archive();
+            archive.files.clear();
+            archive.add_file_bytes(name, b"bytes");
+            let dir = std::env::temp_dir().join(format!("dialog_backup_names_{}", archive.pubkey));
+            assert!(archive.restore_files(&dir, true).is_err(), "{:?} must be refused", name);
+            assert!(!dir.exists());
+        }
+
+        // Without an existing database a leftover WAL is still removed
+        let (_, archive) = sample_archive();
+        let dir = std::env::temp_dir().join(format!("dialog_backup_wal_{}", archive.pubkey));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("mls.db-wal"), b"stale").unwrap();
+        archive.restore_files(&dir, false).unwrap();
+        assert!(!dir.join("mls.db-wal").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_snapshot_includes_wal_contents() {
+        let dir = std::env::temp_dir().join(format!("dialog_snapshot_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("mls.db");
+
+        // Keep the writer open so the row is still only in the write-ahead log
+        let options = SqliteConnectOptions::new()
+            .filename(&db_path)
+            .create_if_missing(true)
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+        let mut writer = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("CREATE TABLE t (v TEXT)").execute(&mut writer).await.unwrap();
+        sqlx::query("INSERT INTO t VALUES ('kept')").execute(&mut writer).await.unwrap();
+
+        let (_, mut archive) = sample_archive();
+        archive.files.clear();
+        archive.add_sqlite_database(&db_path).await.unwrap();
+        assert_eq!(archive.files.len(), 1);
+        writer.close().await.unwrap();
+
+        let restored = dir.join("restored");
+        archive.restore_files(&restored, false).unwrap();
+        let options = SqliteConnectOptions::new().filename(restored.join("mls.db"));
+        let mut reader = SqliteConnection::connect_with(&options).await.unwrap();
+        let (value,): (String,) = sqlx::query_as("SELECT v FROM t").fetch_one(&mut reader).await.unwrap();
+        assert_eq!(value, "kept");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
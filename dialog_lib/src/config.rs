@@ -1,8 +1,22 @@
+use crate::errors::{DialogError, Result};
+use crate::invite_policy::InvitePolicy;
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::Path;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DialogConfig {
     pub relay_urls: Vec<String>,
+    /// Let other group members know which messages we have read (opt-in)
+    #[serde(default)]
+    pub read_receipts: bool,
+    /// Also accept plain kind 444 welcomes, not only gift-wrapped ones
+    /// (compatibility with clients that send unwrapped welcomes)
+    #[serde(default)]
+    pub plain_welcomes: bool,
+    /// Which welcomes are joined, kept for approval, or dropped
+    #[serde(default)]
+    pub invite_policy: InvitePolicy,
 }
 
 impl Default for DialogConfig {
@@ -13,6 +27,9 @@ impl Default for DialogConfig {
                 "ws://localhost:8080".to_string(),
                 "ws://localhost:7777".to_string(),
             ],
+            read_receipts: false,
+            plain_welcomes: false,
+            invite_policy: InvitePolicy::default(),
         }
     }
 }
@@ -29,21 +46,60 @@ impl DialogConfig {
             Self::default().relay_urls
         };
 
+        let read_receipts = env::var("DIALOG_READ_RECEIPTS")
+            .map(|v| matches!(v.trim(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+
+        let plain_welcomes = env::var("DIALOG_PLAIN_WELCOMES")
+            .map(|v| matches!(v.trim(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+
         Self {
             relay_urls,
+            read_receipts,
+            plain_welcomes,
+            ..Self::default()
         }
     }
 
     pub fn with_relay_url(relay_url: impl Into<String>) -> Self {
         Self {
             relay_urls: vec![relay_url.into()],
+            ..Self::default()
         }
     }
 
     pub fn with_relay_urls(relay_urls: Vec<String>) -> Self {
         Self {
             relay_urls,
+            ..Self::default()
+        }
+    }
+
+    /// Load a per-identity config file, falling back to the environment if it does not exist.
+    /// `DIALOG_RELAY_URLS` still overrides the relays saved in the file.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::from_env());
+        }
+        let content = std::fs::read_to_string(path)?;
+        let mut config: Self = serde_json::from_str(&content)
+            .map_err(|e| DialogError::Serialization(format!("Failed to parse {}: {}", path.display(), e)))?;
+        if env::var("DIALOG_RELAY_URLS").is_ok() {
+            config.relay_urls = Self::from_env().relay_urls;
+        }
+        Ok(config)
+    }
+
+    /// Save this config as a per-identity config file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| DialogError::Serialization(e.to_string()))?;
+        std::fs::write(path, content)?;
+        Ok(())
     }
 }
 
@@ -66,5 +122,22 @@ mod tests {
     fn test_with_relay_url() {
         let config = DialogConfig::with_relay_url("ws://custom.relay");
         assert_eq!(config.relay_urls, vec!["ws://custom.relay".to_string()]);
+        assert!(!config.read_receipts);
+        assert!(!config.plain_welcomes);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("dialog_config_{}.json", std::process::id()));
+        let mut config = DialogConfig::with_relay_url("ws://custom.relay");
+        config.read_receipts = true;
+        config.save(&path).unwrap();
+
+        let loaded = DialogConfig::load(&path).unwrap();
+        assert_eq!(loaded.relay_urls, config.relay_urls);
+        assert!(loaded.read_receipts);
+        assert_eq!(loaded.invite_policy, config.invite_policy);
+
+        let _ = std::fs::remove_file(&path);
     }
 }
\ No newline at end of file
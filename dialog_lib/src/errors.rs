@@ -26,6 +26,15 @@ pub enum DialogError {
     #[error("Storage error: {0}")]
     Storage(String),
     
+    #[error("Backup error: {0}")]
+    Backup(String),
+    
+    #[error("Keystore error: {0}")]
+    Keystore(String),
+    
+    #[error("Relay authentication failed: {0}")]
+    Auth(String),
+    
     #[error("General error: {0}")]
     General(#[from] Box<dyn std::error::Error + Send + Sync>),
     
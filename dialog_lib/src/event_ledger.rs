@@ -0,0 +1,274 @@
+//! Persistent record of the group events we have processed.
+//!
+//! Relays hand us the same group message again on every fetch and on every
+//! resubscribe, and MLS cannot process an event twice (nor decrypt our own).
+//! The ledger remembers each event's outcome so it is only applied once, also
+//! across restarts. It is a table in the SQLite database of the MLS storage
+//! (an in-memory database with memory storage), so each outcome is one row
+//! written without rewriting the rest, and backups include it.
+
+use crate::errors::{DialogError, Result};
+use crate::storage::StorageBackend;
+use nostr_mls::prelude::*;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How long a failure is kept. Relays deliver the event again, so one that
+/// still fails is simply recorded anew.
+pub const FAILED_RETENTION_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// What happened when a group event was processed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventOutcome {
+    /// Processed into the group state
+    Applied,
+    /// Sent by us; already applied when it was created
+    OwnMessage,
+    /// Could not be processed; tried again on the next fetch
+    Failed { reason: String },
+}
+
+impl EventOutcome {
+    /// Whether the event needs no further processing
+    pub fn is_final(&self) -> bool {
+        !matches!(self, EventOutcome::Failed { .. })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            EventOutcome::Applied => "applied",
+            EventOutcome::OwnMessage => "own_message",
+            EventOutcome::Failed { .. } => "failed",
+        }
+    }
+
+    fn from_row(name: &str, reason: Option<String>) -> Self {
+        match name {
+            "applied" => EventOutcome::Applied,
+            "own_message" => EventOutcome::OwnMessage,
+            _ => EventOutcome::Failed { reason: reason.unwrap_or_default() },
+        }
+    }
+}
+
+/// Ledger of processed group events
+#[derive(Debug, Clone)]
+pub struct EventLedger {
+    pool: SqlitePool,
+}
+
+impl EventLedger {
+    /// Open the ledger belonging to a storage backend
+    pub async fn for_backend(backend: &StorageBackend) -> Result<Self> {
+        match backend {
+            StorageBackend::Memory => Self::in_memory().await,
+            StorageBackend::Sqlite { path } => Self::open(path).await,
+        }
+    }
+
+    /// Open the ledger table in the SQLite database at `db_path`
+    pub async fn open(db_path: &Path) -> Result<Self> {
+        let options = SqliteConnectOptions::new()
+            .filename(db_path)
+            .create_if_missing(true)
+            .busy_timeout(Duration::from_secs(5));
+        let pool = SqlitePoolOptions::new()
+            .max_connections(2)
+            .connect_with(options)
+            .await
+            .map_err(|e| DialogError::Storage(format!("Failed to open {}: {}", db_path.display(), e)))?;
+        Self::with_pool(pool).await
+    }
+
+    /// A ledger that only lives as long as the process
+    pub async fn in_memory() -> Result<Self> {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:").map_err(ledger_error)?;
+        // Every connection would get its own database; keep exactly one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(options)
+            .await
+            .map_err(ledger_error)?;
+        Self::with_pool(pool).await
+    }
+
+    async fn with_pool(pool: SqlitePool) -> Result<Self> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS dialog_event_ledger (
+                event_id TEXT PRIMARY KEY,
+                group_id TEXT NOT NULL,
+                outcome TEXT NOT NULL,
+                reason TEXT,
+                recorded_at INTEGER NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await
+        .map_err(ledger_error)?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS dialog_event_ledger_group ON dialog_event_ledger (group_id)")
+            .execute(&pool)
+            .await
+            .map_err(ledger_error)?;
+        Ok(Self { pool })
+    }
+
+    /// Record the outcome of processing an event. A final outcome is never
+    /// replaced by a later failure.
+    pub async fn record(&self, group_id: &GroupId, event_id: &EventId, outcome: EventOutcome) -> Result<()> {
+        let reason = match &outcome {
+            EventOutcome::Failed { reason } => Some(reason.as_str()),
+            _ => None,
+        };
+        sqlx::query(
+            "INSERT INTO dialog_event_ledger (event_id, group_id, outcome, reason, recorded_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT (event_id) DO UPDATE SET
+                group_id = excluded.group_id,
+                outcome = excluded.outcome,
+                reason = excluded.reason,
+                recorded_at = excluded.recorded_at
+             WHERE dialog_event_ledger.outcome = 'failed' OR excluded.outcome != 'failed'",
+        )
+        .bind(event_id.to_hex())
+        .bind(hex::encode(group_id.as_slice()))
+        .bind(outcome.name())
+        .bind(reason)
+        .bind(chrono::Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(ledger_error)?;
+        Ok(())
+    }
+
+    pub async fn outcome(&self, event_id: &EventId) -> Result<Option<EventOutcome>> {
+        let row: Option<(String, Option<String>)> =
+            sqlx::query_as("SELECT outcome, reason FROM dialog_event_ledger WHERE event_id = ?")
+                .bind(event_id.to_hex())
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(ledger_error)?;
+        Ok(row.map(|(name, reason)| EventOutcome::from_row(&name, reason)))
+    }
+
+    /// Whether the event was applied or is our own, so must not be processed again
+    pub async fn is_done(&self, event_id: &EventId) -> Result<bool> {
+        Ok(self.outcome(event_id).await?.is_some_and(|outcome| outcome.is_final()))
+    }
+
+    /// Events of a group that could not be processed, with the reason
+    pub async fn failures(&self, group_id: &GroupId) -> Result<Vec<(String, String)>> {
+        sqlx::query_as(
+            "SELECT event_id, COALESCE(reason, '') FROM dialog_event_ledger
+             WHERE group_id = ? AND outcome = 'failed' ORDER BY event_id",
+        )
+        .bind(hex::encode(group_id.as_slice()))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(ledger_error)
+    }
+
+    /// Forget the events of groups not in `groups` and failures older than
+    /// [`FAILED_RETENTION_SECS`]. Returns how many entries were removed.
+    pub async fn prune(&self, groups: &[GroupId], now: i64) -> Result<u64> {
+        let kept: HashSet<String> = groups.iter().map(|id| hex::encode(id.as_slice())).collect();
+        let recorded: Vec<(String,)> = sqlx::query_as("SELECT DISTINCT group_id FROM dialog_event_ledger")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(ledger_error)?;
+
+        let mut removed = 0;
+        for (group_id,) in recorded.into_iter().filter(|(id,)| !kept.contains(id)) {
+            removed += sqlx::query("DELETE FROM dialog_event_ledger WHERE group_id = ?")
+                .bind(group_id)
+                .execute(&self.pool)
+                .await
+                .map_err(ledger_error)?
+                .rows_affected();
+        }
+        removed += sqlx::query("DELETE FROM dialog_event_ledger WHERE outcome = 'failed' AND recorded_at <= ?")
+            .bind(now - FAILED_RETENTION_SECS)
+            .execute(&self.pool)
+            .await
+            .map_err(ledger_error)?
+            .rows_affected();
+        Ok(removed)
+    }
+}
+
+fn ledger_error(e: sqlx::Error) -> DialogError {
+    DialogError::Storage(format!("Event ledger: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_id(byte: u8) -> EventId {
+        EventId::from_slice(&[byte; 32]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_retried_until_applied() {
+        let group_id = GroupId::from_slice(&[1u8; 16]);
+        let ledger = EventLedger::in_memory().await.unwrap();
+        assert_eq!(ledger.outcome(&event_id(1)).await.unwrap(), None);
+
+        ledger.record(&group_id, &event_id(1), EventOutcome::Failed { reason: "wrong epoch".into() }).await.unwrap();
+        assert!(!ledger.is_done(&event_id(1)).await.unwrap());
+        assert_eq!(
+            ledger.failures(&group_id).await.unwrap(),
+            vec![(event_id(1).to_hex(), "wrong epoch".to_string())]
+        );
+
+        ledger.record(&group_id, &event_id(1), EventOutcome::Applied).await.unwrap();
+        assert!(ledger.is_done(&event_id(1)).await.unwrap());
+        assert!(ledger.failures(&group_id).await.unwrap().is_empty());
+
+        // Reprocessing an applied event fails, which must not undo the record
+        ledger.record(&group_id, &event_id(1), EventOutcome::Failed { reason: "already processed".into() }).await.unwrap();
+        assert_eq!(ledger.outcome(&event_id(1)).await.unwrap(), Some(EventOutcome::Applied));
+    }
+
+    #[tokio::test]
+    async fn test_persists_across_reopening() {
+        let dir = std::env::temp_dir().join(format!("dialog_ledger_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let backend = StorageBackend::Sqlite { path: dir.join("mls.db") };
+        let group_id = GroupId::from_slice(&[2u8; 16]);
+
+        let ledger = EventLedger::for_backend(&backend).await.unwrap();
+        ledger.record(&group_id, &event_id(2), EventOutcome::OwnMessage).await.unwrap();
+        drop(ledger);
+
+        let reopened = EventLedger::for_backend(&backend).await.unwrap();
+        assert_eq!(reopened.outcome(&event_id(2)).await.unwrap(), Some(EventOutcome::OwnMessage));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_prune_removes_old_failures_and_gone_groups() {
+        let ledger = EventLedger::in_memory().await.unwrap();
+        let kept = GroupId::from_slice(&[1u8; 16]);
+        let gone = GroupId::from_slice(&[2u8; 16]);
+        ledger.record(&kept, &event_id(1), EventOutcome::Applied).await.unwrap();
+        ledger.record(&kept, &event_id(2), EventOutcome::Failed { reason: "bad".into() }).await.unwrap();
+        ledger.record(&gone, &event_id(3), EventOutcome::Applied).await.unwrap();
+
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(ledger.prune(std::slice::from_ref(&kept), now).await.unwrap(), 1);
+        assert_eq!(ledger.outcome(&event_id(3)).await.unwrap(), None);
+        assert_eq!(ledger.failures(&kept).await.unwrap().len(), 1);
+
+        assert_eq!(ledger.prune(std::slice::from_ref(&kept), now + FAILED_RETENTION_SECS).await.unwrap(), 1);
+        assert!(ledger.failures(&kept).await.unwrap().is_empty());
+        assert!(ledger.is_done(&event_id(1)).await.unwrap());
+    }
+}
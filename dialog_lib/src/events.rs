@@ -0,0 +1,85 @@
+//! Delivery of [`UiUpdate`]s to library consumers.
+//!
+//! Every update goes to a broadcast channel that any number of consumers can
+//! follow as a stream (see `DialogLib::events`), and to the single `mpsc`
+//! sender registered through `subscribe_to_groups`, if any.
+
+use crate::types::UiUpdate;
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, RwLock};
+
+/// Updates a slow stream consumer may fall behind before it misses some
+const BROADCAST_CAPACITY: usize = 256;
+
+#[derive(Debug)]
+pub(crate) struct EventChannel {
+    sender: RwLock<Option<mpsc::Sender<UiUpdate>>>,
+    broadcast: broadcast::Sender<UiUpdate>,
+}
+
+impl EventChannel {
+    pub fn new() -> Self {
+        Self {
+            sender: RwLock::new(None),
+            broadcast: broadcast::channel(BROADCAST_CAPACITY).0,
+        }
+    }
+
+    /// Register the `mpsc` consumer, replacing the previous one
+    pub async fn set_sender(&self, sender: mpsc::Sender<UiUpdate>) {
+        *self.sender.write().await = Some(sender);
+    }
+
+    /// Deliver an update; returns whether anyone received it
+    pub async fn send(&self, update: UiUpdate) -> bool {
+        let streamed = self.broadcast.send(update.clone()).is_ok();
+        let sender = self.sender.read().await.clone();
+        let sent = match sender {
+            Some(sender) => sender.send(update).await.is_ok(),
+            None => false,
+        };
+        streamed || sent
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<UiUpdate> {
+        self.broadcast.subscribe()
+    }
+}
+
+/// Stream of updates from a broadcast receiver. A consumer that falls too far
+/// behind skips the updates it missed rather than ending the stream.
+pub(crate) fn into_stream(receiver: broadcast::Receiver<UiUpdate>) -> BoxStream<'static, UiUpdate> {
+    stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(update) => return Some((update, receiver)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ConnectionStatus;
+
+    #[tokio::test]
+    async fn test_every_consumer_gets_every_update() {
+        let channel = EventChannel::new();
+        assert!(!channel.send(UiUpdate::ConnectionStatus(ConnectionStatus::Connecting)).await);
+
+        let mut first = into_stream(channel.subscribe());
+        let mut second = into_stream(channel.subscribe());
+        let (tx, mut rx) = mpsc::channel(10);
+        channel.set_sender(tx).await;
+
+        assert!(channel.send(UiUpdate::ConnectionStatus(ConnectionStatus::Connected)).await);
+        for update in [first.next().await, second.next().await, rx.recv().await] {
+            assert!(matches!(update, Some(UiUpdate::ConnectionStatus(ConnectionStatus::Connected))));
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! References to groups as users and other clients write them.
+//!
+//! A group can be named by its MLS group ID (32 hex characters), its Nostr
+//! group ID from the `h` tag (64 hex characters), its name, or a unique prefix
+//! of any of these. [`GroupRef`] parses all of these forms; the service
+//! resolves it against stored groups or pending invites.
+
+use crate::errors::{DialogError, Result};
+use crate::types::Conversation;
+use nostr_mls::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// Shortest hex prefix accepted as an abbreviated group ID
+const MIN_PREFIX_LEN: usize = 4;
+
+/// A reference to a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupRef {
+    /// MLS group ID
+    Mls(GroupId),
+    /// Nostr group ID (the `h` tag value of group messages)
+    Nostr([u8; 32]),
+    /// Group name, or a prefix of a name or of either hex ID
+    Name(String),
+}
+
+impl GroupRef {
+    /// Parse user input; full-length hex IDs become `Mls`/`Nostr`, anything else `Name`
+    pub fn parse(input: &str) -> Self {
+        let input = input.trim();
+        let is_hex = !input.is_empty() && input.chars().all(|c| c.is_ascii_hexdigit());
+        if is_hex {
+            if let Ok(bytes) = hex::decode(input) {
+                match bytes.len() {
+                    16 => return GroupRef::Mls(GroupId::from_slice(&bytes)),
+                    32 => {
+                        let mut id = [0u8; 32];
+                        id.copy_from_slice(&bytes);
+                        return GroupRef::Nostr(id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        GroupRef::Name(input.to_string())
+    }
+
+    /// Pick the candidate this reference names.
+    ///
+    /// `key` returns the MLS group ID, Nostr group ID and name of a candidate.
+    /// Exact IDs and names win over prefixes; a prefix matching more than one
+    /// candidate is an error rather than a guess.
+    pub fn resolve<'a, T>(
+        &self,
+        candidates: &'a [T],
+        key: impl Fn(&T) -> (&GroupId, &[u8], &str),
+    ) -> Result<&'a T> {
+        let found = match self {
+            GroupRef::Mls(group_id) => candidates.iter().find(|&c| key(c).0 == group_id),
+            GroupRef::Nostr(nostr_id) => candidates.iter().find(|&c| key(c).1 == nostr_id.as_slice()),
+            GroupRef::Name(query) => return self.resolve_name(query, candidates, key),
+        };
+        found.ok_or_else(|| DialogError::General(format!("Group not found: {}", self).into()))
+    }
+
+    fn resolve_name<'a, T>(
+        &self,
+        query: &str,
+        candidates: &'a [T],
+        key: impl Fn(&T) -> (&GroupId, &[u8], &str),
+    ) -> Result<&'a T> {
+        if query.is_empty() {
+            return Err(DialogError::General("Empty group reference".into()));
+        }
+        let query_lower = query.to_lowercase();
+
+        let exact: Vec<&T> = candidates
+            .iter()
+            .filter(|&c| key(c).2.to_lowercase() == query_lower)
+            .collect();
+        let matches = if exact.is_empty() {
+            let hex_prefix = query.len() >= MIN_PREFIX_LEN && query.chars().all(|c| c.is_ascii_hexdigit());
+            candidates
+                .iter()
+                .filter(|&c| {
+                    let (mls_id, nostr_id, name) = key(c);
+                    name.to_lowercase().starts_with(&query_lower)
+                        || (hex_prefix
+                            && (hex::encode(mls_id.as_slice()).starts_with(&query_lower)
+                                || hex::encode(nostr_id).starts_with(&query_lower)))
+                })
+                .collect()
+        } else {
+            exact
+        };
+
+        match matches.as_slice() {
+            [single] => Ok(*single),
+            [] => Err(DialogError::General(format!("Group not found: {}", query).into())),
+            several => {
+                let names: Vec<String> = several
+                    .iter()
+                    .map(|&c| {
+                        let (mls_id, _, name) = key(c);
+                        format!("{} ({})", name, &hex::encode(mls_id.as_slice())[..8])
+                    })
+                    .collect();
+                Err(DialogError::General(
+                    format!("'{}' matches several groups: {}", query, names.join(", ")).into(),
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for GroupRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupRef::Mls(group_id) => write!(f, "{}", hex::encode(group_id.as_slice())),
+            GroupRef::Nostr(nostr_id) => write!(f, "{}", hex::encode(nostr_id)),
+            GroupRef::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for GroupRef {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(GroupRef::parse(s))
+    }
+}
+
+impl From<GroupId> for GroupRef {
+    fn from(group_id: GroupId) -> Self {
+        GroupRef::Mls(group_id)
+    }
+}
+
+impl From<&GroupId> for GroupRef {
+    fn from(group_id: &GroupId) -> Self {
+        GroupRef::Mls(group_id.clone())
+    }
+}
+
+impl From<&str> for GroupRef {
+    fn from(input: &str) -> Self {
+        GroupRef::parse(input)
+    }
+}
+
+impl From<String> for GroupRef {
+    fn from(input: String) -> Self {
+        GroupRef::parse(&input)
+    }
+}
+
+impl From<&String> for GroupRef {
+    fn from(input: &String) -> Self {
+        GroupRef::parse(input)
+    }
+}
+
+impl From<&GroupRef> for GroupRef {
+    fn from(group: &GroupRef) -> Self {
+        group.clone()
+    }
+}
+
+impl From<&Conversation> for GroupRef {
+    fn from(conversation: &Conversation) -> Self {
+        match &conversation.group_id {
+            Some(group_id) => GroupRef::Mls(group_id.clone()),
+            None => GroupRef::parse(&conversation.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Candidate {
+        mls_id: GroupId,
+        nostr_id: [u8; 32],
+        name: &'static str,
+    }
+
+    fn candidates() -> Vec<Candidate> {
+        vec![
+            Candidate { mls_id: GroupId::from_slice(&[0xab; 16]), nostr_id: [0x11; 32], name: "Team" },
+            Candidate { mls_id: GroupId::from_slice(&[0xac; 16]), nostr_id: [0x22; 32], name: "Team Alpha" },
+            Candidate { mls_id: GroupId::from_slice(&[0xcd; 16]), nostr_id: [0x33; 32], name: "Family" },
+        ]
+    }
+
+    fn resolve<'a>(input: &str, candidates: &'a [Candidate]) -> Result<&'a Candidate> {
+        GroupRef::parse(input).resolve(candidates, |c| (&c.mls_id, c.nostr_id.as_slice(), c.name))
+    }
+
+    #[test]
+    fn test_parse_forms() {
+        assert_eq!(GroupRef::parse(&"ab".repeat(16)), GroupRef::Mls(GroupId::from_slice(&[0xab; 16])));
+        assert_eq!(GroupRef::parse(&"11".repeat(32)), GroupRef::Nostr([0x11; 32]));
+        assert_eq!(GroupRef::parse(" Team "), GroupRef::Name("Team".to_string()));
+        assert_eq!(GroupRef::parse("abcd"), GroupRef::Name("abcd".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_ids_names_and_prefixes() {
+        let groups = candidates();
+        assert_eq!(resolve(&"cd".repeat(16), &groups).unwrap().name, "Family");
+        assert_eq!(resolve(&"22".repeat(32), &groups).unwrap().name, "Team Alpha");
+        // An exact name wins over a name prefix shared with another group
+        assert_eq!(resolve("team", &groups).unwrap().name, "Team");
+        assert_eq!(resolve("fam", &groups).unwrap().name, "Family");
+        assert_eq!(resolve("acac", &groups).unwrap().name, "Team Alpha");
+        assert_eq!(resolve("3333", &groups).unwrap().name, "Family");
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_or_missing() {
+        let groups = candidates();
+        assert!(resolve("Te", &groups).is_err());
+        assert!(resolve("nope", &groups).is_err());
+        assert!(resolve(&"ef".repeat(16), &groups).is_err());
+        assert!(resolve("", &groups).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Ephemeral group signals such as typing indicators.
+//!
+//! Sent as MLS application messages they would be kept by relays and in every
+//! member's message store. Instead the signed inner event is NIP-44 encrypted
+//! to the group's exporter secret, the key that also protects the outer layer
+//! of kind 445 events, and published with an ephemeral kind.
+
+use crate::errors::{DialogError, Result};
+use crate::types::nostr_kinds;
+use nostr::nips::nip44;
+use nostr_mls::prelude::*;
+
+/// Content of a signal saying the sender started typing
+pub const TYPING_STARTED: &str = "typing";
+/// Content of a signal saying the sender stopped typing
+pub const TYPING_STOPPED: &str = "typing-stopped";
+
+/// Wrap a signed event for the group with the given exporter secret
+pub fn seal(secret: &[u8; 32], nostr_group_id: &[u8; 32], inner: &Event) -> Result<Event> {
+    let keys = exporter_keys(secret)?;
+    let content = nip44::encrypt(keys.secret_key(), &keys.public_key(), inner.as_json(), nip44::Version::V2)
+        .map_err(|e| DialogError::General(format!("Failed to encrypt group signal: {}", e).into()))?;
+
+    // A throwaway key signs the outer event, as for kind 445
+    EventBuilder::new(Kind::Custom(nostr_kinds::GROUP_SIGNAL), content)
+        .tag(Tag::custom(
+            TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::H)),
+            [hex::encode(nostr_group_id)],
+        ))
+        .sign_with_keys(&Keys::generate())
+        .map_err(|e| DialogError::General(Box::new(e)))
+}
+
+/// Unwrap a group signal; `None` unless it decrypts to a correctly signed event
+pub fn open(secret: &[u8; 32], outer: &Event) -> Option<Event> {
+    let keys = exporter_keys(secret).ok()?;
+    let json = nip44::decrypt(keys.secret_key(), &keys.public_key(), &outer.content).ok()?;
+    let inner = Event::from_json(json).ok()?;
+    inner.verify().ok()?;
+    Some(inner)
+}
+
+fn exporter_keys(secret: &[u8; 32]) -> Result<Keys> {
+    let secret_key = SecretKey::from_slice(secret).map_err(|e| DialogError::General(Box::new(e)))?;
+    Ok(Keys::new(secret_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let secret = [7u8; 32];
+        let member = Keys::generate();
+        let inner = EventBuilder::new(Kind::Custom(nostr_kinds::GROUP_SIGNAL), TYPING_STARTED)
+            .sign_with_keys(&member)
+            .unwrap();
+
+        let outer = seal(&secret, &[1u8; 32], &inner).unwrap();
+        assert_eq!(outer.kind, Kind::Custom(nostr_kinds::GROUP_SIGNAL));
+        assert!(outer.kind.is_ephemeral());
+        assert_ne!(outer.pubkey, member.public_key());
+
+        let opened = open(&secret, &outer).unwrap();
+        assert_eq!(opened.id, inner.id);
+        assert!(open(&[8u8; 32], &outer).is_none(), "Other groups cannot read the signal");
+    }
+}
@@ -0,0 +1,259 @@
+//! Deciding what to do with incoming welcomes.
+//!
+//! Anyone can send us a welcome, so every welcome passes the [`InvitePolicy`]
+//! before it is processed: welcomes from blocked pubkeys are dropped, welcomes
+//! from contacts can be joined right away, and everything else waits for the
+//! user — up to a per-inviter cap, past which further welcomes are dropped.
+
+use crate::errors::Result;
+use crate::json_store::JsonStore;
+use crate::storage::StorageBackend;
+use nostr_mls::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+
+/// Filters for welcomes addressed to `pubkey`: gift wraps, plus plain kind 444
+/// welcomes when that compatibility path is enabled
+pub fn welcome_filters(pubkey: PublicKey, plain_welcomes: bool) -> Vec<Filter> {
+    let mut filters = vec![Filter::new().kind(Kind::GiftWrap).pubkey(pubkey)];
+    if plain_welcomes {
+        filters.push(Filter::new().kind(Kind::MlsWelcome).pubkey(pubkey));
+    }
+    filters
+}
+
+/// Whether a welcome (or the gift wrap carrying it) is `p`-tagged for `pubkey`
+pub fn is_addressed_to(event: &Event, pubkey: &PublicKey) -> bool {
+    event.tags.public_keys().any(|tagged| tagged == pubkey)
+}
+
+/// Identifies a welcome by its MLS payload, so the gift-wrapped and plain
+/// copies of one welcome are recognised as the same invite
+pub fn welcome_key(content: &str) -> [u8; 32] {
+    Sha256::digest(content.as_bytes()).into()
+}
+
+/// How incoming welcomes are handled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvitePolicy {
+    /// Join groups a contact invites us to without asking
+    pub auto_accept_contacts: bool,
+    /// Welcomes from these pubkeys are dropped unprocessed
+    pub blocked: BTreeSet<PublicKey>,
+    /// Most invites kept pending per inviter within `window_secs`
+    pub max_pending_per_inviter: usize,
+    /// Window the per-inviter cap is counted over, in seconds
+    pub window_secs: i64,
+}
+
+impl Default for InvitePolicy {
+    fn default() -> Self {
+        Self {
+            auto_accept_contacts: true,
+            blocked: BTreeSet::new(),
+            max_pending_per_inviter: 3,
+            window_secs: 3600,
+        }
+    }
+}
+
+/// What happened to an incoming welcome
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InviteDecision {
+    /// Joined automatically
+    Accepted,
+    /// Kept as a pending invite for the user to accept
+    Pending,
+    /// Discarded without processing
+    Dropped(DropReason),
+}
+
+/// Why a welcome was dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DropReason {
+    /// The inviter is blocked
+    Blocked,
+    /// The inviter sent too many invites recently
+    RateLimited,
+}
+
+impl fmt::Display for InviteDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InviteDecision::Accepted => write!(f, "accepted automatically"),
+            InviteDecision::Pending => write!(f, "waiting for approval"),
+            InviteDecision::Dropped(DropReason::Blocked) => write!(f, "dropped (inviter is blocked)"),
+            InviteDecision::Dropped(DropReason::RateLimited) => write!(f, "dropped (too many invites from inviter)"),
+        }
+    }
+}
+
+/// Applies an [`InvitePolicy`] and remembers what it decided.
+///
+/// Welcomes show up both on the live subscription and when invites are
+/// listed, so kept welcomes are remembered by [`welcome_key`] and only count
+/// once against their inviter's cap. Dropped welcomes are not remembered: they are
+/// judged again next time, under whatever the policy is then.
+///
+/// Decisions and rate-limit windows are saved beside the MLS storage, so a
+/// restart neither resets an inviter's budget nor asks about a welcome again.
+#[derive(Debug, Default)]
+pub struct InviteGate {
+    store: JsonStore<GateState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GateState {
+    /// Decisions by hex welcome key
+    decided: HashMap<String, InviteDecision>,
+    /// When each inviter's kept welcomes arrived, oldest first
+    pending_by_inviter: HashMap<PublicKey, VecDeque<i64>>,
+}
+
+impl InviteGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the gate state belonging to a storage backend
+    pub fn for_backend(backend: &StorageBackend) -> Result<Self> {
+        Ok(Self { store: JsonStore::for_backend(backend, "invites.json")? })
+    }
+
+    /// Decide on the welcome with key `welcome` from `inviter`, received at `now`
+    pub fn decide(
+        &mut self,
+        policy: &InvitePolicy,
+        welcome: [u8; 32],
+        inviter: PublicKey,
+        is_contact: bool,
+        now: i64,
+    ) -> Result<InviteDecision> {
+        if policy.blocked.contains(&inviter) {
+            return Ok(InviteDecision::Dropped(DropReason::Blocked));
+        }
+        let key = hex::encode(welcome);
+        let state = self.store.get_mut();
+        if let Some(decision) = state.decided.get(&key) {
+            return Ok(decision.clone());
+        }
+
+        let decision = if is_contact && policy.auto_accept_contacts {
+            InviteDecision::Accepted
+        } else {
+            let recent = state.pending_by_inviter.entry(inviter).or_default();
+            while recent.front().is_some_and(|&at| now - at >= policy.window_secs) {
+                recent.pop_front();
+            }
+            if recent.len() >= policy.max_pending_per_inviter {
+                return Ok(InviteDecision::Dropped(DropReason::RateLimited));
+            }
+            recent.push_back(now);
+            InviteDecision::Pending
+        };
+        state.decided.insert(key, decision.clone());
+        self.store.save()?;
+        Ok(decision)
+    }
+
+    /// Whether the welcome with key `welcome` was already kept (and so already processed)
+    pub fn is_decided(&self, welcome: &[u8; 32]) -> bool {
+        self.store.get().decided.contains_key(&hex::encode(welcome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u8) -> [u8; 32] {
+        [n; 32]
+    }
+
+    #[test]
+    fn test_welcome_filters_and_addressing() {
+        let keys = Keys::generate();
+        let me = Keys::generate().public_key();
+        let someone_else = Keys::generate().public_key();
+
+        let kinds = |plain: bool| -> Vec<Kind> {
+            welcome_filters(me, plain)
+                .iter()
+                .flat_map(|f| f.kinds.clone().unwrap_or_default())
+                .collect()
+        };
+        assert_eq!(kinds(false), vec![Kind::GiftWrap]);
+        assert_eq!(kinds(true), vec![Kind::GiftWrap, Kind::MlsWelcome]);
+        assert!(welcome_filters(me, true).iter().all(|f| f.generic_tags.contains_key(&SingleLetterTag::lowercase(Alphabet::P))));
+
+        let ours = EventBuilder::new(Kind::MlsWelcome, "").tag(Tag::public_key(me)).sign_with_keys(&keys).unwrap();
+        let theirs = EventBuilder::new(Kind::MlsWelcome, "").tag(Tag::public_key(someone_else)).sign_with_keys(&keys).unwrap();
+        let untagged = EventBuilder::new(Kind::MlsWelcome, "").sign_with_keys(&keys).unwrap();
+        assert!(is_addressed_to(&ours, &me));
+        assert!(!is_addressed_to(&theirs, &me));
+        assert!(!is_addressed_to(&untagged, &me));
+        assert_eq!(welcome_key("payload"), welcome_key("payload"));
+        assert_ne!(welcome_key("payload"), welcome_key("other"));
+    }
+
+    #[test]
+    fn test_contacts_strangers_and_blocked() {
+        let friend = Keys::generate().public_key();
+        let stranger = Keys::generate().public_key();
+        let mut policy = InvitePolicy::default();
+        policy.blocked.insert(stranger);
+        let mut gate = InviteGate::new();
+
+        assert_eq!(gate.decide(&policy, id(1), friend, true, 0).unwrap(), InviteDecision::Accepted);
+        assert_eq!(gate.decide(&policy, id(2), stranger, false, 0).unwrap(), InviteDecision::Dropped(DropReason::Blocked));
+
+        policy.blocked.clear();
+        assert_eq!(gate.decide(&policy, id(2), stranger, false, 0).unwrap(), InviteDecision::Pending);
+
+        policy.auto_accept_contacts = false;
+        assert_eq!(gate.decide(&policy, id(3), friend, true, 0).unwrap(), InviteDecision::Pending);
+        // A welcome seen again keeps its first decision
+        assert_eq!(gate.decide(&policy, id(1), friend, true, 0).unwrap(), InviteDecision::Accepted);
+    }
+
+    #[test]
+    fn test_per_inviter_cap() {
+        let spammer = Keys::generate().public_key();
+        let other = Keys::generate().public_key();
+        let policy = InvitePolicy { max_pending_per_inviter: 2, window_secs: 100, ..Default::default() };
+        let mut gate = InviteGate::new();
+
+        assert_eq!(gate.decide(&policy, id(1), spammer, false, 0).unwrap(), InviteDecision::Pending);
+        assert_eq!(gate.decide(&policy, id(2), spammer, false, 10).unwrap(), InviteDecision::Pending);
+        assert_eq!(gate.decide(&policy, id(3), spammer, false, 20).unwrap(), InviteDecision::Dropped(DropReason::RateLimited));
+        // Re-seen welcomes do not count again
+        assert_eq!(gate.decide(&policy, id(1), spammer, false, 30).unwrap(), InviteDecision::Pending);
+        // Other inviters have their own budget
+        assert_eq!(gate.decide(&policy, id(4), other, false, 30).unwrap(), InviteDecision::Pending);
+        // The dropped welcome gets through once the window has moved on
+        assert_eq!(gate.decide(&policy, id(3), spammer, false, 100).unwrap(), InviteDecision::Pending);
+    }
+
+    #[test]
+    fn test_gate_survives_restart() {
+        let path = std::env::temp_dir().join(format!("dialog_invites_{}.db", std::process::id()));
+        let backend = StorageBackend::Sqlite { path: path.clone() };
+        let spammer = Keys::generate().public_key();
+        let policy = InvitePolicy { max_pending_per_inviter: 1, ..Default::default() };
+
+        let mut gate = InviteGate::for_backend(&backend).unwrap();
+        assert_eq!(gate.decide(&policy, id(1), spammer, false, 0).unwrap(), InviteDecision::Pending);
+
+        let mut gate = InviteGate::for_backend(&backend).unwrap();
+        assert!(gate.is_decided(&id(1)));
+        assert_eq!(
+            gate.decide(&policy, id(2), spammer, false, 10).unwrap(),
+            InviteDecision::Dropped(DropReason::RateLimited)
+        );
+
+        let _ = std::fs::remove_file(path.with_extension("invites.json"));
+    }
+}
@@ -0,0 +1,66 @@
+//! Small JSON files for state kept beside the MLS storage.
+//!
+//! With SQLite storage each file sits next to the database (same stem, its own
+//! extension); with memory storage the state only lives in the process, like
+//! the MLS state itself.
+
+use crate::errors::{DialogError, Result};
+use crate::storage::StorageBackend;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Default)]
+pub(crate) struct JsonStore<T> {
+    path: Option<PathBuf>,
+    value: T,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> JsonStore<T> {
+    /// Open the store belonging to a storage backend, e.g. `mls.outbox.json` for `mls.db`
+    pub fn for_backend(backend: &StorageBackend, extension: &str) -> Result<Self> {
+        match backend {
+            StorageBackend::Memory => Ok(Self::default()),
+            StorageBackend::Sqlite { path } => Self::open(path.with_extension(extension)),
+        }
+    }
+
+    /// Open a JSON file, starting from the default value if it does not exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let value = if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            serde_json::from_str(&content)
+                .map_err(|e| DialogError::Storage(format!("Failed to parse {}: {}", path.display(), e)))?
+        } else {
+            T::default()
+        };
+        Ok(Self { path: Some(path), value })
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Mutable access; call [`JsonStore::save`] afterwards to persist
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string(&self.value)
+            .map_err(|e| DialogError::Serialization(e.to_string()))?;
+
+        // Write to a temporary file first so a crash never leaves a truncated file
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
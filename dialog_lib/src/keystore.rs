@@ -0,0 +1,276 @@
+//! Encrypted identity keystore.
+//!
+//! Secret keys are stored as NIP-49 `ncryptsec` strings under named profiles
+//! in a single JSON file, so frontends can refer to an identity by name
+//! (`--key work`) instead of passing raw secret keys around.
+
+use crate::errors::{DialogError, Result};
+use nostr::nips::nip49::{EncryptedSecretKey, KeySecurity};
+use nostr::nips::nip19::{FromBech32, ToBech32};
+use nostr::{Keys, PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the keystore location
+pub const KEYSTORE_ENV: &str = "DIALOG_KEYSTORE";
+/// scrypt cost used for NIP-49 encryption
+const LOG_N: u8 = 16;
+
+/// A stored identity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeystoreEntry {
+    /// Hex-encoded public key (readable without the passphrase)
+    pub pubkey: String,
+    /// NIP-49 encrypted secret key
+    pub ncryptsec: String,
+    /// Creation time (Unix timestamp in seconds)
+    pub created_at: i64,
+}
+
+/// Public information about a stored identity
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeystoreProfile {
+    pub name: String,
+    pub pubkey: PublicKey,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct KeystoreFile {
+    profiles: BTreeMap<String, KeystoreEntry>,
+}
+
+/// File-backed store of named, passphrase-encrypted identities
+#[derive(Debug)]
+pub struct Keystore {
+    path: PathBuf,
+    file: KeystoreFile,
+    log_n: u8,
+}
+
+impl Keystore {
+    /// Default keystore location: `$DIALOG_KEYSTORE`, or `keystore.json` in the
+    /// platform data directory
+    pub fn default_path() -> PathBuf {
+        if let Ok(path) = std::env::var(KEYSTORE_ENV) {
+            return PathBuf::from(path);
+        }
+        let base = dirs::data_dir()
+            .map(|d| d.join("dialog"))
+            .or_else(|| dirs::home_dir().map(|h| h.join(".dialog")))
+            .unwrap_or_else(|| PathBuf::from(".dialog"));
+        base.join("keystore.json")
+    }
+
+    /// Open the keystore at the default location
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path())
+    }
+
+    /// Open a keystore file, starting empty if it does not exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            serde_json::from_str(&content)
+                .map_err(|e| DialogError::Keystore(format!("Failed to parse {}: {}", path.display(), e)))?
+        } else {
+            KeystoreFile::default()
+        };
+        Ok(Self { path, file, log_n: LOG_N })
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether a profile with this name exists
+    pub fn contains(&self, name: &str) -> bool {
+        self.file.profiles.contains_key(name)
+    }
+
+    /// List stored profiles, sorted by name
+    pub fn list(&self) -> Vec<KeystoreProfile> {
+        self.file
+            .profiles
+            .iter()
+            .filter_map(|(name, entry)| {
+                PublicKey::from_hex(&entry.pubkey).ok().map(|pubkey| KeystoreProfile {
+                    name: name.clone(),
+                    pubkey,
+                    created_at: entry.created_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Generate a new identity and store it under `name`
+    pub fn generate(&mut self, name: &str, passphrase: &str) -> Result<Keys> {
+        let keys = Keys::generate();
+        self.insert(name, &keys, passphrase, KeySecurity::Medium)?;
+        Ok(keys)
+    }
+
+    /// Import an existing secret key (`nsec1...` or hex) under `name`
+    pub fn import_nsec(&mut self, name: &str, secret: &str, passphrase: &str) -> Result<PublicKey> {
+        let secret = secret.trim();
+        let secret_key = if secret.starts_with("nsec1") {
+            SecretKey::from_bech32(secret)
+                .map_err(|e| DialogError::Keystore(format!("Invalid nsec: {}", e)))?
+        } else {
+            SecretKey::from_hex(secret)
+                .map_err(|e| DialogError::Keystore(format!("Invalid secret key: {}", e)))?
+        };
+        let keys = Keys::new(secret_key);
+        self.insert(name, &keys, passphrase, KeySecurity::Unknown)?;
+        Ok(keys.public_key())
+    }
+
+    /// Store already loaded keys under `name`
+    pub fn import_keys(&mut self, name: &str, keys: &Keys, passphrase: &str) -> Result<()> {
+        self.insert(name, keys, passphrase, KeySecurity::Unknown)
+    }
+
+    /// Decrypt the identity stored under `name`
+    pub fn unlock(&self, name: &str, passphrase: &str) -> Result<Keys> {
+        let entry = self
+            .file
+            .profiles
+            .get(name)
+            .ok_or_else(|| DialogError::Keystore(format!("No identity named '{}'", name)))?;
+        let encrypted = EncryptedSecretKey::from_bech32(&entry.ncryptsec)
+            .map_err(|e| DialogError::Keystore(format!("Corrupt entry for '{}': {}", name, e)))?;
+        let secret_key = encrypted
+            .decrypt(passphrase)
+            .map_err(|_| DialogError::Keystore(format!("Wrong passphrase for '{}'", name)))?;
+        let keys = Keys::new(secret_key);
+        if keys.public_key().to_hex() != entry.pubkey {
+            return Err(DialogError::Keystore(format!("Stored public key for '{}' does not match", name)));
+        }
+        Ok(keys)
+    }
+
+    /// Delete the identity stored under `name`
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        if self.file.profiles.remove(name).is_none() {
+            return Err(DialogError::Keystore(format!("No identity named '{}'", name)));
+        }
+        self.save()
+    }
+
+    fn insert(&mut self, name: &str, keys: &Keys, passphrase: &str, security: KeySecurity) -> Result<()> {
+        validate_name(name)?;
+        if self.contains(name) {
+            return Err(DialogError::Keystore(format!("An identity named '{}' already exists", name)));
+        }
+        if passphrase.is_empty() {
+            return Err(DialogError::Keystore("Passphrase cannot be empty".into()));
+        }
+
+        let encrypted = EncryptedSecretKey::new(keys.secret_key(), passphrase, self.log_n, security)
+            .map_err(|e| DialogError::Keystore(format!("Failed to encrypt key: {}", e)))?;
+        let ncryptsec = encrypted
+            .to_bech32()
+            .map_err(|e| DialogError::Keystore(format!("Failed to encode key: {}", e)))?;
+
+        self.file.profiles.insert(
+            name.to_string(),
+            KeystoreEntry {
+                pubkey: keys.public_key().to_hex(),
+                ncryptsec,
+                created_at: chrono::Utc::now().timestamp(),
+            },
+        );
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&self.file)
+            .map_err(|e| DialogError::Serialization(e.to_string()))?;
+
+        // Write to a temporary file first so a crash never leaves a truncated keystore
+        let tmp_path = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(DialogError::Keystore(
+            "Identity names may only contain letters, digits, '-' and '_'".into(),
+        ));
+    }
+    if name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(DialogError::Keystore("Identity names cannot look like a hex key".into()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_keystore(tag: &str) -> Keystore {
+        let path = std::env::temp_dir().join(format!("dialog_keystore_{}_{}.json", tag, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut keystore = Keystore::open(path).unwrap();
+        // Low scrypt cost keeps the tests fast
+        keystore.log_n = 4;
+        keystore
+    }
+
+    #[test]
+    fn test_generate_and_unlock() {
+        let mut keystore = temp_keystore("generate");
+        let keys = keystore.generate("work", "hunter2").unwrap();
+
+        // Reopen from disk to make sure the entry was persisted
+        let reopened = Keystore::open(keystore.path()).unwrap();
+        let unlocked = reopened.unlock("work", "hunter2").unwrap();
+        assert_eq!(unlocked.public_key(), keys.public_key());
+        assert!(reopened.unlock("work", "wrong").is_err());
+
+        let _ = std::fs::remove_file(keystore.path());
+    }
+
+    #[test]
+    fn test_import_nsec_and_list() {
+        let mut keystore = temp_keystore("import");
+        let keys = Keys::generate();
+        let nsec = keys.secret_key().to_bech32().unwrap();
+
+        let pubkey = keystore.import_nsec("personal", &nsec, "hunter2").unwrap();
+        assert_eq!(pubkey, keys.public_key());
+        assert!(keystore.import_nsec("personal", &nsec, "hunter2").is_err());
+
+        let profiles = keystore.list();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "personal");
+        assert_eq!(profiles[0].pubkey, keys.public_key());
+
+        keystore.remove("personal").unwrap();
+        assert!(keystore.list().is_empty());
+
+        let _ = std::fs::remove_file(keystore.path());
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let mut keystore = temp_keystore("names");
+        assert!(keystore.generate("", "pw").is_err());
+        assert!(keystore.generate("has space", "pw").is_err());
+        assert!(keystore.generate(&"a".repeat(64), "pw").is_err());
+    }
+}
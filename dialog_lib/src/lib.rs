@@ -4,6 +4,21 @@ pub mod service;
 pub mod mls_service;
 pub mod config;
 pub mod storage;
+pub mod store;
+pub mod backup;
+pub mod keystore;
+pub mod signer;
+pub mod outbox;
+pub mod system_events;
+pub mod event_ledger;
+pub mod subscription;
+mod events;
+pub mod group_ref;
+pub mod presence;
+pub mod invite_policy;
+pub mod relay_lists;
+mod json_store;
+mod group_signal;
 
 // Re-export commonly used types
 pub use types::*;
@@ -11,7 +26,15 @@ pub use errors::*;
 pub use service::MlsService;
 pub use mls_service::{RealMlsService, RealMlsServiceBuilder};
 pub use config::DialogConfig;
+pub use group_ref::GroupRef;
+pub use subscription::SubscriptionHandle;
+pub use invite_policy::{InvitePolicy, InviteDecision, DropReason};
 pub use storage::{StorageBackend, NostrMlsStorage};
+pub use nostr_mls_storage::NostrMlsStorageProvider;
+pub use store::MlsStore;
+pub use backup::{BackupArchive, restore_backup};
+pub use keystore::{Keystore, KeystoreProfile};
+pub use signer::connect_bunker;
 
 // Re-export Nostr-MLS types to eliminate direct dependencies in UIs
 pub use nostr_mls::prelude::{
@@ -20,6 +43,7 @@ pub use nostr_mls::prelude::{
 
 // Re-export nostr utilities
 pub use nostr::nips::nip19::ToBech32;
+pub use nostr::signer::{IntoNostrSigner, NostrSigner};
 
 // Re-export hex utilities
 pub use hex;
@@ -88,6 +112,48 @@ impl DialogLib {
         Ok(Self { service })
     }
     
+    /// Wrap an already configured service, e.g. one built with [`RealMlsService::builder`]
+    pub fn from_service(service: impl MlsService + 'static) -> Self {
+        Self { service: Arc::new(service) }
+    }
+
+    /// Create a new DialogLib instance with custom storage backend and per-identity settings
+    pub async fn new_with_config(
+        keys: nostr_mls::prelude::Keys,
+        relay_url: impl Into<String>,
+        storage_backend: StorageBackend,
+        config: DialogConfig,
+    ) -> Result<Self> {
+        let service: Arc<dyn MlsService> = Arc::new(
+            RealMlsService::builder()
+                .keys(keys)
+                .relay_url(relay_url)
+                .storage_backend(storage_backend)
+                .config(config)
+                .build()
+                .await?
+        );
+        Ok(Self { service })
+    }
+    
+    /// Create a new DialogLib instance whose Nostr identity key is held by a
+    /// signer (e.g. a NIP-46 bunker from [`connect_bunker`])
+    pub async fn new_with_signer(
+        signer: Arc<dyn NostrSigner>,
+        relay_url: impl Into<String>,
+        storage_backend: StorageBackend,
+    ) -> Result<Self> {
+        let service: Arc<dyn MlsService> = Arc::new(
+            RealMlsService::builder()
+                .signer(signer)
+                .relay_url(relay_url)
+                .storage_backend(storage_backend)
+                .build()
+                .await?
+        );
+        Ok(Self { service })
+    }
+    
     /// Get all contacts
     pub async fn get_contacts(&self) -> Result<Vec<Contact>> {
         self.service.get_contacts().await
@@ -104,14 +170,45 @@ impl DialogLib {
     }
     
     /// Send a message to a conversation
-    pub async fn send_message(&self, group_id: &nostr_mls::prelude::GroupId, content: &str) -> Result<()> {
-        self.service.send_message(group_id, content).await
+    pub async fn send_message(&self, group: impl Into<GroupRef>, content: &str) -> Result<SendReport> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.send_message(&group_id, content).await
     }
     
     /// Create a new conversation
     pub async fn create_conversation(&self, name: &str, participants: Vec<nostr_mls::prelude::PublicKey>) -> Result<String> {
         self.service.create_conversation(name, participants).await
     }
+
+    /// Create a conversation with everyone reachable. Participants without a
+    /// usable key package are skipped rather than failing the whole group; the
+    /// report says who was added and who still needs an invite.
+    pub async fn create_conversation_best_effort(
+        &self,
+        name: &str,
+        participants: Vec<nostr_mls::prelude::PublicKey>,
+    ) -> Result<GroupCreationReport> {
+        self.service.create_conversation_best_effort(name, participants).await
+    }
+
+    /// Add members to a group we administer, skipping anyone without a usable
+    /// key package. Members whose welcome could not be published before get it
+    /// again, so this is also how to retry the `undelivered` part of a report.
+    pub async fn add_members(
+        &self,
+        group: impl Into<GroupRef>,
+        participants: Vec<nostr_mls::prelude::PublicKey>,
+    ) -> Result<GroupCreationReport> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.add_members(&group_id, participants).await
+    }
+
+    /// Open the one-to-one conversation with `pubkey`: the active two-member
+    /// group with them opened as a DM before (or an unnamed one they invited
+    /// us to), otherwise a new unnamed group. Named groups are never reused.
+    pub async fn open_direct_conversation(&self, pubkey: &nostr_mls::prelude::PublicKey) -> Result<Conversation> {
+        self.service.open_direct_conversation(pubkey).await
+    }
     
     /// Add a contact
     pub async fn add_contact(&self, pubkey: &str) -> Result<()> {
@@ -119,8 +216,15 @@ impl DialogLib {
     }
     
     /// Switch to a conversation
-    pub async fn switch_conversation(&self, conversation_id: &str) -> Result<()> {
-        self.service.switch_conversation(conversation_id).await
+    pub async fn switch_conversation(&self, group: impl Into<GroupRef>) -> Result<()> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.switch_conversation(&hex::encode(group_id.as_slice())).await
+    }
+    
+    /// Resolve a group reference (MLS ID, Nostr group ID, name or unique prefix)
+    /// to the MLS group ID of a stored group
+    pub async fn resolve_group(&self, group: impl Into<GroupRef>) -> Result<GroupId> {
+        self.service.resolve_group(&group.into()).await
     }
     
     /// Get the active conversation ID
@@ -165,7 +269,11 @@ impl DialogLib {
         self.service.publish_profile(&profile).await
     }
     
-    /// Connect to the relay
+    /// Connect to the relay.
+    ///
+    /// A relay that sends a NIP-42 challenge is authenticated with the identity
+    /// signer; if it rejects us this returns [`DialogError::Auth`] and the
+    /// status stays disconnected.
     pub async fn connect(&self) -> Result<()> {
         // We need to access the concrete RealMlsService, not the trait
         if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
@@ -175,6 +283,129 @@ impl DialogLib {
         }
     }
     
+    /// Create a passphrase-encrypted backup of this identity and its MLS state
+    pub async fn export_backup(&self, passphrase: &str) -> Result<Vec<u8>> {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.export_backup(passphrase).await
+        } else {
+            Err(DialogError::General("Service does not support backups".into()))
+        }
+    }
+
+    /// Current per-identity settings
+    pub async fn config(&self) -> DialogConfig {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.config().await
+        } else {
+            DialogConfig::default()
+        }
+    }
+
+    /// Turn sending read receipts on or off for this identity
+    pub async fn set_read_receipts(&self, enabled: bool) -> Result<()> {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.set_read_receipts(enabled).await;
+            Ok(())
+        } else {
+            Err(DialogError::General("Service does not support read receipts".into()))
+        }
+    }
+
+    /// Replace the policy incoming welcomes are screened with
+    pub async fn set_invite_policy(&self, policy: InvitePolicy) -> Result<()> {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.set_invite_policy(policy).await;
+            Ok(())
+        } else {
+            Err(DialogError::General("Service does not support invite policies".into()))
+        }
+    }
+
+    /// Drop all future welcomes from `pubkey` and hide their pending invites
+    pub async fn block_inviter(&self, pubkey: PublicKey) -> Result<()> {
+        let mut policy = self.config().await.invite_policy;
+        policy.blocked.insert(pubkey);
+        self.set_invite_policy(policy).await
+    }
+
+    /// Let welcomes from `pubkey` through again
+    pub async fn unblock_inviter(&self, pubkey: PublicKey) -> Result<()> {
+        let mut policy = self.config().await.invite_policy;
+        policy.blocked.remove(&pubkey);
+        self.set_invite_policy(policy).await
+    }
+
+    /// Mark the conversation as read up to its newest message, sending a read
+    /// receipt if enabled. Returns whether the read marker moved.
+    pub async fn mark_read(&self, group: impl Into<GroupRef>) -> Result<bool> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.mark_read(&group_id).await
+    }
+
+    /// Tell the group we started or stopped typing. Repeated starts are
+    /// rate-limited, so this can be called on every keystroke.
+    pub async fn send_typing(&self, group: impl Into<GroupRef>, typing: bool) -> Result<bool> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.send_typing(&group_id, typing).await
+    }
+
+    /// Pin or unpin a message for all members; only group admins may do this
+    pub async fn set_pinned(&self, group: impl Into<GroupRef>, message_id: &str, pinned: bool) -> Result<()> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.set_pinned(&group_id, message_id, pinned).await
+    }
+
+    /// Messages currently pinned in a group, most recently pinned first
+    pub async fn pinned_messages(&self, group: impl Into<GroupRef>) -> Result<Vec<Message>> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.pinned_messages(&group_id).await
+    }
+
+    /// Retry publishing queued outgoing messages now; returns how many are still pending
+    pub async fn flush_outbox(&self) -> Result<usize> {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.flush_outbox().await
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// NIP-42 authentication state of every relay that challenged us
+    pub async fn auth_status(&self) -> std::collections::HashMap<String, AuthStatus> {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.auth_status().await
+        } else {
+            std::collections::HashMap::new()
+        }
+    }
+
+    /// Number of welcomes received that were addressed to someone else and skipped
+    pub async fn foreign_welcomes_skipped(&self) -> u64 {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.foreign_welcomes_skipped()
+        } else {
+            0
+        }
+    }
+
+    /// Number of outgoing messages not yet accepted by any relay
+    pub async fn outbox_pending_count(&self) -> usize {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.outbox_pending_count().await
+        } else {
+            0
+        }
+    }
+
+    /// Import contacts from a restored backup, returning how many were added
+    pub async fn import_contacts(&self, contacts: Vec<Contact>) -> Result<usize> {
+        if let Some(real_service) = self.service.as_any().downcast_ref::<RealMlsService>() {
+            real_service.import_contacts(contacts).await
+        } else {
+            Err(DialogError::General("Service does not support importing contacts".into()))
+        }
+    }
+    
     /// Get the relay URL
     pub async fn get_relay_url(&self) -> Result<String> {
         self.service.get_relay_url().await
@@ -192,22 +423,46 @@ impl DialogLib {
     }
 
     /// Accept a group invite
-    pub async fn accept_invite(&self, group_id: &str) -> Result<()> {
-        self.service.accept_invite(group_id).await
+    pub async fn accept_invite(&self, invite: impl Into<GroupRef>) -> Result<()> {
+        self.service.accept_invite(&invite.into()).await
     }
 
     /// Fetch and process group events (for synchronization)
-    pub async fn fetch_and_process_group_events(&self, group_id: &GroupId) -> Result<()> {
-        self.service.fetch_and_process_group_events(group_id).await
+    pub async fn fetch_and_process_group_events(&self, group: impl Into<GroupRef>) -> Result<()> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.fetch_and_process_group_events(&group_id).await
     }
 
     /// Fetch messages for a conversation
-    pub async fn fetch_messages(&self, group_id: &GroupId) -> Result<MessageFetchResult> {
-        self.service.fetch_messages(group_id).await
+    pub async fn fetch_messages(&self, group: impl Into<GroupRef>) -> Result<MessageFetchResult> {
+        let group_id = self.resolve_group(group).await?;
+        self.service.fetch_messages(&group_id).await
     }
 
-    /// Subscribe to real-time updates for all groups
-    pub async fn subscribe_to_groups(&self, ui_sender: tokio::sync::mpsc::Sender<UiUpdate>) -> Result<()> {
+    /// Subscribe to real-time updates for all groups, replacing any previous
+    /// subscription. The returned handle stops it.
+    pub async fn subscribe_to_groups(&self, ui_sender: tokio::sync::mpsc::Sender<UiUpdate>) -> Result<SubscriptionHandle> {
         self.service.subscribe_to_groups(ui_sender).await
     }
+
+    /// Start real-time updates without registering a sender; follow them with
+    /// [`DialogLib::events`]. Replaces any previous subscription.
+    pub async fn subscribe(&self) -> Result<SubscriptionHandle> {
+        self.service.subscribe().await
+    }
+
+    /// Stream of every update (fully decrypted new messages, invites, receipts, ...).
+    /// Any number of streams can be open at once; each sees all updates from the
+    /// moment it is created, while a subscription is running. A consumer that
+    /// falls far behind skips what it missed.
+    pub fn events(&self) -> futures::stream::BoxStream<'static, UiUpdate> {
+        events::into_stream(self.service.events())
+    }
+
+    /// Stop real-time updates and background tasks, try once more to deliver
+    /// queued messages, disconnect and close the storage. The instance cannot
+    /// reconnect afterwards, and calls that need storage fail.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.service.shutdown().await
+    }
 }
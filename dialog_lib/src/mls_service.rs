@@ -1,42 +1,111 @@
 use crate::service::MlsService;
-use crate::types::{Contact, Conversation, ConnectionStatus, Profile, PendingInvite, Message, InviteListResult, MessageFetchResult, UiUpdate};
+use crate::group_ref::GroupRef;
+use crate::types::{Contact, Conversation, ConnectionStatus, Profile, PendingInvite, Message, InviteListResult, MessageFetchResult, UiUpdate, SendReport, RelayOutcome, RelayAck, AuthStatus, RejectReason, DeliveryStatus, SystemEvent, SystemEventKind, TimelineEntry, MessageBody, WelcomeTransport, GroupCreationReport, SkipReason, nostr_kinds};
+use crate::outbox::{Outbox, OutboxEntry};
+use crate::system_events::{GroupSnapshot, SystemEventLog};
+use crate::event_ledger::{EventLedger, EventOutcome};
+use crate::subscription::SubscriptionHandle;
+use crate::events::EventChannel;
 use crate::errors::{Result, DialogError};
 use crate::storage::{NostrMlsStorage, StorageBackend};
+use crate::store::MlsStore;
+use crate::backup::BackupArchive;
+use crate::config::DialogConfig;
+use crate::presence;
+use crate::invite_policy::{self, InviteDecision, InviteGate, InvitePolicy};
+use crate::relay_lists;
+use crate::json_store::JsonStore;
+use crate::group_signal;
 use async_trait::async_trait;
+use nostr_mls::groups::NostrGroupDataUpdate;
+use nostr_mls::messages::MessageProcessingResult;
 use nostr_mls::prelude::*;
+use nostr_mls_storage::messages::types as message_types;
+use nostr_mls_storage::NostrMlsStorageProvider;
+use nostr_mls_storage::welcomes::types as welcome_types;
 use nostr_sdk::prelude::*;
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{RwLock, mpsc, watch};
+
+/// Minimum time between two typing-start signals to the same group
+const TYPING_SEND_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+/// Typing signals older than this are history, not live activity
+const TYPING_MAX_AGE_SECS: u64 = 10;
+/// How long `connect` waits for relays to answer our NIP-42 AUTH
+const AUTH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+/// Prefix of the IDs of our real-time subscriptions
+const SUBSCRIPTION_PREFIX: &str = "dialog:";
 
 /// Message cache entry with timestamp for ordering
 
 /// Real MLS service implementation using actual Nostr-MLS operations
 #[derive(Debug)]
 pub struct RealMlsService {
-    /// The NostrMls instance for MLS operations
-    nostr_mls: Arc<RwLock<NostrMlsStorage>>,
+    /// Handle to the storage actor owning the NostrMls instance
+    store: MlsStore,
+    /// SQLite database holding the MLS state, copied into backups
+    database_path: Option<std::path::PathBuf>,
     /// Nostr client for relay communication
     client: Arc<RwLock<Client>>,
-    /// Identity keys for this user
-    keys: Keys,
+    /// Signer holding the Nostr identity key (local keys or a NIP-46 bunker)
+    signer: Arc<dyn NostrSigner>,
+    /// Local identity keys, when the identity key is held in-process
+    keys: Option<Keys>,
+    /// Public key of the identity
+    pubkey: PublicKey,
     /// Relay URL for communication
     relay_url: String,
     /// Current connection status
     connection_status: Arc<RwLock<ConnectionStatus>>,
-    /// Runtime storage for contacts (pubkey -> Contact)
+    /// Contacts (pubkey -> Contact), with presence as observed this session
     contacts: Arc<RwLock<HashMap<PublicKey, Contact>>>,
+    /// Where the contact list is persisted
+    contact_book: Arc<RwLock<JsonStore<Vec<Contact>>>>,
     /// Runtime cache for profiles (pubkey -> Profile)
     profiles: Arc<RwLock<HashMap<PublicKey, Profile>>>,
     /// Last sync timestamp for each group
     last_sync: Arc<RwLock<HashMap<GroupId, i64>>>,
-    /// Track displayed message event IDs per group to prevent duplicates
-    displayed_messages: Arc<RwLock<HashMap<GroupId, std::collections::HashSet<String>>>>,
-    /// UI sender for real-time updates
-    ui_sender: Arc<RwLock<Option<mpsc::Sender<UiUpdate>>>>,
-    /// Current subscription ID for group messages
-    subscription_id: Arc<RwLock<Option<SubscriptionId>>>,
+    /// Group events already processed, so relays replaying them are ignored
+    ledger: Arc<EventLedger>,
+    /// Real-time updates for the UI and other consumers
+    events: Arc<EventChannel>,
+    /// Open real-time subscriptions, one per group and per filter
+    subscription_ids: Arc<RwLock<HashSet<SubscriptionId>>>,
+    /// The task handling real-time events, if subscribed
+    subscription: Arc<RwLock<Option<SubscriptionHandle>>>,
+    /// Set on shutdown; background tasks stop when it flips
+    shutdown: Arc<watch::Sender<bool>>,
+    /// Outgoing messages waiting for relay acceptance
+    outbox: Arc<RwLock<Outbox>>,
+    /// Whether the outbox retry task is running
+    outbox_retry_running: Arc<AtomicBool>,
+    /// Whether the presence refresh task is running
+    presence_refresh_running: Arc<AtomicBool>,
+    /// NIP-42 authentication state per relay that challenged us
+    auth_status: Arc<RwLock<HashMap<String, AuthStatus>>>,
+    /// The NIP-42 challenge handler; replaced on every connect, stopped on disconnect
+    auth_handler: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// Per-identity settings (read receipts, ...)
+    config: Arc<RwLock<DialogConfig>>,
+    /// How welcomes are sent to new members
+    welcome_transport: WelcomeTransport,
+    /// Newest message from others we have read, per hex group id
+    read_markers: Arc<RwLock<JsonStore<HashMap<String, (EventId, Timestamp)>>>>,
+    /// When we last sent a typing-start signal, per group
+    typing_sent: Arc<RwLock<HashMap<GroupId, std::time::Instant>>>,
+    /// Invite policy decisions on welcomes seen so far
+    invite_gate: Arc<RwLock<InviteGate>>,
+    /// Welcomes received that were addressed to someone else
+    foreign_welcomes_skipped: Arc<AtomicU64>,
+    /// Welcomes that could not be published, by `<group hex>:<member hex>`
+    undelivered_welcomes: Arc<RwLock<JsonStore<BTreeMap<String, UnsignedEvent>>>>,
+    /// Membership and metadata changes shown in conversation timelines
+    system_events: Arc<RwLock<SystemEventLog>>,
+    /// Hex ids of the groups we opened as direct conversations
+    direct_groups: Arc<RwLock<JsonStore<BTreeSet<String>>>>,
 }
 
 impl RealMlsService {
@@ -55,10 +124,58 @@ impl RealMlsService {
     }
 
     /// Internal constructor used by the builder
-    async fn new_with_storage(keys: Keys, relay_url: String, storage_backend: StorageBackend) -> Result<Self> {
-        let nostr_mls = NostrMlsStorage::new(storage_backend).await?;
+    async fn new_with_storage(
+        signer: Arc<dyn NostrSigner>,
+        keys: Option<Keys>,
+        relay_url: String,
+        nostr_mls: NostrMlsStorage,
+        storage_backend: StorageBackend,
+        config: DialogConfig,
+        welcome_transport: WelcomeTransport,
+    ) -> Result<Self> {
+        let pubkey = signer
+            .get_public_key()
+            .await
+            .map_err(|e| DialogError::General(format!("Failed to get public key from signer: {}", e).into()))?;
+        if let Some(keys) = &keys {
+            if keys.public_key() != pubkey {
+                return Err(DialogError::General("Signer public key does not match the provided keys".into()));
+            }
+        }
+
+        let database_path = nostr_mls.database_path().map(|path| path.to_path_buf());
+        let outbox = Outbox::for_backend(&storage_backend)?;
+        let system_events = SystemEventLog::for_backend(&storage_backend)?;
+        let store = MlsStore::spawn(nostr_mls)?;
+        let ledger = EventLedger::for_backend(&storage_backend).await?;
+        // Forget the events of groups we are no longer in, and old failures
+        let live_groups: Vec<GroupId> = store
+            .get_groups()
+            .await?
+            .into_iter()
+            .filter(|group| group.state != group_types::GroupState::Inactive)
+            .map(|group| group.mls_group_id)
+            .collect();
+        ledger.prune(&live_groups, chrono::Utc::now().timestamp()).await?;
+        let contact_book = JsonStore::<Vec<Contact>>::for_backend(&storage_backend, "contacts.json")?;
+        let read_markers = JsonStore::for_backend(&storage_backend, "read.json")?;
+        let undelivered_welcomes = JsonStore::for_backend(&storage_backend, "welcomes.json")?;
+        let direct_groups = JsonStore::for_backend(&storage_backend, "direct.json")?;
+        let invite_gate = InviteGate::for_backend(&storage_backend)?;
+        // Presence is only known once contacts are seen again
+        let contacts = contact_book
+            .get()
+            .iter()
+            .map(|contact| (contact.pubkey, Contact { online: false, ..contact.clone() }))
+            .collect();
         
-        let client = Client::new(keys.clone());
+        // MLS keys stay in local storage; only Nostr events are signed by the signer.
+        // NIP-42 challenges are answered by our own handler (see `spawn_auth_handler`)
+        // so the outcome can be tracked and reported.
+        let client = Client::builder()
+            .signer(signer.clone())
+            .opts(ClientOptions::new().automatic_authentication(false))
+            .build();
         
         // Add relay 
         client
@@ -67,22 +184,45 @@ impl RealMlsService {
             .map_err(|e| DialogError::General(Box::new(e)))?;
         
         Ok(Self {
-            nostr_mls: Arc::new(RwLock::new(nostr_mls)),
+            store,
+            database_path,
             client: Arc::new(RwLock::new(client)),
+            signer,
             keys,
+            pubkey,
             relay_url,
             connection_status: Arc::new(RwLock::new(ConnectionStatus::Disconnected)),
-            contacts: Arc::new(RwLock::new(HashMap::new())),
+            contacts: Arc::new(RwLock::new(contacts)),
+            contact_book: Arc::new(RwLock::new(contact_book)),
             profiles: Arc::new(RwLock::new(HashMap::new())),
             last_sync: Arc::new(RwLock::new(HashMap::new())),
-            displayed_messages: Arc::new(RwLock::new(HashMap::new())),
-            ui_sender: Arc::new(RwLock::new(None)),
-            subscription_id: Arc::new(RwLock::new(None)),
+            ledger: Arc::new(ledger),
+            events: Arc::new(EventChannel::new()),
+            subscription_ids: Arc::new(RwLock::new(HashSet::new())),
+            subscription: Arc::new(RwLock::new(None)),
+            shutdown: Arc::new(watch::channel(false).0),
+            outbox: Arc::new(RwLock::new(outbox)),
+            outbox_retry_running: Arc::new(AtomicBool::new(false)),
+            presence_refresh_running: Arc::new(AtomicBool::new(false)),
+            auth_status: Arc::new(RwLock::new(HashMap::new())),
+            auth_handler: Arc::new(std::sync::Mutex::new(None)),
+            config: Arc::new(RwLock::new(config)),
+            welcome_transport,
+            read_markers: Arc::new(RwLock::new(read_markers)),
+            typing_sent: Arc::new(RwLock::new(HashMap::new())),
+            invite_gate: Arc::new(RwLock::new(invite_gate)),
+            foreign_welcomes_skipped: Arc::new(AtomicU64::new(0)),
+            undelivered_welcomes: Arc::new(RwLock::new(undelivered_welcomes)),
+            system_events: Arc::new(RwLock::new(system_events)),
+            direct_groups: Arc::new(RwLock::new(direct_groups)),
         })
     }
 
     /// Connect to the relay
     pub async fn connect(&self) -> Result<()> {
+        if *self.shutdown.borrow() {
+            return Err(DialogError::General("Cannot connect after shutdown".into()));
+        }
         let client = self.client.read().await;
         
         // Update status to connecting
@@ -91,6 +231,9 @@ impl RealMlsService {
             *status = ConnectionStatus::Connecting;
         }
         
+        // Answer NIP-42 challenges; the listener must exist before a relay can send one
+        self.spawn_auth_handler(client.notifications());
+
         // Try to connect to the relay
         client.connect().await;
         
@@ -100,14 +243,27 @@ impl RealMlsService {
         // Test the connection by trying to fetch some events (reduced timeouts)
         let test_result = tokio::time::timeout(
             std::time::Duration::from_secs(2),
-            client.fetch_events(Filter::new().limit(1), std::time::Duration::from_secs(1))
+            client.fetch_events_from([self.relay_url.as_str()], Filter::new().limit(1), std::time::Duration::from_secs(1))
         ).await;
         
         match test_result {
             Ok(Ok(_)) => {
-                // Connection successful
-                let mut status = self.connection_status.write().await;
-                *status = ConnectionStatus::Connected;
+                // Relays that challenged us withhold private events until AUTH succeeds
+                if let Err(e) = self.wait_for_auth().await {
+                    *self.connection_status.write().await = ConnectionStatus::Disconnected;
+                    return Err(e);
+                }
+                {
+                    let mut status = self.connection_status.write().await;
+                    *status = ConnectionStatus::Connected;
+                }
+
+                // Retry anything composed while offline without waiting for the backoff
+                self.outbox.write().await.reset_backoff();
+                if self.outbox.read().await.pending_count() > 0 {
+                    self.spawn_outbox_retry();
+                }
+                self.spawn_presence_refresh();
                 Ok(())
             }
             Ok(Err(e)) => {
@@ -125,10 +281,125 @@ impl RealMlsService {
         }
     }
 
+    /// Sign and send an AUTH event for every NIP-42 challenge and record whether
+    /// the relay accepted it. Subscriptions a relay closed with `auth-required:`
+    /// are sent again once it accepted our AUTH.
+    fn spawn_auth_handler(&self, mut notifications: tokio::sync::broadcast::Receiver<RelayPoolNotification>) {
+        let client = self.client.clone();
+        let auth_status = self.auth_status.clone();
+
+        let handler = tokio::spawn(async move {
+            // AUTH event ID -> relay it was sent to
+            let mut pending: HashMap<EventId, RelayUrl> = HashMap::new();
+            // Relays that closed a subscription until we authenticate
+            let mut closed: HashSet<RelayUrl> = HashSet::new();
+            loop {
+                let notification = match notifications.recv().await {
+                    Ok(notification) => notification,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                let RelayPoolNotification::Message { relay_url, message } = notification else {
+                    continue;
+                };
+                match message {
+                    RelayMessage::Auth { challenge } => {
+                        let client = client.read().await;
+                        let builder = EventBuilder::auth(challenge.to_string(), relay_url.clone());
+                        let status = match client.sign_event_builder(builder).await {
+                            Ok(event) => {
+                                pending.insert(event.id, relay_url.clone());
+                                match client.send_msg_to([relay_url.clone()], ClientMessage::auth(event)).await {
+                                    Ok(_) => AuthStatus::Pending,
+                                    Err(e) => AuthStatus::Failed(format!("Failed to send AUTH: {}", e)),
+                                }
+                            }
+                            Err(e) => AuthStatus::Failed(format!("Failed to sign AUTH: {}", e)),
+                        };
+                        auth_status.write().await.insert(relay_url.to_string(), status);
+                    }
+                    RelayMessage::Closed { message, .. } if message.starts_with("auth-required:") => {
+                        let authenticated = auth_status.read().await.get(relay_url.as_str()) == Some(&AuthStatus::Authenticated);
+                        if authenticated {
+                            resubscribe(&client, &relay_url).await;
+                        } else {
+                            closed.insert(relay_url);
+                        }
+                    }
+                    RelayMessage::Ok { event_id, status, message } => {
+                        if let Some(relay_url) = pending.remove(&event_id) {
+                            let status = if status {
+                                AuthStatus::Authenticated
+                            } else {
+                                AuthStatus::Failed(message.to_string())
+                            };
+                            let accepted = status == AuthStatus::Authenticated;
+                            auth_status.write().await.insert(relay_url.to_string(), status);
+                            if accepted && closed.remove(&relay_url) {
+                                resubscribe(&client, &relay_url).await;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        // A previous connection's handler would answer challenges twice
+        if let Some(previous) = self.auth_handler.lock().unwrap().replace(handler) {
+            previous.abort();
+        }
+    }
+
+    /// Wait briefly for an outstanding AUTH answer, then fail if our relay refused us
+    async fn wait_for_auth(&self) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + AUTH_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            if self.own_auth_status().await != Some(AuthStatus::Pending) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        self.check_auth().await
+    }
+
+    /// Error out if our relay rejected (or never answered) our authentication.
+    /// Other relays in the pool (e.g. a member's inbox we published to) do
+    /// not hold our private events, so their outcome does not count.
+    async fn check_auth(&self) -> Result<()> {
+        match self.own_auth_status().await {
+            Some(AuthStatus::Failed(reason)) => Err(DialogError::Auth(format!("{}: {}", self.relay_url, reason))),
+            Some(AuthStatus::Pending) => Err(DialogError::Auth(format!("{}: no answer to AUTH", self.relay_url))),
+            Some(AuthStatus::Authenticated) | None => Ok(()),
+        }
+    }
+
+    /// Authentication state of the configured relay, if it challenged us
+    async fn own_auth_status(&self) -> Option<AuthStatus> {
+        let own = RelayUrl::parse(&self.relay_url).ok()?;
+        self.auth_status
+            .read()
+            .await
+            .iter()
+            .find(|(relay, _)| RelayUrl::parse(relay).is_ok_and(|relay| relay == own))
+            .map(|(_, status)| status.clone())
+    }
+
+    /// NIP-42 authentication state of every relay that sent us a challenge
+    pub async fn auth_status(&self) -> HashMap<String, AuthStatus> {
+        self.auth_status.read().await.clone()
+    }
+
     /// Disconnect from the relay
     pub async fn disconnect(&self) -> Result<()> {
         let client = self.client.read().await;
         client.disconnect().await;
+
+        // The next connection is challenged afresh
+        if let Some(handler) = self.auth_handler.lock().unwrap().take() {
+            handler.abort();
+        }
+        self.auth_status.write().await.clear();
         
         // Update connection status
         let mut status = self.connection_status.write().await;
@@ -140,143 +411,253 @@ impl RealMlsService {
     /// Generate and publish a key package to the relay
     pub async fn publish_key_package(&self) -> Result<()> {
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
         
         let relay_url = RelayUrl::parse(&self.relay_url)
             .map_err(|e| DialogError::General(Box::new(e)))?;
 
-        let (key_package_encoded, tags) = nostr_mls
-            .create_key_package_for_event(&self.keys.public_key(), [relay_url])
+        let (key_package_encoded, tags) = self
+            .store
+            .create_key_package_for_event(&self.pubkey, vec![relay_url])
             .await
             .map_err(|e| DialogError::General(Box::new(e)))?;
 
-        let key_package_event = EventBuilder::new(Kind::MlsKeyPackage, key_package_encoded)
-            .tags(tags)
-            .sign_with_keys(&self.keys)
+        let key_package_event = client
+            .sign_event_builder(EventBuilder::new(Kind::MlsKeyPackage, key_package_encoded).tags(tags))
+            .await
             .map_err(|e| DialogError::General(Box::new(e)))?;
 
         client
-            .send_event(&key_package_event)
+            .send_event_to([self.relay_url.as_str()], &key_package_event)
             .await
             .map_err(|e| DialogError::General(Box::new(e)))?;
 
         Ok(())
     }
 
-    /// Find a group by its ID (supports both MLS Group ID and Nostr Group ID)
-    async fn find_group_by_id(&self, group_id_hex: &str) -> Result<group_types::Group> {
-        let nostr_mls = self.nostr_mls.read().await;
-        
-        let groups = nostr_mls.get_groups()
-            .await
-            .map_err(|e| DialogError::General(Box::new(e)))?;
-        
-        // Try as MLS Group ID first (32 hex chars)
-        if group_id_hex.len() == 32 {
-            if let Ok(group_id_bytes) = hex::decode(group_id_hex) {
-                let mls_group_id = GroupId::from_slice(&group_id_bytes);
-                for group in &groups {
-                    if group.mls_group_id == mls_group_id {
-                        return Ok(group.clone());
-                    }
+    /// Create a passphrase-encrypted backup of the identity, contacts,
+    /// relay configuration and the MLS database (SQLite storage only)
+    pub async fn export_backup(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let contacts = {
+            let contacts = self.contacts.read().await;
+            contacts.values().cloned().collect()
+        };
+        let mut config = self.config.read().await.clone();
+        config.relay_urls = vec![self.relay_url.clone()];
+        let keys = self.keys.as_ref().ok_or_else(|| {
+            DialogError::Backup("Backups require a local identity key, not a remote signer".into())
+        })?;
+        let mut archive = BackupArchive::new(keys, config).with_contacts(contacts);
+
+        // In-memory and custom storage cannot be copied; a backup without the
+        // MLS state would silently lose every group on restore
+        let path = self.database_path.as_ref().ok_or_else(|| {
+            DialogError::Backup("Backups require the SQLite storage backend; this MLS state cannot be exported".into())
+        })?;
+        archive.add_sqlite_database(path).await?;
+
+        archive.encrypt(passphrase)
+    }
+
+    /// Import contacts (e.g. from a restored backup), keeping existing entries
+    pub async fn import_contacts(&self, imported: Vec<Contact>) -> Result<usize> {
+        let mut added = 0;
+        {
+            let mut contacts = self.contacts.write().await;
+            for contact in imported {
+                if contact.pubkey == self.pubkey {
+                    continue;
                 }
-            }
-        }
-        
-        // Try as Nostr Group ID (64 hex chars)
-        if group_id_hex.len() == 64 {
-            if let Ok(nostr_group_id_bytes) = hex::decode(group_id_hex) {
-                for group in &groups {
-                    if group.nostr_group_id.as_slice() == nostr_group_id_bytes.as_slice() {
-                        return Ok(group.clone());
-                    }
+                if !contacts.contains_key(&contact.pubkey) {
+                    contacts.insert(contact.pubkey, contact);
+                    added += 1;
                 }
             }
         }
-        
-        Err(DialogError::General("Group not found".into()))
+        self.save_contacts().await?;
+        Ok(added)
     }
-}
 
-#[async_trait]
-impl MlsService for RealMlsService {
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// Persist the contact list next to the MLS storage
+    async fn save_contacts(&self) -> Result<()> {
+        let contacts: Vec<Contact> = self.contacts.read().await.values().cloned().collect();
+        let mut contact_book = self.contact_book.write().await;
+        *contact_book.get_mut() = contacts;
+        contact_book.save()
     }
 
-    async fn get_contacts(&self) -> Result<Vec<Contact>> {
-        // Return all contacts from runtime storage
-        let contacts = self.contacts.read().await;
-        Ok(contacts.values().cloned().collect())
+    /// Try to publish all outbox messages that are due, returning how many are still pending
+    pub async fn flush_outbox(&self) -> Result<usize> {
+        let status = *self.connection_status.read().await;
+        if status != ConnectionStatus::Connected {
+            return Ok(self.outbox.read().await.pending_count());
+        }
+        Ok(deliver_outbox(&self.client, &self.store, &self.relay_url, &self.outbox, &self.events).await)
     }
 
-    async fn get_conversations(&self) -> Result<Vec<Conversation>> {
-        let nostr_mls = self.nostr_mls.read().await;
-        
-        let groups = nostr_mls.get_groups()
-            .await
-            .map_err(|e| DialogError::General(Box::new(e)))?;
+    /// Number of outgoing messages not yet accepted by any relay
+    pub async fn outbox_pending_count(&self) -> usize {
+        self.outbox.read().await.pending_count()
+    }
 
-        let mut conversations = Vec::new();
-        for group in groups {
-            let conversation = Conversation {
-                id: hex::encode(group.mls_group_id.as_slice()),
-                group_id: Some(group.mls_group_id.clone()),
-                name: group.name.clone(),
-                participants: vec![], // TODO: Extract participants from group
-                last_message: None,   // TODO: Get last message from storage
-                unread_count: 0,      // TODO: Implement unread tracking
-                is_group: true,
-            };
-            conversations.push(conversation);
+    /// Start the background task retrying pending outbox messages with backoff.
+    /// Does nothing if the task is already running.
+    fn spawn_outbox_retry(&self) {
+        if self.outbox_retry_running.swap(true, Ordering::SeqCst) {
+            return;
         }
 
-        Ok(conversations)
+        let client = self.client.clone();
+        let store = self.store.clone();
+        let relay_url = self.relay_url.clone();
+        let outbox = self.outbox.clone();
+        let events = self.events.clone();
+        let connection_status = self.connection_status.clone();
+        let running = self.outbox_retry_running.clone();
+        let mut shutdown = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                // Stop while offline; reconnecting restarts the task
+                if *connection_status.read().await != ConnectionStatus::Connected {
+                    break;
+                }
+                if deliver_outbox(&client, &store, &relay_url, &outbox, &events).await == 0 {
+                    break;
+                }
+
+                let now = chrono::Utc::now().timestamp();
+                let wait = outbox
+                    .read()
+                    .await
+                    .next_attempt_at()
+                    .map(|at| (at - now).max(1))
+                    .unwrap_or(1);
+                if !sleep_unless_shutdown(&mut shutdown, std::time::Duration::from_secs(wait as u64)).await {
+                    break;
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+        });
     }
 
-    async fn get_connection_status(&self) -> Result<ConnectionStatus> {
-        let status = self.connection_status.read().await;
-        Ok(*status)
+    /// Periodically re-check contact activity on the relay and let the UI know
+    /// when contacts come online or go offline. Runs while connected.
+    fn spawn_presence_refresh(&self) {
+        if self.presence_refresh_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let client = self.client.clone();
+        let relay_url = self.relay_url.clone();
+        let contacts = self.contacts.clone();
+        let events = self.events.clone();
+        let connection_status = self.connection_status.clone();
+        let running = self.presence_refresh_running.clone();
+        let mut shutdown = self.shutdown.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                if *connection_status.read().await != ConnectionStatus::Connected {
+                    break;
+                }
+                refresh_presence(&client, &relay_url, &contacts, &events).await;
+                if !sleep_unless_shutdown(&mut shutdown, presence::REFRESH_INTERVAL).await {
+                    break;
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+        });
     }
 
-    async fn send_message(&self, group_id: &GroupId, content: &str) -> Result<()> {
-        // CRITICAL: Fetch and process any MLS evolution events before sending
-        // This ensures our group state is synchronized with other members
-        self.fetch_and_process_group_events(group_id).await?;
+    /// Record that `pubkey` was active at `timestamp`, notifying the UI if they came online
+    async fn note_activity(&self, pubkey: &PublicKey, timestamp: i64) {
+        let now = chrono::Utc::now().timestamp();
+        let changed = presence::record_activity(&mut *self.contacts.write().await, pubkey, timestamp, now);
+        if let Some(contact) = changed {
+            self.notify_ui(UiUpdate::PresenceChanged {
+                pubkey: contact.pubkey,
+                online: contact.online,
+                last_seen: contact.last_seen,
+            })
+            .await;
+        }
+    }
 
-        let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
+    /// Current per-identity settings
+    pub async fn config(&self) -> DialogConfig {
+        self.config.read().await.clone()
+    }
 
-        // Create message rumor
-        let rumor = EventBuilder::new(Kind::TextNote, content).build(self.keys.public_key());
+    /// Turn sending read receipts on or off
+    pub async fn set_read_receipts(&self, enabled: bool) {
+        self.config.write().await.read_receipts = enabled;
+    }
 
-        // Create MLS message
-        let message_event = nostr_mls.create_message(group_id, rumor).await?;
-        
-        // Process locally for state sync (required in MLS)
-        let _ = nostr_mls.process_message(&message_event).await?;
+    /// Replace the policy incoming welcomes are screened with
+    pub async fn set_invite_policy(&self, policy: InvitePolicy) {
+        self.config.write().await.invite_policy = policy;
+    }
 
-        // Mark this message's event ID as displayed to prevent showing it again
-        // when we receive it back from the relay
-        {
-            let mut displayed_msgs = self.displayed_messages.write().await;
-            let group_displayed = displayed_msgs.entry(group_id.clone())
-                .or_insert_with(std::collections::HashSet::new);
-            group_displayed.insert(message_event.id.to_hex());
+    /// Number of welcomes received that were addressed to someone else
+    pub fn foreign_welcomes_skipped(&self) -> u64 {
+        self.foreign_welcomes_skipped.load(Ordering::Relaxed)
+    }
+
+    /// The MLS storage actor, for direct access to stored groups and messages
+    pub fn store(&self) -> &MlsStore {
+        &self.store
+    }
+
+    /// Forward an update to the UI, if one is subscribed
+    async fn notify_ui(&self, update: UiUpdate) {
+        self.events.send(update).await;
+    }
+
+    /// Tell the UI how a processed commit changed a group
+    async fn notify_group_changes(&self, group_id: &GroupId, changes: &[SystemEvent]) {
+        for update in group_change_updates(group_id, changes) {
+            self.notify_ui(update).await;
         }
+    }
 
-        // Send to relay
+    /// Publish our own commit to the group's relays, then apply it locally
+    async fn publish_commit(&self, client: &Client, group_id: &GroupId, commit: &Event) -> Result<()> {
+        // Our own commit must not be processed when the relay echoes it back
+        self.record_own_event(group_id, &commit.id).await;
+        let relays = group_relays(&self.store, group_id, &self.relay_url).await;
+        connect_relays(client, &relays).await;
         client
-            .send_event(&message_event)
+            .send_event_to(relays, commit)
             .await
-            .map_err(|e| DialogError::General(Box::new(e)))?;
+            .map_err(|e| DialogError::General(format!("Failed to publish commit: {}", e).into()))?;
+        self.store.merge_pending_commit(group_id).await
+    }
 
-        Ok(())
+    /// Record a system event for a group
+    async fn record_system_event(&self, group_id: &GroupId, epoch: u64, kind: SystemEventKind) {
+        let event = SystemEvent {
+            timestamp: chrono::Utc::now().timestamp(),
+            epoch,
+            kind,
+        };
+        if let Err(e) = self.system_events.write().await.record(group_id, vec![event]) {
+            tracing::warn!("Failed to record group event: {}", e);
+        }
     }
 
-    async fn create_conversation(&self, name: &str, participants: Vec<PublicKey>) -> Result<String> {
+    /// Create a group with `participants` and send them their welcomes.
+    ///
+    /// Strictly, any participant without a usable key package or welcome fails
+    /// the whole call. With `best_effort` the group is created with everyone
+    /// reachable and the others are listed in the report instead.
+    async fn create_mls_group(
+        &self,
+        name: &str,
+        description: &str,
+        participants: Vec<PublicKey>,
+        best_effort: bool,
+    ) -> Result<GroupCreationReport> {
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
 
         // Ensure we're connected
         let status = self.connection_status.read().await;
@@ -289,51 +670,26 @@ impl MlsService for RealMlsService {
             return Err(DialogError::General("Cannot create group without participants".into()));
         }
 
-        // Collect key package events for all participants
-        let mut key_package_events = Vec::new();
-        
-        for participant in &participants {
-            // Fetch key packages for this participant
-            let filter = Filter::new()
-                .kind(Kind::MlsKeyPackage)
-                .author(*participant);
-            
-            let events = client
-                .fetch_events(filter, std::time::Duration::from_secs(5))
-                .await
-                .map_err(|e| DialogError::General(format!("Failed to fetch key packages: {}", e).into()))?;
-            
-            if let Some(key_package_event) = events.first() {
-                // Validate the key package
-                nostr_mls.parse_key_package(key_package_event)
-                    .await
-                    .map_err(|e| DialogError::General(format!("Invalid key package from {}: {}", participant.to_hex(), e).into()))?;
-                
-                key_package_events.push(key_package_event.clone());
-            } else {
-                return Err(DialogError::General(
-                    format!("No key package found for participant: {}", participant.to_hex()).into()
-                ));
-            }
-        }
+        let (key_package_events, reachable, skipped) =
+            self.collect_key_packages(&client, &participants, best_effort).await?;
 
         // Set up group configuration
-        let admins = vec![self.keys.public_key()];  // Creator is admin, can add participants as admins later
+        let admins = vec![self.pubkey];  // Creator is admin, can add participants as admins later
         let relay_url = RelayUrl::parse(&self.relay_url)
             .map_err(|e| DialogError::General(format!("Invalid relay URL: {}", e).into()))?;
         
         let config = NostrGroupConfigData::new(
             name.to_string(),
-            String::new(),  // Empty description for now
+            description.to_string(),
             None,           // No picture
             None,           // No pinned messages
             vec![relay_url],
         );
 
         // Create the group
-        let group_create_result = nostr_mls
+        let group_create_result = self.store
             .create_group(
-                &self.keys.public_key(),
+                &self.pubkey,
                 key_package_events,
                 admins,
                 config,
@@ -343,161 +699,655 @@ impl MlsService for RealMlsService {
 
         // Send welcome messages to participants
         // Each welcome rumor corresponds to a specific participant in the same order
-        if group_create_result.welcome_rumors.len() != participants.len() {
+        if group_create_result.welcome_rumors.len() != reachable.len() {
             return Err(DialogError::General(
                 format!("Welcome rumor count mismatch: {} rumors for {} participants", 
                     group_create_result.welcome_rumors.len(), 
-                    participants.len()
+                    reachable.len()
                 ).into()
             ));
         }
-        
-        for (i, rumor) in group_create_result.welcome_rumors.into_iter().enumerate() {
-            let participant = &participants[i];
-            
-            // Send gift-wrapped invite (for denoise compatibility)
-            let gift_wrap_event = EventBuilder::gift_wrap(&self.keys, participant, rumor.clone(), None)
-                .await
-                .map_err(|e| DialogError::General(format!("Failed to create gift wrap for {}: {}", participant.to_hex(), e).into()))?;
-            
-            client
-                .send_event(&gift_wrap_event)
-                .await
-                .map_err(|e| DialogError::General(format!("Failed to send gift-wrapped welcome to {}: {}", participant.to_hex(), e).into()))?;
-            
-            // Send regular MLS welcome event (for whitenoise compatibility)
-            let welcome_event = EventBuilder::new(Kind::MlsWelcome, rumor.content.clone())
-                .tags(rumor.tags.clone())
-                .sign_with_keys(&self.keys)
-                .map_err(|e| DialogError::General(format!("Failed to sign MLS welcome for {}: {}", participant.to_hex(), e).into()))?;
-            
-            client
-                .send_event(&welcome_event)
-                .await
-                .map_err(|e| DialogError::General(format!("Failed to send MLS welcome to {}: {}", participant.to_hex(), e).into()))?;
-        }
+
+        let group = &group_create_result.group;
+        let undelivered = self
+            .deliver_welcomes(&client, &group.mls_group_id, &reachable, group_create_result.welcome_rumors, best_effort)
+            .await?;
+
+        self.record_system_event(&group.mls_group_id, group.epoch, SystemEventKind::Created { name: name.to_string() }).await;
+        self.record_system_event(
+            &group.mls_group_id,
+            group.epoch,
+            SystemEventKind::MembersAdded { actor: Some(self.pubkey), members: reachable.clone() },
+        ).await;
 
         // Refresh subscriptions to include the new group
         if let Err(e) = self.refresh_subscriptions().await {
             // Log error but don't fail the group creation
-            eprintln!("Warning: Failed to refresh subscriptions after group creation: {}", e);
+            tracing::warn!("Failed to refresh subscriptions after group creation: {}", e);
         }
 
-        // Return the group ID as hex string
-        Ok(hex::encode(group_create_result.group.mls_group_id.as_slice()))
+        Ok(GroupCreationReport {
+            group_id: hex::encode(group.mls_group_id.as_slice()),
+            added: reachable,
+            skipped,
+            undelivered,
+        })
     }
 
-    async fn add_contact(&self, pubkey: &str) -> Result<()> {
-        // Validate input is not empty
-        if pubkey.trim().is_empty() {
-            return Err(DialogError::General("Pubkey cannot be empty".into()));
+    /// Fetch and validate a key package for each participant. With
+    /// `best_effort`, participants without a usable one are skipped instead of
+    /// failing; it is still an error if nobody is left.
+    async fn collect_key_packages(
+        &self,
+        client: &Client,
+        participants: &[PublicKey],
+        best_effort: bool,
+    ) -> Result<(Vec<Event>, Vec<PublicKey>, Vec<(PublicKey, SkipReason)>)> {
+        let mut key_package_events = Vec::new();
+        let mut reachable = Vec::new();
+        let mut skipped = Vec::new();
+
+        for participant in participants {
+            // Fetch key packages for this participant, wherever they publish them
+            let outcome = match self.fetch_key_packages(client, participant).await {
+                Ok(events) => match events.first() {
+                    // Validate the key package
+                    Some(key_package_event) => match self.store.parse_key_package(key_package_event).await {
+                        Ok(_) => Ok(key_package_event.clone()),
+                        Err(e) => Err(SkipReason::InvalidKeyPackage(e.to_string())),
+                    },
+                    None => Err(SkipReason::NoKeyPackage),
+                },
+                Err(e) => Err(SkipReason::FetchFailed(e.to_string())),
+            };
+
+            match outcome {
+                Ok(key_package_event) => {
+                    key_package_events.push(key_package_event);
+                    reachable.push(*participant);
+                }
+                Err(reason) if best_effort => skipped.push((*participant, reason)),
+                Err(reason) => {
+                    return Err(DialogError::General(format!("{} for participant: {}", reason, participant.to_hex()).into()));
+                }
+            }
         }
 
-        let pubkey = pubkey.trim();
+        if reachable.is_empty() {
+            let reasons: Vec<String> = skipped
+                .iter()
+                .map(|(pubkey, reason)| format!("{}: {}", &pubkey.to_hex()[..16], reason))
+                .collect();
+            return Err(DialogError::General(
+                format!("None of the participants could be added ({})", reasons.join("; ")).into(),
+            ));
+        }
 
-        // Parse the pubkey string - could be bech32 (npub1...) or hex
-        let public_key = if pubkey.starts_with("npub1") {
-            // Parse bech32 format
-            PublicKey::from_bech32(pubkey)
-                .map_err(|e| DialogError::General(format!("Invalid bech32 pubkey: {}", e).into()))?
-        } else {
-            // Try to parse as hex
-            PublicKey::from_hex(pubkey)
-                .map_err(|e| DialogError::General(format!("Invalid hex pubkey: {}", e).into()))?
-        };
+        Ok((key_package_events, reachable, skipped))
+    }
 
-        // Check if we're trying to add ourselves
-        if public_key == self.keys.public_key() {
-            return Err(DialogError::General("Cannot add yourself as a contact".into()));
-        }
+    /// Key packages of `participant`, newest first. Looks on our relay and on
+    /// every relay their kind 10051 (or NIP-65) relay list advertises.
+    async fn fetch_key_packages(&self, client: &Client, participant: &PublicKey) -> Result<Vec<Event>> {
+        let timeout = std::time::Duration::from_secs(5);
+        let filter = Filter::new()
+            .kind(Kind::MlsKeyPackage)
+            .author(*participant);
 
-        // Check if contact already exists
-        {
-            let contacts = self.contacts.read().await;
-            if contacts.contains_key(&public_key) {
-                return Err(DialogError::General("Contact already exists".into()));
-            }
-        }
+        let lists = client
+            .fetch_events_from([self.relay_url.as_str()], relay_lists::relay_lists_filter(*participant), timeout)
+            .await
+            .map_err(|e| DialogError::General(format!("Failed to fetch relay lists: {}", e).into()))?;
+        let mut key_packages: Vec<Event> = client
+            .fetch_events_from([self.relay_url.as_str()], filter.clone(), timeout)
+            .await
+            .map_err(|e| DialogError::General(format!("Failed to fetch key packages: {}", e).into()))?
+            .into_iter()
+            .collect();
 
-        // Check if we're connected before trying to load profile
-        let connection_status = {
-            let status = self.connection_status.read().await;
-            *status
-        };
-        
-        let name = if connection_status != ConnectionStatus::Connected {
-            // Not connected, use truncated pubkey (warning handled at UI layer)
-            if pubkey.starts_with("npub1") {
-                format!("{}... (offline)", &pubkey[0..12])
-            } else {
-                format!("{}... (offline)", &pubkey[0..8])
-            }
-        } else {
-            // Try to load the profile for this contact to get their real name
-            match self.load_profile(&public_key).await {
-                Ok(Some(profile)) => {
-                    // Use the best available name from the profile
-                    if let Some(display_name) = profile.display_name() {
-                        display_name.to_string()
-                    } else {
-                        // Profile exists but no name, use truncated pubkey
-                        if pubkey.starts_with("npub1") {
-                            format!("{}... (no name)", &pubkey[0..12])
-                        } else {
-                            format!("{}... (no name)", &pubkey[0..8])
-                        }
-                    }
-                },
-                Ok(None) => {
-                    // No profile found on relay
-                    if pubkey.starts_with("npub1") {
-                        format!("{}... (no profile)", &pubkey[0..12])
-                    } else {
-                        format!("{}... (no profile)", &pubkey[0..8])
-                    }
-                },
-                Err(_) => {
-                    // Error loading profile, use truncated pubkey
-                    if pubkey.starts_with("npub1") {
-                        format!("{}... (load error)", &pubkey[0..12])
-                    } else {
-                        format!("{}... (load error)", &pubkey[0..8])
-                    }
+        let own_relay = RelayUrl::parse(&self.relay_url).ok();
+        let elsewhere: Vec<RelayUrl> = relay_lists::key_package_relays(lists.iter())
+            .into_iter()
+            .filter(|url| Some(url) != own_relay.as_ref())
+            .collect();
+
+        if !elsewhere.is_empty() {
+            let remote = remote_client(&elsewhere).await?;
+            let fetched = remote.fetch_events(filter, timeout).await;
+            remote.shutdown().await;
+            match fetched {
+                Ok(events) => key_packages.extend(events),
+                // Only fatal when we have nothing from our own relay either
+                Err(e) if key_packages.is_empty() => {
+                    return Err(DialogError::General(format!("Failed to fetch key packages from advertised relays: {}", e).into()));
                 }
+                Err(_) => {}
             }
-        };
-
-        let contact = Contact {
-            name,
-            pubkey: public_key,
-            online: false, // Default to offline since we don't have presence info yet
-        };
-
-        // Store the contact in our runtime storage
-        let mut contacts = self.contacts.write().await;
-        contacts.insert(public_key, contact);
+        }
 
-        Ok(())
+        key_packages.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+        key_packages.dedup_by_key(|event| event.id);
+        Ok(key_packages)
     }
 
-    async fn switch_conversation(&self, _conversation_id: &str) -> Result<()> {
+    /// Send each new member their welcome. Welcomes that cannot be published
+    /// are kept, so calling `add_members` for them again sends them later.
+    /// Without `best_effort` the first failure is returned instead.
+    async fn deliver_welcomes(
+        &self,
+        client: &Client,
+        group_id: &GroupId,
+        members: &[PublicKey],
+        rumors: Vec<UnsignedEvent>,
+        best_effort: bool,
+    ) -> Result<Vec<(PublicKey, String)>> {
+        let group_hex = hex::encode(group_id.as_slice());
+        let mut undelivered = Vec::new();
+        for (member, rumor) in members.iter().zip(rumors) {
+            if let Err(e) = self.send_welcome(client, member, rumor.clone()).await {
+                let mut kept = self.undelivered_welcomes.write().await;
+                kept.get_mut().insert(format!("{}:{}", group_hex, member.to_hex()), rumor);
+                kept.save()?;
+                if !best_effort {
+                    return Err(e);
+                }
+                undelivered.push((*member, e.to_string()));
+            }
+        }
+        Ok(undelivered)
+    }
+
+    /// Send a welcome rumor to a new member using the configured transport,
+    /// on our relay and on the relays their relay lists say they read from.
+    /// Fails only when a welcome reached none of them.
+    async fn send_welcome(&self, client: &Client, participant: &PublicKey, rumor: UnsignedEvent) -> Result<()> {
+        let mut welcomes = Vec::new();
+        if self.welcome_transport.gift_wrap() {
+            // Send gift-wrapped invite (for denoise compatibility)
+            let gift_wrap_event = EventBuilder::gift_wrap(self.signer.as_ref(), participant, rumor.clone(), None)
+                .await
+                .map_err(|e| DialogError::General(format!("Failed to create gift wrap for {}: {}", participant.to_hex(), e).into()))?;
+            welcomes.push(("gift-wrapped welcome", gift_wrap_event));
+        }
+
+        if self.welcome_transport.plain() {
+            // Send regular MLS welcome event (for whitenoise compatibility),
+            // tagged for the recipient so they can filter for their own welcomes
+            let welcome_event = client
+                .sign_event_builder(
+                    EventBuilder::new(Kind::MlsWelcome, rumor.content.clone())
+                        .tags(rumor.tags.clone())
+                        .tag(Tag::public_key(*participant)),
+                )
+                .await
+                .map_err(|e| DialogError::General(format!("Failed to sign MLS welcome for {}: {}", participant.to_hex(), e).into()))?;
+            welcomes.push(("MLS welcome", welcome_event));
+        }
+
+        let inbox = self.inbox_relays_elsewhere(client, participant).await;
+        let remote = if inbox.is_empty() { None } else { remote_client(&inbox).await.ok() };
+
+        let mut result = Ok(());
+        for (label, event) in &welcomes {
+            let home = client.send_event_to([self.relay_url.as_str()], event).await;
+            let away = match &remote {
+                Some(remote) => remote.send_event(event).await.is_ok(),
+                None => false,
+            };
+            if let Err(e) = home {
+                if !away {
+                    result = Err(DialogError::General(
+                        format!("Failed to send {} to {}: {}", label, participant.to_hex(), e).into(),
+                    ));
+                }
+            }
+        }
+        if let Some(remote) = remote {
+            remote.shutdown().await;
+        }
+        result
+    }
+
+    /// Relays other than ours that `participant` advertises for receiving
+    async fn inbox_relays_elsewhere(&self, client: &Client, participant: &PublicKey) -> Vec<RelayUrl> {
+        let own_relay = RelayUrl::parse(&self.relay_url).ok();
+        let Ok(lists) = client
+            .fetch_events_from([self.relay_url.as_str()], relay_lists::relay_lists_filter(*participant), std::time::Duration::from_secs(5))
+            .await
+        else {
+            return Vec::new();
+        };
+        relay_lists::inbox_relays(lists.iter())
+            .into_iter()
+            .filter(|url| Some(url) != own_relay.as_ref())
+            .collect()
+    }
+
+    /// Name to show for `pubkey`: contact name, then profile name, then a short key
+    async fn display_name_of(&self, pubkey: &PublicKey) -> String {
+        if let Some(contact) = self.contacts.read().await.get(pubkey) {
+            return contact.name.clone();
+        }
+        if let Some(name) = self.profiles.read().await.get(pubkey).and_then(|p| p.display_name()) {
+            return name.to_string();
+        }
+        format!("{}...", &pubkey.to_hex()[..8])
+    }
+
+    fn invite_screen(&self) -> InviteScreen {
+        InviteScreen {
+            pubkey: self.pubkey,
+            foreign_skipped: self.foreign_welcomes_skipped.clone(),
+            gate: self.invite_gate.clone(),
+            config: self.config.clone(),
+            contacts: self.contacts.clone(),
+            system_events: self.system_events.clone(),
+        }
+    }
+
+    /// Run a welcome through the invite policy and report the decision to the UI.
+    /// Kept welcomes are processed, and joined right away when accepted.
+    /// Returns `None` for welcomes handled before.
+    async fn screen_welcome(
+        &self,
+        store: &MlsStore,
+        wrapper_id: EventId,
+        rumor: &UnsignedEvent,
+        inviter: PublicKey,
+    ) -> Result<Option<InviteDecision>> {
+        let screened = self.invite_screen().screen(store, wrapper_id, rumor, inviter).await?;
+        let Some((decision, invite)) = screened else {
+            return Ok(None);
+        };
+        self.notify_ui(UiUpdate::InviteDecided { inviter, invite, decision: decision.clone() }).await;
+        Ok(Some(decision))
+    }
+
+    /// Encrypt a control rumor (receipt, ...) for the group and publish it directly.
+    /// These bypass the outbox: a stale one is not worth retrying.
+    async fn send_group_rumor(&self, group_id: &GroupId, rumor: UnsignedEvent) -> Result<()> {
+        let message_event = self.store.create_message(group_id, rumor).await?;
+        let _ = self.store.process_message(&message_event).await?;
+
+        self.record_own_event(group_id, &message_event.id).await;
+
+        let relays = group_relays(&self.store, group_id, &self.relay_url).await;
+        let client = self.client.read().await;
+        connect_relays(&client, &relays).await;
+        client
+            .send_event_to(relays, &message_event)
+            .await
+            .map_err(|e| DialogError::General(format!("Failed to publish to group: {}", e).into()))?;
+        Ok(())
+    }
+
+    /// Note an event we created ourselves, so it is never processed when it comes back
+    async fn record_own_event(&self, group_id: &GroupId, event_id: &EventId) {
+        if let Err(e) = self.ledger.record(group_id, event_id, EventOutcome::OwnMessage).await {
+            tracing::warn!("Failed to record sent event: {}", e);
+        }
+    }
+
+    /// Find a stored group by any form of reference
+    async fn find_group(&self, group: &GroupRef) -> Result<group_types::Group> {
+        let groups = self.store.get_groups()
+            .await
+            .map_err(|e| DialogError::General(Box::new(e)))?;
+
+        group
+            .resolve(&groups, |g| (&g.mls_group_id, g.nostr_group_id.as_slice(), g.name.as_str()))
+            .cloned()
+    }
+}
+
+#[async_trait]
+impl MlsService for RealMlsService {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn get_contacts(&self) -> Result<Vec<Contact>> {
+        // Return all contacts from runtime storage, with presence as of now
+        let now = chrono::Utc::now().timestamp();
+        let contacts = self.contacts.read().await;
+        Ok(contacts
+            .values()
+            .cloned()
+            .map(|mut contact| {
+                contact.online = presence::is_online(contact.last_seen, now);
+                contact
+            })
+            .collect())
+    }
+
+    async fn get_conversations(&self) -> Result<Vec<Conversation>> {
+        let groups = self.store.get_groups()
+            .await
+            .map_err(|e| DialogError::General(Box::new(e)))?;
+
+        let direct_groups = self.direct_groups.read().await.get().clone();
+        let mut conversations = Vec::new();
+        for group in groups {
+            let participants: Vec<PublicKey> = self.store
+                .get_members(&group.mls_group_id)
+                .await
+                .map(|members| members.into_iter().collect())
+                .unwrap_or_default();
+            let flagged = direct_groups.contains(&hex::encode(group.mls_group_id.as_slice()));
+            let is_group = !is_direct_message(&group, &participants, flagged);
+            // A direct message is named after the other person
+            let name = match participants.iter().find(|pk| **pk != self.pubkey) {
+                Some(other) if !is_group => self.display_name_of(other).await,
+                _ => group.name.clone(),
+            };
+            let conversation = Conversation {
+                id: hex::encode(group.mls_group_id.as_slice()),
+                group_id: Some(group.mls_group_id.clone()),
+                name,
+                participants,
+                last_message: None,   // TODO: Get last message from storage
+                unread_count: 0,      // TODO: Implement unread tracking
+                is_group,
+            };
+            conversations.push(conversation);
+        }
+
+        Ok(conversations)
+    }
+
+    async fn get_connection_status(&self) -> Result<ConnectionStatus> {
+        let status = self.connection_status.read().await;
+        Ok(*status)
+    }
+
+    async fn send_message(&self, group_id: &GroupId, content: &str) -> Result<SendReport> {
+        let connected = *self.connection_status.read().await == ConnectionStatus::Connected;
+
+        // CRITICAL: Fetch and process any MLS evolution events before sending
+        // This ensures our group state is synchronized with other members.
+        // While offline we encrypt against the last known epoch instead.
+        if connected {
+            self.fetch_and_process_group_events(group_id).await?;
+        }
+
+        let (message_id, message_event) = {
+            // Create message rumor
+            let mut rumor = EventBuilder::new(Kind::TextNote, content).build(self.pubkey);
+            rumor.ensure_id();
+            let message_id = rumor.id.map(|id| id.to_hex()).unwrap_or_default();
+
+            // Create MLS message
+            let message_event = self.store.create_message(group_id, rumor).await?;
+
+            // Process locally for state sync (required in MLS)
+            let _ = self.store.process_message(&message_event).await?;
+
+            // Queue before publishing so the message survives a failed send
+            self.outbox
+                .write()
+                .await
+                .enqueue(group_id, message_id.clone(), message_event.clone())?;
+            (message_id, message_event)
+        };
+
+        // Already applied locally; the copy the relay sends back must be skipped
+        self.record_own_event(group_id, &message_event.id).await;
+
+        // The message itself tells everyone we stopped typing
+        self.typing_sent.write().await.remove(group_id);
+
+        // Publish now if we can; anything left pending is retried in the background
+        let mut report = None;
+        if connected {
+            let entry = self.outbox.read().await.get(&message_id).cloned();
+            if let Some(entry) = entry {
+                let now = chrono::Utc::now().timestamp();
+                report = publish_outbox_entry(&self.client, &self.store, &self.relay_url, &self.outbox, &self.events, &entry, now).await;
+            }
+            if self.outbox.read().await.pending_count() > 0 {
+                self.spawn_outbox_retry();
+            }
+        }
+
+        Ok(report.unwrap_or(SendReport {
+            message_id,
+            event_id: message_event.id.to_hex(),
+            relays: Vec::new(),
+            status: DeliveryStatus::Pending,
+        }))
+    }
+
+    async fn create_conversation(&self, name: &str, participants: Vec<PublicKey>) -> Result<String> {
+        Ok(self.create_mls_group(name, "", participants, false).await?.group_id)
+    }
+
+    async fn create_conversation_best_effort(&self, name: &str, participants: Vec<PublicKey>) -> Result<GroupCreationReport> {
+        self.create_mls_group(name, "", participants, true).await
+    }
+
+    async fn add_members(&self, group_id: &GroupId, participants: Vec<PublicKey>) -> Result<GroupCreationReport> {
+        if *self.connection_status.read().await != ConnectionStatus::Connected {
+            return Err(DialogError::General("Not connected to relay".into()));
+        }
+        let group = self.store
+            .group(group_id)
+            .await?
+            .ok_or_else(|| DialogError::General("Group not found".into()))?;
+        if !group.admin_pubkeys.contains(&self.pubkey) {
+            return Err(DialogError::General("Only group admins can add members".into()));
+        }
+
+        let client = self.client.read().await;
+        let group_hex = hex::encode(group_id.as_slice());
+        let members = self.store.get_members(group_id).await?;
+        let mut added = Vec::new();
+        let mut undelivered = Vec::new();
+        let mut invite = Vec::new();
+        for participant in participants {
+            if !members.contains(&participant) {
+                invite.push(participant);
+                continue;
+            }
+            // Already a member: all that is left is a welcome that never went out
+            let key = format!("{}:{}", group_hex, participant.to_hex());
+            let kept = self.undelivered_welcomes.read().await.get().get(&key).cloned();
+            let Some(rumor) = kept else {
+                continue;
+            };
+            match self.send_welcome(&client, &participant, rumor).await {
+                Ok(()) => {
+                    let mut kept = self.undelivered_welcomes.write().await;
+                    kept.get_mut().remove(&key);
+                    kept.save()?;
+                    added.push(participant);
+                }
+                Err(e) => undelivered.push((participant, e.to_string())),
+            }
+        }
+
+        let mut skipped = Vec::new();
+        if !invite.is_empty() {
+            let (key_packages, reachable, missing) = self.collect_key_packages(&client, &invite, true).await?;
+            let update = self.store.add_members(group_id, key_packages).await?;
+            self.publish_commit(&client, group_id, &update.evolution_event).await?;
+
+            let rumors = update.welcome_rumors.unwrap_or_default();
+            undelivered.extend(self.deliver_welcomes(&client, group_id, &reachable, rumors, true).await?);
+            let epoch = self.store.group(group_id).await?.map_or(group.epoch, |g| g.epoch);
+            self.record_system_event(
+                group_id,
+                epoch,
+                SystemEventKind::MembersAdded { actor: Some(self.pubkey), members: reachable.clone() },
+            ).await;
+            added.extend(reachable);
+            skipped = missing;
+        }
+
+        Ok(GroupCreationReport {
+            group_id: group_hex,
+            added,
+            skipped,
+            undelivered,
+        })
+    }
+
+    async fn open_direct_conversation(&self, pubkey: &PublicKey) -> Result<Conversation> {
+        if *pubkey == self.pubkey {
+            return Err(DialogError::General("Cannot open a direct conversation with yourself".into()));
+        }
+
+        // Prefer a group we opened as a DM, then an unnamed one they invited us
+        // to. Named groups are chats of their own and left groups are history.
+        let direct_groups = self.direct_groups.read().await.get().clone();
+        let mut existing = None;
+        for group in self.store.get_groups().await? {
+            if group.state == group_types::GroupState::Inactive {
+                continue;
+            }
+            let members = self.store.get_members(&group.mls_group_id).await?;
+            if members.len() != 2 || !members.contains(pubkey) {
+                continue;
+            }
+            let group_hex = hex::encode(group.mls_group_id.as_slice());
+            if direct_groups.contains(&group_hex) {
+                existing = Some(group_hex);
+                break;
+            }
+            if group.name.is_empty() && existing.is_none() {
+                existing = Some(group_hex);
+            }
+        }
+
+        let group_hex = match existing {
+            Some(group_hex) => group_hex,
+            None => self.create_mls_group("", "", vec![*pubkey], false).await?.group_id,
+        };
+        {
+            let mut direct_groups = self.direct_groups.write().await;
+            if direct_groups.get_mut().insert(group_hex.clone()) {
+                direct_groups.save()?;
+            }
+        }
+        self.get_conversations()
+            .await?
+            .into_iter()
+            .find(|c| c.id == group_hex)
+            .ok_or_else(|| DialogError::General("Created direct conversation not found".into()))
+    }
+
+    async fn add_contact(&self, pubkey: &str) -> Result<()> {
+        // Validate input is not empty
+        if pubkey.trim().is_empty() {
+            return Err(DialogError::General("Pubkey cannot be empty".into()));
+        }
+
+        let pubkey = pubkey.trim();
+
+        // Parse the pubkey string - could be bech32 (npub1...) or hex
+        let public_key = if pubkey.starts_with("npub1") {
+            // Parse bech32 format
+            PublicKey::from_bech32(pubkey)
+                .map_err(|e| DialogError::General(format!("Invalid bech32 pubkey: {}", e).into()))?
+        } else {
+            // Try to parse as hex
+            PublicKey::from_hex(pubkey)
+                .map_err(|e| DialogError::General(format!("Invalid hex pubkey: {}", e).into()))?
+        };
+
+        // Check if we're trying to add ourselves
+        if public_key == self.pubkey {
+            return Err(DialogError::General("Cannot add yourself as a contact".into()));
+        }
+
+        // Check if contact already exists
+        {
+            let contacts = self.contacts.read().await;
+            if contacts.contains_key(&public_key) {
+                return Err(DialogError::General("Contact already exists".into()));
+            }
+        }
+
+        // Check if we're connected before trying to load profile
+        let connection_status = {
+            let status = self.connection_status.read().await;
+            *status
+        };
+        
+        let name = if connection_status != ConnectionStatus::Connected {
+            // Not connected, use truncated pubkey (warning handled at UI layer)
+            if pubkey.starts_with("npub1") {
+                format!("{}... (offline)", &pubkey[0..12])
+            } else {
+                format!("{}... (offline)", &pubkey[0..8])
+            }
+        } else {
+            // Try to load the profile for this contact to get their real name
+            match self.load_profile(&public_key).await {
+                Ok(Some(profile)) => {
+                    // Use the best available name from the profile
+                    if let Some(display_name) = profile.display_name() {
+                        display_name.to_string()
+                    } else {
+                        // Profile exists but no name, use truncated pubkey
+                        if pubkey.starts_with("npub1") {
+                            format!("{}... (no name)", &pubkey[0..12])
+                        } else {
+                            format!("{}... (no name)", &pubkey[0..8])
+                        }
+                    }
+                },
+                Ok(None) => {
+                    // No profile found on relay
+                    if pubkey.starts_with("npub1") {
+                        format!("{}... (no profile)", &pubkey[0..12])
+                    } else {
+                        format!("{}... (no profile)", &pubkey[0..8])
+                    }
+                },
+                Err(_) => {
+                    // Error loading profile, use truncated pubkey
+                    if pubkey.starts_with("npub1") {
+                        format!("{}... (load error)", &pubkey[0..12])
+                    } else {
+                        format!("{}... (load error)", &pubkey[0..8])
+                    }
+                }
+            }
+        };
+
+        let contact = Contact {
+            name,
+            pubkey: public_key,
+            online: false, // Updated from observed activity (see `presence`)
+            last_seen: None,
+            status: None,
+        };
+
+        self.contacts.write().await.insert(public_key, contact);
+        self.save_contacts().await
+    }
+
+    async fn switch_conversation(&self, _conversation_id: &str) -> Result<()> {
         // TODO: Implement conversation switching with real state management
         Ok(())
     }
 
+    async fn resolve_group(&self, group: &GroupRef) -> Result<GroupId> {
+        Ok(self.find_group(group).await?.mls_group_id)
+    }
+
     async fn get_active_conversation(&self) -> Result<Option<String>> {
         // TODO: Implement real active conversation tracking
         Ok(None)
     }
 
     async fn get_pending_invites_count(&self) -> Result<usize> {
-        let nostr_mls = self.nostr_mls.read().await;
-        
-        let pending_welcomes = nostr_mls.get_pending_welcomes()
+        let pending_welcomes = self.store.get_pending_welcomes()
             .await
             .map_err(|e| DialogError::General(Box::new(e)))?;
-        
-        Ok(pending_welcomes.len())
+        let config = self.config.read().await;
+
+        Ok(pending_welcomes.iter().filter(|w| !config.invite_policy.blocked.contains(&w.welcomer)).count())
     }
 
     async fn toggle_connection(&self) -> Result<ConnectionStatus> {
@@ -523,7 +1373,7 @@ impl MlsService for RealMlsService {
     }
 
     async fn get_own_pubkey(&self) -> Result<PublicKey> {
-        Ok(self.keys.public_key())
+        Ok(self.pubkey)
     }
 
     async fn load_profile(&self, pubkey: &PublicKey) -> Result<Option<Profile>> {
@@ -546,7 +1396,7 @@ impl MlsService for RealMlsService {
 
         // Query the relay for the profile event
         let events = client
-            .fetch_events(filter, std::time::Duration::from_secs(5))
+            .fetch_events_from([self.relay_url.as_str()], filter, std::time::Duration::from_secs(5))
             .await
             .map_err(|e| DialogError::General(format!("Failed to query profile: {}", e).into()))?;
 
@@ -589,7 +1439,7 @@ impl MlsService for RealMlsService {
 
         // Publish the event to the relay
         client
-            .send_event(&signed_event)
+            .send_event_to([self.relay_url.as_str()], &signed_event)
             .await
             .map_err(|e| DialogError::General(format!("Failed to publish profile: {}", e).into()))?;
 
@@ -602,7 +1452,6 @@ impl MlsService for RealMlsService {
 
     async fn publish_key_packages(&self) -> Result<Vec<String>> {
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
 
         // Ensure we're connected
         let status = self.connection_status.read().await;
@@ -628,32 +1477,54 @@ impl MlsService for RealMlsService {
             let relay_url = RelayUrl::parse(&self.relay_url)
                 .map_err(|e| DialogError::General(format!("Invalid relay URL: {}", e).into()))?;
             let relay_urls = vec![relay_url];
-            let (key_package_encoded, tags) = nostr_mls
-                .create_key_package_for_event(&self.keys.public_key(), relay_urls)
+            let (key_package_encoded, tags) = self.store
+                .create_key_package_for_event(&self.pubkey, relay_urls)
                 .await?;
 
-            // Build the key package event
-            let key_package_event = EventBuilder::new(Kind::MlsKeyPackage, key_package_encoded)
-                .tags(tags)
-                .sign_with_keys(&self.keys)
+            // Build and sign the key package event
+            let key_package_event = client
+                .sign_event_builder(EventBuilder::new(Kind::MlsKeyPackage, key_package_encoded).tags(tags))
+                .await
                 .map_err(|e| DialogError::General(format!("Failed to sign key package: {}", e).into()))?;
 
             // Publish the key package event
             let event_id = client
-                .send_event(&key_package_event)
+                .send_event_to([self.relay_url.as_str()], &key_package_event)
                 .await
                 .map_err(|e| DialogError::General(format!("Failed to publish key package: {}", e).into()))?;
             
             event_ids.push(event_id.to_hex());
         }
 
+        // Tell inviters where to find our key packages, and where to reach us.
+        // Lists we published before (maybe from another client) are extended,
+        // not replaced, and left alone when they already name our relay.
+        let relay_url = RelayUrl::parse(&self.relay_url)
+            .map_err(|e| DialogError::General(format!("Invalid relay URL: {}", e).into()))?;
+        let existing = client
+            .fetch_events_from([self.relay_url.as_str()], relay_lists::relay_lists_filter(self.pubkey), std::time::Duration::from_secs(5))
+            .await
+            .map_err(|e| DialogError::General(format!("Failed to fetch our relay lists: {}", e).into()))?;
+        for list in relay_lists::merged_lists(existing.iter(), std::slice::from_ref(&relay_url)) {
+            let list_event = client
+                .sign_event_builder(list)
+                .await
+                .map_err(|e| DialogError::General(format!("Failed to sign relay list: {}", e).into()))?;
+            client
+                .send_event_to([self.relay_url.as_str()], &list_event)
+                .await
+                .map_err(|e| DialogError::General(format!("Failed to publish relay list: {}", e).into()))?;
+        }
+
         Ok(event_ids)
     }
 
     async fn list_pending_invites(&self) -> Result<InviteListResult> {
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
 
+        // Gift wraps are withheld by a relay we failed to authenticate with;
+        // that connection is reported as down, so say why first
+        self.check_auth().await?;
         // Ensure we're connected
         let status = self.connection_status.read().await;
         if *status != ConnectionStatus::Connected {
@@ -666,28 +1537,40 @@ impl MlsService for RealMlsService {
         // Fetch and process gift-wrapped events (for denoise compatibility)
         let giftwrap_filter = Filter::new()
             .kind(Kind::GiftWrap)
-            .pubkey(self.keys.public_key());
+            .pubkey(self.pubkey);
         
         let giftwrap_events = client
-            .fetch_events(giftwrap_filter, std::time::Duration::from_secs(5))
+            .fetch_events_from([self.relay_url.as_str()], giftwrap_filter, std::time::Duration::from_secs(5))
             .await
             .map_err(|e| DialogError::General(format!("Failed to fetch gift wraps: {}", e).into()))?;
 
+        // Welcomes the invite policy joined right away
+        let mut joined = false;
+
         // Process gift-wrapped events to extract welcome messages
+        let invite_screen = self.invite_screen();
         for event in giftwrap_events {
+            if !invite_screen.wants(&event).await {
+                continue;
+            }
             // Try to extract rumor from gift wrap using NIP-59
-            match nip59::extract_rumor(&self.keys, &event).await {
-                Ok(unwrapped_gift) => {
-                    // Process the welcome rumor
-                    if let Err(e) = nostr_mls.process_welcome(&event.id, &unwrapped_gift.rumor).await {
-                        // Collect error for UI display
-                        processing_errors.push(format!(
-                            "⚠️  Failed to process gift-wrapped welcome from {}: {}", 
-                            unwrapped_gift.sender.to_hex()[0..16].to_string(),
-                            e
-                        ));
+            match client.unwrap_gift_wrap(&event).await {
+                Ok(unwrapped_gift) if unwrapped_gift.rumor.kind == Kind::MlsWelcome => {
+                    // Screen and process the welcome rumor
+                    match self.screen_welcome(&self.store, event.id, &unwrapped_gift.rumor, unwrapped_gift.sender).await {
+                        Ok(Some(decision)) => joined |= decision == InviteDecision::Accepted,
+                        Ok(None) => {}
+                        Err(e) => {
+                            // Collect error for UI display
+                            processing_errors.push(format!(
+                                "⚠️  Failed to process gift-wrapped welcome from {}: {}",
+                                unwrapped_gift.sender.to_hex()[0..16].to_string(),
+                                e
+                            ));
+                        }
                     }
                 }
+                Ok(_) => {}
                 Err(e) => {
                     // Error unwrapping gift wrap - might not be for us
                     processing_errors.push(format!(
@@ -699,17 +1582,26 @@ impl MlsService for RealMlsService {
             }
         }
 
-        // Fetch and process regular MLS welcome events (for whitenoise compatibility)
-        let welcome_filter = Filter::new()
-            .kind(Kind::MlsWelcome);
-        
-        let welcome_events = client
-            .fetch_events(welcome_filter, std::time::Duration::from_secs(5))
-            .await
-            .map_err(|e| DialogError::General(format!("Failed to fetch MLS welcomes: {}", e).into()))?;
-
+        // Fetch and process regular MLS welcome events (opt-in whitenoise compatibility)
+        let welcome_events = if self.config.read().await.plain_welcomes {
+            let welcome_filter = Filter::new()
+                .kind(Kind::MlsWelcome)
+                .pubkey(self.pubkey);
+            client
+                .fetch_events_from([self.relay_url.as_str()], welcome_filter, std::time::Duration::from_secs(5))
+                .await
+                .map_err(|e| DialogError::General(format!("Failed to fetch MLS welcomes: {}", e).into()))?
+                .into_iter()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
         // Process regular MLS welcome events directly
         for event in welcome_events {
+            if !invite_screen.wants(&event).await {
+                continue;
+            }
             // Create an UnsignedEvent from the event data to match the API
             let unsigned_event = UnsignedEvent {
                 id: Some(event.id),
@@ -720,30 +1612,37 @@ impl MlsService for RealMlsService {
                 tags: event.tags.clone(),
             };
             
-            // Process the MLS welcome event using process_welcome (same as gift-wrapped)
-            if let Err(e) = nostr_mls.process_welcome(&event.id, &unsigned_event).await {
-                // Collect error for UI display
-                processing_errors.push(format!(
-                    "⚠️  Failed to process MLS welcome from {}: {}", 
-                    event.pubkey.to_hex()[0..16].to_string(),
-                    e
-                ));
+            // Screen and process the MLS welcome event (same as gift-wrapped)
+            match self.screen_welcome(&self.store, event.id, &unsigned_event, event.pubkey).await {
+                Ok(Some(decision)) => joined |= decision == InviteDecision::Accepted,
+                Ok(None) => {}
+                Err(e) => {
+                    // Collect error for UI display
+                    processing_errors.push(format!(
+                        "⚠️  Failed to process MLS welcome from {}: {}",
+                        event.pubkey.to_hex()[0..16].to_string(),
+                        e
+                    ));
+                }
             }
         }
 
-        // Get pending welcomes from storage
-        let pending_welcomes = nostr_mls.get_pending_welcomes().await?;
-        
-        // Convert to our PendingInvite type
-        let invites = pending_welcomes.into_iter().map(|welcome| {
-            PendingInvite {
-                group_id: welcome.mls_group_id,
-                group_name: welcome.group_name,
-                inviter: None, // TODO: Extract inviter from welcome data if available
-                member_count: welcome.member_count as usize,
-                timestamp: chrono::Utc::now().timestamp(), // TODO: Get actual timestamp from event
+        if joined {
+            if let Err(e) = self.refresh_subscriptions().await {
+                tracing::warn!("Failed to refresh subscriptions after joining invited groups: {}", e);
             }
-        }).collect();
+        }
+
+        // Get pending welcomes from storage, hiding those from inviters blocked since
+        let pending_welcomes = self.store.get_pending_welcomes().await?;
+        let blocked = self.config.read().await.invite_policy.blocked.clone();
+
+        // Convert to our PendingInvite type
+        let invites = pending_welcomes
+            .iter()
+            .filter(|welcome| !blocked.contains(&welcome.welcomer))
+            .map(pending_invite)
+            .collect();
 
         Ok(InviteListResult {
             invites,
@@ -751,39 +1650,39 @@ impl MlsService for RealMlsService {
         })
     }
 
-    async fn accept_invite(&self, group_id: &str) -> Result<()> {
-        let nostr_mls = self.nostr_mls.read().await;
+    async fn accept_invite(&self, invite: &GroupRef) -> Result<()> {
+        // Find the matching welcome among pending invites
+        let pending_welcomes = self.store.get_pending_welcomes().await?;
+        let welcome = invite
+            .resolve(&pending_welcomes, |w| (&w.mls_group_id, w.nostr_group_id.as_slice(), w.group_name.as_str()))
+            .map_err(|e| DialogError::General(format!("No matching pending invite: {}", e).into()))?;
+        let group_id = welcome.mls_group_id.clone();
 
-        // Parse the group ID from hex string
-        let group_id_bytes = hex::decode(group_id)
-            .map_err(|e| DialogError::General(format!("Invalid group ID: {}", e).into()))?;
-        let group_id = GroupId::from_slice(&group_id_bytes);
+        self.store.accept_welcome(welcome).await?;
 
-        // Get pending welcomes
-        let pending_welcomes = nostr_mls.get_pending_welcomes().await?;
+        let epoch = self.store
+            .get_groups()
+            .await?
+            .iter()
+            .find(|g| g.mls_group_id == group_id)
+            .map(|g| g.epoch)
+            .unwrap_or_default();
+        self.record_system_event(&group_id, epoch, SystemEventKind::Joined).await;
         
-        // Find the matching welcome
-        if let Some(welcome) = pending_welcomes.iter().find(|w| w.mls_group_id == group_id) {
-            nostr_mls.accept_welcome(welcome).await?;
-            
-            // Refresh subscriptions to include the new group
-            if let Err(e) = self.refresh_subscriptions().await {
-                // Log error but don't fail the invite acceptance
-                eprintln!("Warning: Failed to refresh subscriptions after accepting invite: {}", e);
-            }
-            
-            Ok(())
-        } else {
-            Err(DialogError::General(format!("No pending invite found for group ID: {}", hex::encode(group_id.as_slice())).into()))
+        // Refresh subscriptions to include the new group
+        if let Err(e) = self.refresh_subscriptions().await {
+            // Log error but don't fail the invite acceptance
+            tracing::warn!("Failed to refresh subscriptions after accepting invite: {}", e);
         }
+        
+        Ok(())
     }
 
     async fn fetch_and_process_group_events(&self, group_id: &GroupId) -> Result<()> {
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
 
         // Get the stored group to find its Nostr group ID
-        let groups = nostr_mls.get_groups().await?;
+        let groups = self.store.get_groups().await?;
         let stored_group = groups
             .iter()
             .find(|g| &g.mls_group_id == group_id)
@@ -795,17 +1694,20 @@ impl MlsService for RealMlsService {
             .kind(Kind::MlsGroupMessage)
             .custom_tag(nostr_sdk::SingleLetterTag::lowercase(nostr_sdk::Alphabet::H), nostr_group_id_hex);
 
-        // Fetch events from relay
+        // Fetch events from the group's relays
+        let relays = group_relays(&self.store, group_id, &self.relay_url).await;
+        connect_relays(&client, &relays).await;
         let events = client
-            .fetch_events(filter, std::time::Duration::from_secs(5))
+            .fetch_events_from(relays, filter, std::time::Duration::from_secs(5))
             .await
             .map_err(|e| DialogError::General(format!("Failed to fetch group events: {}", e).into()))?;
 
         // Process each event to update MLS state
         for event in events {
-            if let Err(_) = nostr_mls.process_message(&event).await {
-                // Silently ignore processing errors - the event might be malformed
-                // or for a different epoch/state
+            // Silently ignore processing errors - the event might be malformed
+            // or for a different epoch/state
+            if let Some(Ok((_, changes))) = process_new_group_event(&self.store, &self.system_events, &self.ledger, stored_group, &event).await {
+                self.notify_group_changes(group_id, &changes).await;
             }
         }
 
@@ -814,205 +1716,386 @@ impl MlsService for RealMlsService {
 
     /// Refresh subscriptions after group changes (create/join/leave)
     async fn refresh_subscriptions(&self) -> Result<()> {
-        // Check if we have a UI sender stored
-        let _ui_sender = {
-            let sender_guard = self.ui_sender.read().await;
-            match sender_guard.as_ref() {
-                Some(sender) => sender.clone(),
-                None => {
-                    // No UI sender stored, subscriptions not active yet
-                    return Ok(());
-                }
-            }
-        };
+        // Nothing to refresh until the subscription task runs
+        if !self.subscription.read().await.as_ref().is_some_and(SubscriptionHandle::is_running) {
+            return Ok(());
+        }
 
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
 
-        // Cancel existing subscription if it exists
-        if let Some(old_sub_id) = self.subscription_id.read().await.as_ref() {
-            let _ = client.unsubscribe(old_sub_id).await;
-        }
+        // Cancel the existing subscriptions
+        unsubscribe_all(&client, &self.subscription_ids).await;
 
         // Get all groups to subscribe to
-        let groups = nostr_mls.get_groups().await?;
+        let groups = self.store.get_groups().await?;
         let mut filters = Vec::new();
 
-        // Create filters for all group messages
-        for group in &groups {
-            let filter = Filter::new()
-                .kind(Kind::MlsGroupMessage)
-                .custom_tag(
-                    SingleLetterTag::lowercase(Alphabet::H),
-                    hex::encode(&group.nostr_group_id),
-                );
-            filters.push(filter);
-        }
+        // Also subscribe to welcomes addressed to us
+        let plain_welcomes = self.config.read().await.plain_welcomes;
+        filters.extend(invite_policy::welcome_filters(self.pubkey, plain_welcomes));
 
-        // Also subscribe to gift wraps for invites (denoise compatibility)
-        let giftwrap_filter = Filter::new()
-            .kind(Kind::GiftWrap)
-            .pubkey(self.keys.public_key());
-        filters.push(giftwrap_filter);
+        // Profile and NIP-38 status updates from contacts feed their presence
+        let contact_pubkeys: Vec<PublicKey> = self.contacts.read().await.keys().copied().collect();
+        if !contact_pubkeys.is_empty() {
+            filters.push(presence::activity_filter(contact_pubkeys).since(Timestamp::now()));
+        }
 
-        // Subscribe to regular MLS welcome events (whitenoise compatibility)
-        let welcome_filter = Filter::new()
-            .kind(Kind::MlsWelcome);
-        filters.push(welcome_filter);
+        self.open_subscriptions(&client, &groups, filters).await
+    }
 
-        // Create new subscription with all filters at once
-        let subscription_id = SubscriptionId::new("dialog_messages");
-        
-        // Store the subscription ID for later use
-        {
-            let mut sub_id_guard = self.subscription_id.write().await;
-            *sub_id_guard = Some(subscription_id.clone());
+    /// Subscribe to each group on its relays and to each filter on our relay.
+    /// A relay replaces a REQ that reuses an ID, so every one gets its own.
+    async fn open_subscriptions(&self, client: &Client, groups: &[group_types::Group], filters: Vec<Filter>) -> Result<()> {
+        // Each group's messages come from that group's relays
+        for group in groups {
+            subscribe_group(client, &self.store, group, &self.subscription_ids, &self.relay_url).await?;
         }
-        
+
         // Subscribe to each filter individually since the API expects a single filter
-        for filter in filters {
+        for (i, filter) in filters.into_iter().enumerate() {
+            let id = subscription_id(&format!("f{}", i));
             client
-                .subscribe_with_id(subscription_id.clone(), filter, None)
+                .subscribe_with_id_to([self.relay_url.as_str()], id.clone(), filter, None)
                 .await
-                .map_err(|e| DialogError::General(format!("Failed to refresh subscription: {}", e).into()))?;
+                .map_err(|e| DialogError::General(format!("Failed to subscribe: {}", e).into()))?;
+            self.subscription_ids.write().await.insert(id);
         }
 
         Ok(())
     }
 
-    async fn subscribe_to_groups(&self, ui_sender: mpsc::Sender<UiUpdate>) -> Result<()> {
-        // Store the UI sender for later use
+    async fn mark_read(&self, group_id: &GroupId) -> Result<bool> {
+        let latest = {
+            self.store
+                .get_messages(group_id)
+                .await?
+                .into_iter()
+                .filter(|m| m.pubkey != self.pubkey && !is_control_kind(m.kind))
+                .max_by_key(|m| m.created_at)
+        };
+        let Some(latest) = latest else {
+            return Ok(false);
+        };
+
         {
-            let mut sender_guard = self.ui_sender.write().await;
-            *sender_guard = Some(ui_sender.clone());
+            let mut markers = self.read_markers.write().await;
+            let key = hex::encode(group_id.as_slice());
+            if let Some((id, created_at)) = markers.get().get(&key) {
+                if *id == latest.id || *created_at > latest.created_at {
+                    return Ok(false);
+                }
+            }
+            markers.get_mut().insert(key, (latest.id, latest.created_at));
+            markers.save()?;
         }
 
-        let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
+        // One receipt per marker advance covers every earlier message too
+        let connected = *self.connection_status.read().await == ConnectionStatus::Connected;
+        if connected && self.config.read().await.read_receipts {
+            let rumor = EventBuilder::new(Kind::Custom(nostr_kinds::READ_RECEIPT), "")
+                .tag(Tag::event(latest.id))
+                .build(self.pubkey);
+            self.send_group_rumor(group_id, rumor).await?;
+        }
 
-        // Get all groups to subscribe to
-        let groups = nostr_mls.get_groups().await?;
-        let mut filters = Vec::new();
+        Ok(true)
+    }
 
-        // Create filters for all group messages
-        for group in &groups {
-            let filter = Filter::new()
-                .kind(Kind::MlsGroupMessage)
-                .custom_tag(
-                    SingleLetterTag::lowercase(Alphabet::H),
-                    hex::encode(&group.nostr_group_id),
-                );
-            filters.push(filter);
+    async fn send_typing(&self, group_id: &GroupId, typing: bool) -> Result<bool> {
+        if *self.connection_status.read().await != ConnectionStatus::Connected {
+            return Ok(false);
         }
-        
 
-        // Also subscribe to gift wraps for invites (denoise compatibility)
-        let giftwrap_filter = Filter::new()
-            .kind(Kind::GiftWrap)
-            .pubkey(self.keys.public_key());
-        filters.push(giftwrap_filter);
+        {
+            let mut typing_sent = self.typing_sent.write().await;
+            if typing {
+                if let Some(last) = typing_sent.get(group_id) {
+                    if last.elapsed() < TYPING_SEND_INTERVAL {
+                        return Ok(false);
+                    }
+                }
+                typing_sent.insert(group_id.clone(), std::time::Instant::now());
+            } else if typing_sent.remove(group_id).is_none() {
+                // Nobody was told we started, so there is nothing to stop
+                return Ok(false);
+            }
+        }
 
-        // Subscribe to regular MLS welcome events (whitenoise compatibility)
-        let welcome_filter = Filter::new()
-            .kind(Kind::MlsWelcome);
-        filters.push(welcome_filter);
+        // Typing is ephemeral: neither relays nor the MLS message store keep it
+        let group = self.store
+            .group(group_id)
+            .await?
+            .ok_or_else(|| DialogError::General("Group not found".into()))?;
+        let secret = self.store.exporter_secret(group_id).await?;
+        let content = if typing { group_signal::TYPING_STARTED } else { group_signal::TYPING_STOPPED };
+        let client = self.client.read().await;
+        let signal = client
+            .sign_event_builder(EventBuilder::new(Kind::Custom(nostr_kinds::GROUP_SIGNAL), content))
+            .await
+            .map_err(|e| DialogError::General(Box::new(e)))?;
+        let event = group_signal::seal(&secret.secret, &group.nostr_group_id, &signal)?;
 
-        // Create subscription with all filters at once
-        let subscription_id = SubscriptionId::new("dialog_messages");
-        
-        // Store the subscription ID for later use
-        {
-            let mut sub_id_guard = self.subscription_id.write().await;
-            *sub_id_guard = Some(subscription_id.clone());
+        let relays = group_relays(&self.store, group_id, &self.relay_url).await;
+        connect_relays(&client, &relays).await;
+        client
+            .send_event_to(relays, &event)
+            .await
+            .map_err(|e| DialogError::General(format!("Failed to publish to group: {}", e).into()))?;
+        Ok(true)
+    }
+
+    async fn set_pinned(&self, group_id: &GroupId, message_id: &str, pinned: bool) -> Result<()> {
+        let target = EventId::from_hex(message_id)
+            .map_err(|e| DialogError::General(format!("Invalid message ID: {}", e).into()))?;
+
+        let group = self.store
+            .group(group_id)
+            .await?
+            .ok_or_else(|| DialogError::General("Group not found".into()))?;
+        if !group.admin_pubkeys.contains(&self.pubkey) {
+            return Err(DialogError::General("Only group admins can pin messages".into()));
         }
-        
-        // Subscribe to each filter individually since the API expects a single filter
-        for filter in filters {
-            client
-                .subscribe_with_id(subscription_id.clone(), filter, None)
-                .await
-                .map_err(|e| DialogError::General(format!("Failed to create subscription: {}", e).into()))?;
+        let exists = self.store
+            .get_messages(group_id)
+            .await?
+            .iter()
+            .any(|m| m.id == target && !is_control_kind(m.kind));
+        if !exists {
+            return Err(DialogError::General(format!("Message {} not found in this group", message_id).into()));
+        }
+
+        // Pins are group data, so members who join later get them with their welcome
+        let current = group.pinned.clone().unwrap_or_default();
+        let mut pins = current.clone();
+        pins.retain(|id| *id != target);
+        if pinned {
+            pins.insert(0, target);
+        }
+        if pins == current {
+            return Ok(());
+        }
+
+        let update = NostrGroupDataUpdate {
+            pinned: Some(pins),
+            ..Default::default()
+        };
+        let commit = self.store.update_group_data(group_id, update).await?;
+        let client = self.client.read().await;
+        self.publish_commit(&client, group_id, &commit.evolution_event).await?;
+
+        let epoch = self.store.group(group_id).await?.map_or(group.epoch, |g| g.epoch);
+        self.record_system_event(group_id, epoch, SystemEventKind::PinsChanged).await;
+        self.notify_ui(UiUpdate::PinsChanged { group_id: group_id.clone() }).await;
+        Ok(())
+    }
+
+    async fn pinned_messages(&self, group_id: &GroupId) -> Result<Vec<Message>> {
+        let group = self.store
+            .group(group_id)
+            .await?
+            .ok_or_else(|| DialogError::General("Group not found".into()))?;
+        let stored_messages = self.store.get_messages(group_id).await?;
+
+        // Messages sent before we joined are pinned but not stored here
+        let outbox = self.outbox.read().await;
+        let messages = group.pinned
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| stored_messages.iter().find(|m| m.id == id))
+            .map(|msg| {
+                let id = msg.id.to_hex();
+                Message {
+                    sender: msg.pubkey,
+                    content: msg.content.clone(),
+                    body: MessageBody::parse(msg.kind, &msg.content, &msg.tags),
+                    timestamp: msg.created_at.as_u64() as i64,
+                    delivery: outbox.delivery(&id).cloned(),
+                    seen_by: Vec::new(),
+                    id: Some(id),
+                }
+            })
+            .collect();
+        Ok(messages)
+    }
+
+    async fn subscribe_to_groups(&self, ui_sender: mpsc::Sender<UiUpdate>) -> Result<SubscriptionHandle> {
+        self.events.set_sender(ui_sender).await;
+        self.subscribe().await
+    }
+
+    async fn subscribe(&self) -> Result<SubscriptionHandle> {
+        // A new subscription replaces the previous one rather than running beside it
+        let previous = self.subscription.write().await.take();
+        if let Some(previous) = previous {
+            previous.stop().await;
         }
 
+        let client = self.client.read().await;
+
+        // Get all groups to subscribe to
+        let groups = self.store.get_groups().await?;
+        let mut filters = Vec::new();
+
+        // Also subscribe to welcomes addressed to us
+        let plain_welcomes = self.config.read().await.plain_welcomes;
+        filters.extend(invite_policy::welcome_filters(self.pubkey, plain_welcomes));
+
+        self.open_subscriptions(&client, &groups, filters).await?;
+
         // Spawn a task to handle incoming events
         let client_clone = self.client.clone();
-        let nostr_mls_clone = self.nostr_mls.clone();
-        let keys_clone = self.keys.clone();
-        let displayed_messages_clone = self.displayed_messages.clone();
+        let subscription_ids = self.subscription_ids.clone();
+        let store = self.store.clone();
+        let ledger_clone = self.ledger.clone();
+        let system_events_clone = self.system_events.clone();
+        let contacts_clone = self.contacts.clone();
+        let invite_screen = self.invite_screen();
+        let relay_url = self.relay_url.clone();
+        let events = self.events.clone();
+        let own_pubkey = self.pubkey;
         
+        let (handle, mut stop) = SubscriptionHandle::new();
         tokio::spawn(async move {
-            loop {
-                // Handle events from subscription
-                let mut notifications = client_clone.read().await.notifications();
-                
-                while let Ok(notification) = notifications.recv().await {
-                    if let RelayPoolNotification::Event { subscription_id: sub_id, event, .. } = notification {
-                        if sub_id == subscription_id {
-                            // Process the event based on its kind
-                            match event.kind {
-                                Kind::MlsGroupMessage => {
-                                    // Check if this is our own message we already processed
-                                    let displayed_msgs = displayed_messages_clone.read().await;
-                                    let event_id_hex = event.id.to_hex();
-                                    
-                                    // Extract group ID from tags first to check
-                                    if let Some(tag) = event.tags.iter().find(|t| {
-                                        t.as_slice().len() >= 2 && t.as_slice()[0] == "h"
-                                    }) {
-                                        if let Ok(nostr_group_id_bytes) = hex::decode(&tag.as_slice()[1]) {
-                                            // Find the matching group
-                                            let nostr_mls = nostr_mls_clone.read().await;
-                                            if let Ok(groups) = nostr_mls.get_groups().await {
-                                                if let Some(group) = groups.iter().find(|g| {
-                                                    g.nostr_group_id.as_slice() == nostr_group_id_bytes.as_slice()
-                                                }) {
-                                                    // Check if we've already processed this message
-                                                    if let Some(group_displayed) = displayed_msgs.get(&group.mls_group_id) {
-                                                        if group_displayed.contains(&event_id_hex) {
-                                                            // Skip processing - we already handled this when sending
-                                                            continue;
-                                                        }
+            // Handle events from the subscription until cancelled
+            let mut notifications = client_clone.read().await.notifications();
+            'notifications: loop {
+                let notification = tokio::select! {
+                    _ = stop.wait_for(|stop| *stop) => break,
+                    received = notifications.recv() => match received {
+                        Ok(notification) => notification,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    },
+                };
+
+                if let RelayPoolNotification::Event { subscription_id: sub_id, event, .. } = notification {
+                    if sub_id.as_str().starts_with(SUBSCRIPTION_PREFIX) {
+                        // Process the event based on its kind
+                        match event.kind {
+                            Kind::MlsGroupMessage => {
+                                // Extract group ID from tags first to check
+                                if let Some(tag) = event.tags.iter().find(|t| {
+                                    t.as_slice().len() >= 2 && t.as_slice()[0] == "h"
+                                }) {
+                                    if let Ok(nostr_group_id_bytes) = hex::decode(&tag.as_slice()[1]) {
+                                        // Find the matching group in the actor's index
+                                        if let Ok(Some(group)) = store.group_by_nostr_id(&nostr_group_id_bytes).await {
+                                            // Process the event to decrypt it, unless it was
+                                            // handled before (including our own messages)
+                                            let Some(processed) = process_new_group_event(&store, &system_events_clone, &ledger_clone, &group, &event).await else {
+                                                continue;
+                                            };
+                                            if let Ok((_, changes)) = &processed {
+                                                for update in group_change_updates(&group.mls_group_id, changes) {
+                                                    if !deliver(&events, &mut stop, update).await {
+                                                        break 'notifications;
                                                     }
-                                                    
-                                                    // Process the event to decrypt it
-                                                    if let Ok(_) = nostr_mls.process_message(&event).await {
-                                                        // Just notify that this group has new messages
-                                                        // The UI will re-fetch to get the correct, deduplicated list
-                                                        let _ = ui_sender.send(UiUpdate::GroupHasNewMessages {
-                                                            group_id: group.mls_group_id.clone(),
-                                                        }).await;
+                                                }
+                                            }
+                                            if let Ok((MessageProcessingResult::ApplicationMessage(message), _)) = &processed {
+                                                let now = chrono::Utc::now().timestamp();
+                                                let seen_at = message.created_at.as_u64() as i64;
+                                                let changed = presence::record_activity(&mut *contacts_clone.write().await, &message.pubkey, seen_at, now);
+                                                if let Some(contact) = changed {
+                                                    let update = UiUpdate::PresenceChanged {
+                                                        pubkey: contact.pubkey,
+                                                        online: contact.online,
+                                                        last_seen: contact.last_seen,
+                                                    };
+                                                    if !deliver(&events, &mut stop, update).await {
+                                                        break 'notifications;
                                                     }
                                                 }
                                             }
-                                        }
-                                    }
-                                }
-                                Kind::GiftWrap => {
-                                    // Process potential gift-wrapped invite (denoise compatibility)
-                                    if let Ok(unwrapped_gift) = nip59::extract_rumor(&keys_clone, &event).await {
-                                        let nostr_mls = nostr_mls_clone.read().await;
-                                        if let Ok(_) = nostr_mls.process_welcome(&event.id, &unwrapped_gift.rumor).await {
-                                            // Get the new pending welcome
-                                            if let Ok(welcomes) = nostr_mls.get_pending_welcomes().await {
-                                                if let Some(welcome) = welcomes.last() {
-                                                    let invite = PendingInvite {
-                                                        group_id: welcome.mls_group_id.clone(),
-                                                        group_name: welcome.group_name.clone(),
-                                                        inviter: Some(unwrapped_gift.sender),
-                                                        member_count: welcome.member_count as usize,
-                                                        timestamp: event.created_at.as_u64() as i64,
+                                            match processed.map(|(result, _)| result) {
+                                                Ok(MessageProcessingResult::ApplicationMessage(message))
+                                                    if message.kind == Kind::Custom(nostr_kinds::READ_RECEIPT) =>
+                                                {
+                                                    if let Some(target) = message.tags.event_ids().next() {
+                                                        let update = UiUpdate::ReadReceipt {
+                                                            group_id: group.mls_group_id.clone(),
+                                                            reader: message.pubkey,
+                                                            message_id: target.to_hex(),
+                                                        };
+                                                        if !deliver(&events, &mut stop, update).await {
+                                                            break 'notifications;
+                                                        }
+                                                    }
+                                                }
+                                                Ok(MessageProcessingResult::ApplicationMessage(message)) => {
+                                                    // The ledger guarantees each message is delivered once
+                                                    let update = UiUpdate::NewMessage {
+                                                        group_id: group.mls_group_id.clone(),
+                                                        message: received_message(&message),
                                                     };
-                                                    
-                                                    // Send UI update
-                                                    let _ = ui_sender.send(UiUpdate::NewInvite(invite)).await;
+                                                    if !deliver(&events, &mut stop, update).await {
+                                                        break 'notifications;
+                                                    }
+                                                    // Still announced for consumers written against older versions
+                                                    #[allow(deprecated)]
+                                                    let update = UiUpdate::GroupHasNewMessages { group_id: group.mls_group_id.clone() };
+                                                    if !deliver(&events, &mut stop, update).await {
+                                                        break 'notifications;
+                                                    }
                                                 }
+                                                Ok(_) => {}
+                                                Err(_) => {}
                                             }
                                         }
                                     }
                                 }
-                                Kind::MlsWelcome => {
-                                    // Process regular MLS welcome invite (whitenoise compatibility)
-                                    let nostr_mls = nostr_mls_clone.read().await;
+                            }
+                            kind if kind == Kind::Custom(nostr_kinds::GROUP_SIGNAL) => {
+                                let Some((group, signal)) = open_group_signal(&store, &event, &own_pubkey).await else {
+                                    continue;
+                                };
+                                // A relay holding back an ephemeral event makes it stale all the same
+                                let age = Timestamp::now().as_u64().saturating_sub(signal.created_at.as_u64());
+                                let typing = match signal.content.as_str() {
+                                    group_signal::TYPING_STARTED => true,
+                                    group_signal::TYPING_STOPPED => false,
+                                    _ => continue,
+                                };
+                                if age > TYPING_MAX_AGE_SECS {
+                                    continue;
+                                }
+                                let now = chrono::Utc::now().timestamp();
+                                let seen_at = signal.created_at.as_u64() as i64;
+                                let changed = presence::record_activity(&mut *contacts_clone.write().await, &signal.pubkey, seen_at, now);
+                                if let Some(contact) = changed {
+                                    let update = UiUpdate::PresenceChanged {
+                                        pubkey: contact.pubkey,
+                                        online: contact.online,
+                                        last_seen: contact.last_seen,
+                                    };
+                                    if !deliver(&events, &mut stop, update).await {
+                                        break 'notifications;
+                                    }
+                                }
+                                let group_id = group.mls_group_id.clone();
+                                let pubkey = signal.pubkey;
+                                let update = if typing {
+                                    UiUpdate::Typing { group_id, pubkey }
+                                } else {
+                                    UiUpdate::TypingStopped { group_id, pubkey }
+                                };
+                                if !deliver(&events, &mut stop, update).await {
+                                    break 'notifications;
+                                }
+                            }
+                            Kind::GiftWrap | Kind::MlsWelcome => {
+                                // Gift-wrapped (denoise) or plain (whitenoise) welcome invite
+                                if !invite_screen.wants(&event).await {
+                                    continue;
+                                }
+                                let welcome = if event.kind == Kind::GiftWrap {
+                                    match client_clone.read().await.unwrap_gift_wrap(&event).await {
+                                        Ok(unwrapped) if unwrapped.rumor.kind == Kind::MlsWelcome => {
+                                            Some((unwrapped.rumor, unwrapped.sender))
+                                        }
+                                        _ => None,
+                                    }
+                                } else {
                                     let unsigned_event = UnsignedEvent {
                                         id: Some(event.id),
                                         pubkey: event.pubkey,
@@ -1021,39 +2104,84 @@ impl MlsService for RealMlsService {
                                         content: event.content.clone(),
                                         tags: event.tags.clone(),
                                     };
-                                    
-                                    if let Ok(_) = nostr_mls.process_welcome(&event.id, &unsigned_event).await {
-                                        // Get the new pending welcome
-                                        if let Ok(welcomes) = nostr_mls.get_pending_welcomes().await {
-                                            if let Some(welcome) = welcomes.last() {
-                                                let invite = PendingInvite {
-                                                    group_id: welcome.mls_group_id.clone(),
-                                                    group_name: welcome.group_name.clone(),
-                                                    inviter: Some(event.pubkey),
-                                                    member_count: welcome.member_count as usize,
-                                                    timestamp: event.created_at.as_u64() as i64,
-                                                };
-                                                
-                                                // Send UI update
-                                                let _ = ui_sender.send(UiUpdate::NewInvite(invite)).await;
-                                            }
+                                    Some((unsigned_event, event.pubkey))
+                                };
+                                let Some((rumor, inviter)) = welcome else {
+                                    continue;
+                                };
+
+                                let Ok(Some((decision, invite))) = invite_screen.screen(&store, event.id, &rumor, inviter).await else {
+                                    continue;
+                                };
+                                if let Some(invite) = &invite {
+                                    if decision == InviteDecision::Accepted {
+                                        // Start receiving the joined group's messages
+                                        if let Ok(Some(group)) = store.group(&invite.group_id).await {
+                                            let client = client_clone.read().await;
+                                            let _ = subscribe_group(&client, &store, &group, &subscription_ids, &relay_url).await;
+                                        }
+                                    } else if decision == InviteDecision::Pending {
+                                        if !deliver(&events, &mut stop, UiUpdate::NewInvite(invite.clone())).await {
+                                            break 'notifications;
                                         }
                                     }
                                 }
-                                _ => {}
+                                let update = UiUpdate::InviteDecided { inviter, invite, decision };
+                                if !deliver(&events, &mut stop, update).await {
+                                    break 'notifications;
+                                }
+                            }
+                            kind if kind == Kind::Metadata || kind == Kind::Custom(presence::USER_STATUS_KIND) => {
+                                let now = chrono::Utc::now().timestamp();
+                                let changed = presence::apply_event(&mut *contacts_clone.write().await, &event, now);
+                                if let Some(contact) = changed {
+                                    let update = UiUpdate::PresenceChanged {
+                                        pubkey: contact.pubkey,
+                                        online: contact.online,
+                                        last_seen: contact.last_seen,
+                                    };
+                                    if !deliver(&events, &mut stop, update).await {
+                                        break 'notifications;
+                                    }
+                                }
                             }
+                            _ => {}
                         }
                     }
                 }
             }
+            unsubscribe_all(&*client_clone.read().await, &subscription_ids).await;
         });
+        *self.subscription.write().await = Some(handle.clone());
+
+        Ok(handle)
+    }
+
+    fn events(&self) -> tokio::sync::broadcast::Receiver<UiUpdate> {
+        self.events.subscribe()
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        let subscription = self.subscription.write().await.take();
+        if let Some(subscription) = subscription {
+            subscription.stop().await;
+        }
 
+        // Last chance to deliver queued messages; whatever is left stays queued
+        if *self.connection_status.read().await == ConnectionStatus::Connected {
+            deliver_outbox(&self.client, &self.store, &self.relay_url, &self.outbox, &self.events).await;
+        }
+
+        // Background tasks exit and release their storage handles
+        self.shutdown.send_replace(true);
+        self.disconnect().await?;
+        self.client.read().await.shutdown().await;
+        self.store.close().await;
         Ok(())
     }
 
     async fn fetch_messages(&self, group_id: &GroupId) -> Result<MessageFetchResult> {
         let client = self.client.read().await;
-        let nostr_mls = self.nostr_mls.read().await;
 
         // Collect processing errors
         let mut processing_errors = Vec::new();
@@ -1064,7 +2192,7 @@ impl MlsService for RealMlsService {
         }
 
         // Get the stored group to find its Nostr group ID
-        let groups = nostr_mls.get_groups().await?;
+        let groups = self.store.get_groups().await?;
         let stored_group = groups
             .iter()
             .find(|g| &g.mls_group_id == group_id)
@@ -1076,55 +2204,97 @@ impl MlsService for RealMlsService {
             .kind(Kind::MlsGroupMessage)
             .custom_tag(nostr_sdk::SingleLetterTag::lowercase(nostr_sdk::Alphabet::H), nostr_group_id_hex);
 
-        // Fetch message events from relay
-        let events = client
-            .fetch_events(filter, std::time::Duration::from_secs(5))
+        // Fetch message events from the group's relays; when offline, fall back to what is stored locally
+        let relays = group_relays(&self.store, group_id, &self.relay_url).await;
+        connect_relays(&client, &relays).await;
+        let events: Vec<Event> = match client
+            .fetch_events_from(relays, filter, std::time::Duration::from_secs(5))
             .await
-            .map_err(|e| DialogError::General(format!("Failed to fetch messages: {}", e).into()))?;
+        {
+            Ok(events) => events.into_iter().collect(),
+            Err(e) => {
+                processing_errors.push(format!("⚠️  Failed to fetch messages: {}", e));
+                Vec::new()
+            }
+        };
 
         // Process each event to decrypt and store messages
         for event in events {
             // Process the message to decrypt it
-            if let Err(e) = nostr_mls.process_message(&event).await {
-                // Collect error for UI display
-                processing_errors.push(format!(
-                    "⚠️  Failed to process message {}: {}",
-                    event.id.to_hex()[0..16].to_string(),
-                    e
-                ));
-                continue;
+            match process_new_group_event(&self.store, &self.system_events, &self.ledger, stored_group, &event).await {
+                Some(Ok((_, changes))) => self.notify_group_changes(group_id, &changes).await,
+                None => {}
+                Some(Err(e)) => {
+                    // Collect error for UI display
+                    processing_errors.push(format!(
+                        "⚠️  Failed to process message {}: {}",
+                        event.id.to_hex()[0..16].to_string(),
+                        e
+                    ));
+                }
             }
         }
 
         // Get all decrypted messages from storage
-        let stored_messages = nostr_mls.get_messages(&stored_group.mls_group_id).await?;
+        let stored_messages = self.store.get_messages(&stored_group.mls_group_id).await?;
         
-        // Update displayed messages tracking
-        {
-            let mut displayed_msgs = self.displayed_messages.write().await;
-            let group_displayed = displayed_msgs.entry(group_id.clone())
-                .or_insert_with(std::collections::HashSet::new);
-            
-            // Mark all fetched messages as displayed
-            for msg in stored_messages.iter() {
-                group_displayed.insert(msg.id.to_hex());
-            }
+        // Receipts are aggregated onto the messages they cover rather than shown
+        let (receipts, stored_messages): (Vec<_>, Vec<_>) = stored_messages
+            .into_iter()
+            .partition(|m| m.kind == Kind::Custom(nostr_kinds::READ_RECEIPT));
+        let read_positions = read_positions(&stored_messages, &receipts);
+
+        // Anything a member sent, receipts included, counts as activity
+        let mut latest_activity: HashMap<PublicKey, i64> = HashMap::new();
+        for msg in stored_messages.iter().chain(receipts.iter()).filter(|m| m.pubkey != self.pubkey) {
+            let at = latest_activity.entry(msg.pubkey).or_default();
+            *at = (*at).max(msg.created_at.as_u64() as i64);
         }
-        
+        for (pubkey, timestamp) in latest_activity {
+            self.note_activity(&pubkey, timestamp).await;
+        }
+
         // Convert storage messages to our Message format
+        let outbox = self.outbox.read().await;
         let mut messages: Vec<Message> = stored_messages
             .iter()
-            .map(|msg| Message {
-                sender: msg.pubkey,
-                content: msg.content.clone(),
-                timestamp: msg.created_at.as_u64() as i64,
-                id: Some(msg.id.to_hex()), // Include the event ID!
+            .map(|msg| {
+                let id = msg.id.to_hex();
+                let mut seen_by: Vec<PublicKey> = read_positions
+                    .iter()
+                    .filter(|(reader, read_up_to)| **reader != msg.pubkey && **read_up_to >= msg.created_at)
+                    .map(|(reader, _)| *reader)
+                    .collect();
+                seen_by.sort();
+                Message {
+                    sender: msg.pubkey,
+                    content: msg.content.clone(),
+                    body: MessageBody::parse(msg.kind, &msg.content, &msg.tags),
+                    timestamp: msg.created_at.as_u64() as i64,
+                    delivery: outbox.delivery(&id).cloned(),
+                    seen_by,
+                    id: Some(id), // Include the event ID!
+                }
             })
             .collect();
+        drop(outbox);
 
         // Sort messages by timestamp (oldest first)
         messages.sort_by_key(|m| m.timestamp);
 
+        // Interleave system events (joins, renames, ...) with the messages; on equal
+        // timestamps the stable sort keeps a system event ahead of the message
+        let mut timeline: Vec<TimelineEntry> = self
+            .system_events
+            .read()
+            .await
+            .events(group_id)
+            .into_iter()
+            .map(TimelineEntry::System)
+            .chain(messages.iter().cloned().map(TimelineEntry::Message))
+            .collect();
+        timeline.sort_by_key(|entry| entry.timestamp());
+
         // Update last sync time
         {
             let mut last_sync = self.last_sync.write().await;
@@ -1133,17 +2303,511 @@ impl MlsService for RealMlsService {
 
         Ok(MessageFetchResult {
             messages,
+            timeline,
             processing_errors,
         })
     }
 }
 
+/// Whether a group is a one-to-one conversation: opened as one here (or left
+/// unnamed by whoever did) and still only between two people
+fn is_direct_message(group: &group_types::Group, members: &[PublicKey], flagged: bool) -> bool {
+    (flagged || group.name.is_empty()) && members.len() == 2
+}
+
+/// Summary of a pending welcome for the UI
+fn pending_invite(welcome: &welcome_types::Welcome) -> PendingInvite {
+    PendingInvite {
+        group_id: welcome.mls_group_id.clone(),
+        group_name: welcome.group_name.clone(),
+        inviter: Some(welcome.welcomer),
+        member_count: welcome.member_count as usize,
+        timestamp: welcome.event.created_at.as_u64() as i64,
+    }
+}
+
+/// State the invite policy is applied with, shared with the subscription task
+#[derive(Clone)]
+struct InviteScreen {
+    pubkey: PublicKey,
+    foreign_skipped: Arc<AtomicU64>,
+    gate: Arc<RwLock<InviteGate>>,
+    config: Arc<RwLock<DialogConfig>>,
+    contacts: Arc<RwLock<HashMap<PublicKey, Contact>>>,
+    system_events: Arc<RwLock<SystemEventLog>>,
+}
+
+impl InviteScreen {
+    /// Whether a welcome event is one we handle: addressed to us, and not a
+    /// plain welcome while that compatibility path is off. Relays do not
+    /// always honour `#p`, so foreign welcomes are skipped (and counted) here.
+    async fn wants(&self, event: &Event) -> bool {
+        if event.kind == Kind::MlsWelcome && !self.config.read().await.plain_welcomes {
+            return false;
+        }
+        if !invite_policy::is_addressed_to(event, &self.pubkey) {
+            self.foreign_skipped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Run a welcome from `inviter` through the invite policy before anything
+    /// else touches it. Dropped welcomes are never processed; kept ones are
+    /// processed into a pending welcome, which is accepted right away when the
+    /// policy says so. Returns `None` for welcomes already decided earlier,
+    /// including the second copy of a welcome sent both wrapped and plain.
+    async fn screen(
+        &self,
+        store: &MlsStore,
+        wrapper_id: EventId,
+        rumor: &UnsignedEvent,
+        inviter: PublicKey,
+    ) -> Result<Option<(InviteDecision, Option<PendingInvite>)>> {
+        let key = invite_policy::welcome_key(&rumor.content);
+        let decision = {
+            let mut gate = self.gate.write().await;
+            if gate.is_decided(&key) {
+                return Ok(None);
+            }
+            let is_contact = self.contacts.read().await.contains_key(&inviter);
+            let config = self.config.read().await;
+            gate.decide(&config.invite_policy, key, inviter, is_contact, chrono::Utc::now().timestamp())?
+        };
+        if matches!(decision, InviteDecision::Dropped(_)) {
+            return Ok(Some((decision, None)));
+        }
+
+        let welcome = store.process_welcome(&wrapper_id, rumor).await?;
+        let invite = pending_invite(&welcome);
+        if decision == InviteDecision::Accepted {
+            store.accept_welcome(&welcome).await?;
+            let epoch = store
+                .get_groups()
+                .await?
+                .iter()
+                .find(|g| g.mls_group_id == welcome.mls_group_id)
+                .map(|g| g.epoch)
+                .unwrap_or_default();
+            let joined = SystemEvent {
+                timestamp: chrono::Utc::now().timestamp(),
+                epoch,
+                kind: SystemEventKind::Joined,
+            };
+            if let Err(e) = self.system_events.write().await.record(&welcome.mls_group_id, vec![joined]) {
+                tracing::warn!("Failed to record group event: {}", e);
+            }
+        }
+        Ok(Some((decision, Some(invite))))
+    }
+}
+
+/// Process a group message event and record any membership or metadata change
+/// it caused as system events. Returns the processing result and the recorded
+/// changes, which all carry the group's new epoch.
+async fn process_group_event(
+    store: &MlsStore,
+    system_events: &RwLock<SystemEventLog>,
+    group: &group_types::Group,
+    event: &Event,
+) -> Result<(MessageProcessingResult, Vec<SystemEvent>)> {
+    // The caller's copy of the group may be several events old; compare against
+    // the state this event applies to. Snapshots are best effort: failing to
+    // describe a change must not block processing.
+    let before = match store.group(&group.mls_group_id).await {
+        Ok(Some(current)) => GroupSnapshot::capture(store, &current).await.ok(),
+        _ => None,
+    };
+    let result = store.process_message(event).await?;
+
+    // Only commits (and proposals we commit right away) change the group
+    if !matches!(result, MessageProcessingResult::Commit { .. } | MessageProcessingResult::Proposal { .. }) {
+        return Ok((result, Vec::new()));
+    }
+    let Some(before) = before else {
+        return Ok((result, Vec::new()));
+    };
+    let Some(updated) = store.group(&group.mls_group_id).await? else {
+        return Ok((result, Vec::new()));
+    };
+    let Ok(after) = GroupSnapshot::capture(store, &updated).await else {
+        return Ok((result, Vec::new()));
+    };
+
+    // The Nostr event is signed with an ephemeral key and nostr-mls does not
+    // report the MLS sender of a commit, so the actor is unknown here
+    let changes = before.diff(&after, None, event.created_at.as_u64() as i64);
+    if let Err(e) = system_events.write().await.record(&group.mls_group_id, changes.clone()) {
+        tracing::warn!("Failed to record group event: {}", e);
+    }
+    Ok((result, changes))
+}
+
+/// Deliver an update from the subscription task, giving up when the task is
+/// stopped first: a consumer that stopped reading must not keep `stop` waiting
+/// on a full channel. Returns whether the task should go on.
+async fn deliver(events: &EventChannel, stop: &mut watch::Receiver<bool>, update: UiUpdate) -> bool {
+    tokio::select! {
+        _ = stop.wait_for(|stop| *stop) => false,
+        _ = events.send(update) => true,
+    }
+}
+
+/// Process a group event unless the ledger shows it was handled already, and
+/// record the outcome. Returns `None` for events handled before.
+async fn process_new_group_event(
+    store: &MlsStore,
+    system_events: &RwLock<SystemEventLog>,
+    ledger: &EventLedger,
+    group: &group_types::Group,
+    event: &Event,
+) -> Option<Result<(MessageProcessingResult, Vec<SystemEvent>)>> {
+    match ledger.is_done(&event.id).await {
+        Ok(true) => return None,
+        Ok(false) => {}
+        Err(e) => tracing::warn!("Failed to read processed events: {}", e),
+    }
+
+    let processed = process_group_event(store, system_events, group, event).await;
+    let outcome = match &processed {
+        Ok(_) => EventOutcome::Applied,
+        Err(e) => EventOutcome::Failed { reason: e.to_string() },
+    };
+    if let Err(e) = ledger.record(&group.mls_group_id, &event.id, outcome).await {
+        tracing::warn!("Failed to record processed event: {}", e);
+    }
+    Some(processed)
+}
+
+/// Open a group signal sent by another member of one of our groups
+async fn open_group_signal(
+    store: &MlsStore,
+    event: &Event,
+    own_pubkey: &PublicKey,
+) -> Option<(group_types::Group, Event)> {
+    let tag = event.tags.iter().find(|t| t.as_slice().len() >= 2 && t.as_slice()[0] == "h")?;
+    let nostr_group_id = hex::decode(&tag.as_slice()[1]).ok()?;
+    let group = store.group_by_nostr_id(&nostr_group_id).await.ok()??;
+    let secret = store.exporter_secret(&group.mls_group_id).await.ok()?;
+    let signal = group_signal::open(&secret.secret, event)?;
+    if signal.pubkey == *own_pubkey {
+        return None;
+    }
+    let members = store.get_members(&group.mls_group_id).await.ok()?;
+    members.contains(&signal.pubkey).then_some((group, signal))
+}
+
+/// A message received from another member, as shown to the user
+fn received_message(message: &message_types::Message) -> Message {
+    Message {
+        sender: message.pubkey,
+        content: message.content.clone(),
+        body: MessageBody::parse(message.kind, &message.content, &message.tags),
+        timestamp: message.created_at.as_u64() as i64,
+        id: Some(message.id.to_hex()),
+        delivery: None,
+        seen_by: Vec::new(),
+    }
+}
+
+/// Rumor kinds used for group signalling rather than conversation content
+fn is_control_kind(kind: Kind) -> bool {
+    kind == Kind::Custom(nostr_kinds::READ_RECEIPT)
+}
+
+/// UI updates for the changes a processed commit made to a group
+fn group_change_updates(group_id: &GroupId, changes: &[SystemEvent]) -> Vec<UiUpdate> {
+    let Some(first) = changes.first() else {
+        return Vec::new();
+    };
+    let mut updates = vec![UiUpdate::GroupStateChange { group_id: group_id.clone(), epoch: first.epoch }];
+    if changes.iter().any(|e| e.kind == SystemEventKind::PinsChanged) {
+        updates.push(UiUpdate::PinsChanged { group_id: group_id.clone() });
+    }
+    updates
+}
+
+/// For every reader, the timestamp of the newest message their receipts cover
+fn read_positions(
+    messages: &[message_types::Message],
+    receipts: &[message_types::Message],
+) -> HashMap<PublicKey, Timestamp> {
+    let timestamps: HashMap<EventId, Timestamp> = messages.iter().map(|m| (m.id, m.created_at)).collect();
+    let mut positions: HashMap<PublicKey, Timestamp> = HashMap::new();
+    for receipt in receipts {
+        for target in receipt.tags.event_ids() {
+            if let Some(created_at) = timestamps.get(target) {
+                let position = positions.entry(receipt.pubkey).or_insert(*created_at);
+                if *created_at > *position {
+                    *position = *created_at;
+                }
+            }
+        }
+    }
+    positions
+}
+
+/// Fetch the latest profile and status events of all contacts, then expire
+/// stale presence, notifying the UI about every contact whose state changed
+async fn refresh_presence(
+    client: &RwLock<Client>,
+    relay_url: &str,
+    contacts: &RwLock<HashMap<PublicKey, Contact>>,
+    updates: &EventChannel,
+) {
+    let pubkeys: Vec<PublicKey> = contacts.read().await.keys().copied().collect();
+    if pubkeys.is_empty() {
+        return;
+    }
+
+    let events = client
+        .read()
+        .await
+        .fetch_events_from([relay_url], presence::activity_filter(pubkeys), std::time::Duration::from_secs(5))
+        .await;
+
+    let now = chrono::Utc::now().timestamp();
+    let mut changed: HashMap<PublicKey, Contact> = HashMap::new();
+    {
+        let mut contacts = contacts.write().await;
+        if let Ok(events) = events {
+            for event in events.iter() {
+                if let Some(contact) = presence::apply_event(&mut contacts, event, now) {
+                    changed.insert(contact.pubkey, contact);
+                }
+            }
+        }
+        for contact in presence::expire(&mut contacts, now) {
+            changed.insert(contact.pubkey, contact);
+        }
+    }
+
+    for contact in changed.into_values() {
+        updates
+            .send(UiUpdate::PresenceChanged {
+                pubkey: contact.pubkey,
+                online: contact.online,
+                last_seen: contact.last_seen,
+            })
+            .await;
+    }
+}
+
+/// Relays carrying a group's traffic: the group's own relay set, or
+/// `fallback` for groups that do not name any
+async fn group_relays(store: &MlsStore, group_id: &GroupId, fallback: &str) -> Vec<RelayUrl> {
+    let relays: Vec<RelayUrl> = store
+        .get_relays(group_id)
+        .await
+        .map(|relays| relays.into_iter().collect())
+        .unwrap_or_default();
+    if relays.is_empty() {
+        return RelayUrl::parse(fallback).into_iter().collect();
+    }
+    relays
+}
+
+/// Add and connect any of `relays` the client is not using yet. They stay in
+/// the shared pool, so requests that are not about a group name our own relay.
+async fn connect_relays(client: &Client, relays: &[RelayUrl]) {
+    for url in relays {
+        if let Ok(true) = client.add_relay(url.clone()).await {
+            if let Err(e) = client.try_connect_relay(url.clone(), std::time::Duration::from_secs(2)).await {
+                tracing::warn!("Failed to connect to group relay {}: {}", url, e);
+            }
+        }
+    }
+}
+
+/// A short-lived client for someone else's relays, which are not ours to keep.
+/// Call `shutdown` on it when done.
+async fn remote_client(relays: &[RelayUrl]) -> Result<Client> {
+    let remote = Client::default();
+    for url in relays {
+        remote
+            .add_relay(url.clone())
+            .await
+            .map_err(|e| DialogError::General(format!("Invalid relay {}: {}", url, e).into()))?;
+    }
+    remote.connect().await;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    Ok(remote)
+}
+
+/// ID of one of our real-time subscriptions, e.g. `dialog:f0`
+fn subscription_id(name: &str) -> SubscriptionId {
+    SubscriptionId::new(format!("{}{}", SUBSCRIPTION_PREFIX, name))
+}
+
+/// Close every subscription in `ids` and forget them
+async fn unsubscribe_all(client: &Client, ids: &RwLock<HashSet<SubscriptionId>>) {
+    let ids: Vec<SubscriptionId> = ids.write().await.drain().collect();
+    for id in ids {
+        let _ = client.unsubscribe(&id).await;
+    }
+}
+
+/// Subscribe to a group's messages on the group's relays, under an ID of its
+/// own that is added to `ids`
+async fn subscribe_group(
+    client: &Client,
+    store: &MlsStore,
+    group: &group_types::Group,
+    ids: &RwLock<HashSet<SubscriptionId>>,
+    fallback_relay: &str,
+) -> Result<()> {
+    let filter = Filter::new()
+        .kinds([Kind::MlsGroupMessage, Kind::Custom(nostr_kinds::GROUP_SIGNAL)])
+        .custom_tag(
+            SingleLetterTag::lowercase(Alphabet::H),
+            hex::encode(&group.nostr_group_id),
+        );
+    let relays = group_relays(store, &group.mls_group_id, fallback_relay).await;
+    connect_relays(client, &relays).await;
+    // Short enough for relays that limit ID length; 64 bits tell groups apart
+    let id = subscription_id(&format!("g{}", &hex::encode(&group.nostr_group_id)[..16]));
+    client
+        .subscribe_with_id_to(relays, id.clone(), filter, None)
+        .await
+        .map_err(|e| DialogError::General(format!("Failed to subscribe to group: {}", e).into()))?;
+    ids.write().await.insert(id);
+    Ok(())
+}
+
+/// Send a relay our subscriptions again, e.g. after it closed them until we authenticated
+async fn resubscribe(client: &RwLock<Client>, relay_url: &RelayUrl) {
+    let relay = match client.read().await.relay(relay_url).await {
+        Ok(relay) => relay,
+        Err(e) => {
+            tracing::warn!("Failed to resubscribe to {}: {}", relay_url, e);
+            return;
+        }
+    };
+    if let Err(e) = relay.resubscribe().await {
+        tracing::warn!("Failed to resubscribe to {}: {}", relay_url, e);
+    }
+}
+
+/// Sleep for `duration`; returns false if shutdown was requested meanwhile
+async fn sleep_unless_shutdown(shutdown: &mut watch::Receiver<bool>, duration: std::time::Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => !*shutdown.borrow(),
+        _ = shutdown.wait_for(|stop| *stop) => false,
+    }
+}
+
+/// Publish every due outbox entry once.
+/// Returns the number of messages still pending afterwards.
+async fn deliver_outbox(
+    client: &RwLock<Client>,
+    store: &MlsStore,
+    fallback_relay: &str,
+    outbox: &RwLock<Outbox>,
+    updates: &EventChannel,
+) -> usize {
+    let now = chrono::Utc::now().timestamp();
+    let due = outbox.read().await.due(now);
+
+    for entry in due {
+        publish_outbox_entry(client, store, fallback_relay, outbox, updates, &entry, now).await;
+    }
+
+    outbox.read().await.pending_count()
+}
+
+/// Publish one outbox entry to its group's relays, record how each relay
+/// answered and notify the UI
+async fn publish_outbox_entry(
+    client: &RwLock<Client>,
+    store: &MlsStore,
+    fallback_relay: &str,
+    outbox: &RwLock<Outbox>,
+    updates: &EventChannel,
+    entry: &OutboxEntry,
+    now: i64,
+) -> Option<SendReport> {
+    let event = entry.event.as_ref()?;
+    let group_id = entry.mls_group_id()?;
+    let targets = group_relays(store, &group_id, fallback_relay).await;
+
+    let mut relays = Vec::new();
+    let mut error = None;
+    let client = client.read().await;
+    connect_relays(&client, &targets).await;
+    match client.send_event_to(targets, event).await {
+        Ok(output) => {
+            relays.extend(output.success.iter().map(|url| RelayOutcome {
+                relay_url: url.to_string(),
+                ack: RelayAck::Accepted,
+            }));
+            relays.extend(output.failed.iter().map(|(url, message)| {
+                let reason = RejectReason::from_message(message);
+                // The relay already stores this event, so it counts as accepted
+                let ack = if reason == RejectReason::Duplicate {
+                    RelayAck::Accepted
+                } else {
+                    RelayAck::Rejected { reason, message: message.clone() }
+                };
+                RelayOutcome { relay_url: url.to_string(), ack }
+            }));
+        }
+        Err(e) => error = Some(e.to_string()),
+    }
+
+    let accepted_by = relays
+        .iter()
+        .filter(|r| r.ack == RelayAck::Accepted)
+        .map(|r| r.relay_url.clone())
+        .collect();
+    let rejected_by = relays
+        .iter()
+        .filter_map(|r| match &r.ack {
+            RelayAck::Rejected { message, .. } => Some((r.relay_url.clone(), message.clone())),
+            RelayAck::Accepted => None,
+        })
+        .collect();
+
+    let delivery = match outbox
+        .write()
+        .await
+        .record_attempt(&entry.message_id, accepted_by, rejected_by, error, now)
+    {
+        Ok(Some(delivery)) => delivery,
+        Ok(None) => return None,
+        Err(e) => {
+            tracing::warn!("Failed to update outbox: {}", e);
+            return None;
+        }
+    };
+
+    if let Some(group_id) = entry.mls_group_id() {
+        updates
+            .send(UiUpdate::DeliveryUpdate {
+                group_id,
+                message_id: entry.message_id.clone(),
+                delivery: delivery.clone(),
+            })
+            .await;
+    }
+
+    Some(SendReport {
+        message_id: entry.message_id.clone(),
+        event_id: event.id.to_hex(),
+        relays,
+        status: delivery.status,
+    })
+}
+
 /// Builder for RealMlsService with configurable storage backend
 #[derive(Default)]
 pub struct RealMlsServiceBuilder {
     keys: Option<Keys>,
+    signer: Option<Arc<dyn NostrSigner>>,
     relay_url: Option<String>,
     storage_backend: Option<StorageBackend>,
+    storage: Option<NostrMlsStorage>,
+    config: Option<DialogConfig>,
+    welcome_transport: Option<WelcomeTransport>,
 }
 
 impl RealMlsServiceBuilder {
@@ -1153,6 +2817,18 @@ impl RealMlsServiceBuilder {
         self
     }
 
+    /// Set a signer for the Nostr identity key (e.g. a NIP-46 bunker).
+    ///
+    /// Takes precedence over [`RealMlsServiceBuilder::keys`] for signing;
+    /// MLS key material always stays in local storage.
+    pub fn signer<T>(mut self, signer: T) -> Self
+    where
+        T: IntoNostrSigner,
+    {
+        self.signer = Some(signer.into_nostr_signer());
+        self
+    }
+
     /// Set the relay URL
     pub fn relay_url(mut self, relay_url: impl Into<String>) -> Self {
         self.relay_url = Some(relay_url.into());
@@ -1165,12 +2841,57 @@ impl RealMlsServiceBuilder {
         self
     }
 
+    /// Keep the MLS state in a custom storage provider instead of the storage
+    /// backend (e.g. an encrypted or remote-backed store).
+    ///
+    /// The storage backend then only decides where the outbox, event ledger
+    /// and system events are kept, and backups leave the MLS state out. It
+    /// must be set explicitly (even to [`StorageBackend::Memory`]); building
+    /// fails otherwise.
+    pub fn storage_provider<S>(mut self, provider: S) -> Self
+    where
+        S: NostrMlsStorageProvider + Send + 'static,
+    {
+        self.storage = Some(NostrMlsStorage::from_provider(provider));
+        self
+    }
+
+    /// Set per-identity settings (read receipts, ...)
+    pub fn config(mut self, config: DialogConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Set how welcomes are sent to new members (gift-wrapped only by default)
+    pub fn welcome_transport(mut self, welcome_transport: WelcomeTransport) -> Self {
+        self.welcome_transport = Some(welcome_transport);
+        self
+    }
+
     /// Build the RealMlsService
     pub async fn build(self) -> Result<RealMlsService> {
-        let keys = self.keys.ok_or_else(|| DialogError::General("Keys not provided".into()))?;
+        let signer = match (self.signer, &self.keys) {
+            (Some(signer), _) => signer,
+            (None, Some(keys)) => keys.clone().into_nostr_signer(),
+            (None, None) => return Err(DialogError::General("Keys or signer not provided".into())),
+        };
         let relay_url = self.relay_url.ok_or_else(|| DialogError::General("Relay URL not provided".into()))?;
-        let storage_backend = self.storage_backend.unwrap_or_default();
+        let (nostr_mls, storage_backend) = match (self.storage, self.storage_backend) {
+            (Some(storage), Some(backend)) => (storage, backend),
+            // Side stores would silently end up in memory and be lost on restart
+            (Some(_), None) => {
+                return Err(DialogError::General(
+                    "A storage backend is required alongside a storage provider".into(),
+                ))
+            }
+            (None, backend) => {
+                let backend = backend.unwrap_or_default();
+                (NostrMlsStorage::new(backend.clone()).await?, backend)
+            }
+        };
+        let config = self.config.unwrap_or_default();
+        let welcome_transport = self.welcome_transport.unwrap_or_default();
 
-        RealMlsService::new_with_storage(keys, relay_url, storage_backend).await
+        RealMlsService::new_with_storage(signer, self.keys, relay_url, nostr_mls, storage_backend, config, welcome_transport).await
     }
-}
\ No newline at end of file
+}
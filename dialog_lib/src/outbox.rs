@@ -0,0 +1,329 @@
+//! Persistent queue of outgoing group messages.
+//!
+//! Messages are MLS-encrypted when composed and queued here until a relay
+//! accepts them, so composing works while offline. With SQLite storage the
+//! queue is kept in a JSON file next to the database; with memory storage it
+//! lives as long as the process, like the MLS state itself.
+
+use crate::errors::Result;
+use crate::json_store::JsonStore;
+use crate::storage::StorageBackend;
+use crate::types::{DeliveryInfo, DeliveryStatus, RejectReason};
+use nostr_mls::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Publish attempts before a message is marked as failed
+pub const MAX_ATTEMPTS: u32 = 8;
+/// Upper bound for the delay between two attempts, in seconds
+const MAX_BACKOFF_SECS: i64 = 300;
+/// How long a sent message's delivery report is kept for display, in seconds
+pub const SENT_RETENTION_SECS: i64 = 24 * 60 * 60;
+
+/// A queued outgoing message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    /// Hex-encoded MLS group ID
+    pub group_id: String,
+    /// ID of the inner message (matches `Message::id`)
+    pub message_id: String,
+    /// Encrypted group message event; dropped once the message is sent
+    pub event: Option<Event>,
+    /// When the message was queued (Unix timestamp in seconds)
+    pub created_at: i64,
+    /// Earliest time for the next attempt (Unix timestamp in seconds)
+    pub next_attempt_at: i64,
+    /// When a relay accepted the message (Unix timestamp in seconds)
+    #[serde(default)]
+    pub sent_at: Option<i64>,
+    pub delivery: DeliveryInfo,
+}
+
+impl OutboxEntry {
+    pub fn mls_group_id(&self) -> Option<GroupId> {
+        hex::decode(&self.group_id).ok().map(|bytes| GroupId::from_slice(&bytes))
+    }
+}
+
+/// Outbox of outgoing messages, keyed by message ID
+#[derive(Debug, Default)]
+pub struct Outbox {
+    store: JsonStore<BTreeMap<String, OutboxEntry>>,
+}
+
+impl Outbox {
+    /// Open the outbox belonging to a storage backend
+    pub fn for_backend(backend: &StorageBackend) -> Result<Self> {
+        Self::pruned(JsonStore::for_backend(backend, "outbox.json")?)
+    }
+
+    /// Open an outbox file, starting empty if it does not exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        Self::pruned(JsonStore::open(path)?)
+    }
+
+    fn pruned(store: JsonStore<BTreeMap<String, OutboxEntry>>) -> Result<Self> {
+        let mut outbox = Self { store };
+        if outbox.prune_sent(chrono::Utc::now().timestamp()) > 0 {
+            outbox.store.save()?;
+        }
+        Ok(outbox)
+    }
+
+    /// Drop sent messages whose delivery report is older than
+    /// [`SENT_RETENTION_SECS`], returning how many were removed
+    pub fn prune_sent(&mut self, now: i64) -> usize {
+        let before = self.store.get().len();
+        self.store.get_mut().retain(|_, e| {
+            e.delivery.status != DeliveryStatus::Sent
+                || e.sent_at.unwrap_or(e.created_at) + SENT_RETENTION_SECS > now
+        });
+        before - self.store.get().len()
+    }
+
+    /// Queue an encrypted message for delivery
+    pub fn enqueue(&mut self, group_id: &GroupId, message_id: String, event: Event) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        self.store.get_mut().insert(
+            message_id.clone(),
+            OutboxEntry {
+                group_id: hex::encode(group_id.as_slice()),
+                message_id,
+                event: Some(event),
+                created_at: now,
+                next_attempt_at: now,
+                sent_at: None,
+                delivery: DeliveryInfo::pending(),
+            },
+        );
+        self.store.save()
+    }
+
+    /// Pending entries whose next attempt is due at `now`
+    pub fn due(&self, now: i64) -> Vec<OutboxEntry> {
+        self.store
+            .get()
+            .values()
+            .filter(|e| e.delivery.status == DeliveryStatus::Pending && e.next_attempt_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Earliest scheduled attempt among pending entries
+    pub fn next_attempt_at(&self) -> Option<i64> {
+        self.store
+            .get()
+            .values()
+            .filter(|e| e.delivery.status == DeliveryStatus::Pending)
+            .map(|e| e.next_attempt_at)
+            .min()
+    }
+
+    /// Number of messages still waiting for a relay
+    pub fn pending_count(&self) -> usize {
+        self.store
+            .get()
+            .values()
+            .filter(|e| e.delivery.status == DeliveryStatus::Pending)
+            .count()
+    }
+
+    /// Look up a queued message
+    pub fn get(&self, message_id: &str) -> Option<&OutboxEntry> {
+        self.store.get().get(message_id)
+    }
+
+    /// Delivery state of a message, if it went through the outbox
+    pub fn delivery(&self, message_id: &str) -> Option<&DeliveryInfo> {
+        self.store.get().get(message_id).map(|e| &e.delivery)
+    }
+
+    /// Record the outcome of a publish attempt and return the updated delivery state.
+    ///
+    /// `error` describes a failure that prevented reaching any relay at all.
+    /// Rejections that cannot succeed on retry (e.g. `invalid:` or `blocked:`)
+    /// from every relay fail the message immediately.
+    pub fn record_attempt(
+        &mut self,
+        message_id: &str,
+        accepted_by: Vec<String>,
+        rejected_by: Vec<(String, String)>,
+        error: Option<String>,
+        now: i64,
+    ) -> Result<Option<DeliveryInfo>> {
+        let Some(entry) = self.store.get_mut().get_mut(message_id) else {
+            return Ok(None);
+        };
+
+        let delivery = &mut entry.delivery;
+        delivery.attempts += 1;
+        for relay in accepted_by {
+            delivery.rejected_by.retain(|(url, _)| url != &relay);
+            if !delivery.accepted_by.contains(&relay) {
+                delivery.accepted_by.push(relay);
+            }
+        }
+        for (relay, reason) in rejected_by {
+            delivery.rejected_by.retain(|(url, _)| url != &relay);
+            delivery.rejected_by.push((relay, reason));
+        }
+
+        let permanently_rejected = error.is_none()
+            && !delivery.rejected_by.is_empty()
+            && delivery
+                .rejected_by
+                .iter()
+                .all(|(_, reason)| !RejectReason::from_message(reason).is_retryable());
+
+        if !delivery.accepted_by.is_empty() {
+            delivery.status = DeliveryStatus::Sent;
+            entry.event = None;
+            entry.sent_at = Some(now);
+        } else if permanently_rejected || delivery.attempts >= MAX_ATTEMPTS {
+            let reason = error
+                .or_else(|| delivery.rejected_by.last().map(|(_, reason)| reason.clone()))
+                .unwrap_or_else(|| "No relay accepted the message".to_string());
+            delivery.status = DeliveryStatus::Failed(reason);
+            entry.event = None;
+        } else {
+            entry.next_attempt_at = now + backoff_secs(delivery.attempts);
+        }
+
+        let delivery = delivery.clone();
+        self.prune_sent(now);
+        self.store.save()?;
+        Ok(Some(delivery))
+    }
+
+    /// Make every pending entry due immediately (e.g. after reconnecting)
+    pub fn reset_backoff(&mut self) {
+        let now = chrono::Utc::now().timestamp();
+        for entry in self.store.get_mut().values_mut() {
+            if entry.delivery.status == DeliveryStatus::Pending {
+                entry.next_attempt_at = entry.next_attempt_at.min(now);
+            }
+        }
+    }
+}
+
+/// Exponential backoff: 2s, 4s, 8s, ... capped at five minutes
+fn backoff_secs(attempts: u32) -> i64 {
+    (1i64 << attempts.min(16)).min(MAX_BACKOFF_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_event() -> Event {
+        EventBuilder::new(Kind::MlsGroupMessage, "ciphertext")
+            .sign_with_keys(&Keys::generate())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_retry_then_sent() {
+        let group_id = GroupId::from_slice(&[1u8; 16]);
+        let mut outbox = Outbox::default();
+        outbox.enqueue(&group_id, "m1".into(), test_event()).unwrap();
+        assert_eq!(outbox.pending_count(), 1);
+        assert_eq!(outbox.due(0).len(), 0);
+
+        let now = chrono::Utc::now().timestamp();
+        assert_eq!(outbox.due(now).len(), 1);
+
+        let delivery = outbox
+            .record_attempt("m1", vec![], vec![], Some("offline".into()), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert!(outbox.due(now).is_empty());
+        assert_eq!(outbox.next_attempt_at(), Some(now + 2));
+
+        let delivery = outbox
+            .record_attempt(
+                "m1",
+                vec!["wss://a".into()],
+                vec![("wss://b".into(), "blocked: spam".into())],
+                None,
+                now + 2,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Sent);
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.accepted_by, vec!["wss://a".to_string()]);
+        assert_eq!(delivery.rejected_by.len(), 1);
+        assert_eq!(outbox.pending_count(), 0);
+    }
+
+    #[test]
+    fn test_fails_after_max_attempts() {
+        let group_id = GroupId::from_slice(&[2u8; 16]);
+        let mut outbox = Outbox::default();
+        outbox.enqueue(&group_id, "m2".into(), test_event()).unwrap();
+
+        let mut delivery = None;
+        for attempt in 0..MAX_ATTEMPTS {
+            delivery = outbox
+                .record_attempt("m2", vec![], vec![], Some("timeout".into()), attempt as i64)
+                .unwrap();
+        }
+        assert_eq!(delivery.unwrap().status, DeliveryStatus::Failed("timeout".into()));
+        assert_eq!(outbox.next_attempt_at(), None);
+    }
+
+    #[test]
+    fn test_permanent_rejection_fails_immediately() {
+        let group_id = GroupId::from_slice(&[4u8; 16]);
+        let mut outbox = Outbox::default();
+        outbox.enqueue(&group_id, "m4".into(), test_event()).unwrap();
+
+        let delivery = outbox
+            .record_attempt("m4", vec![], vec![("wss://a".into(), "rate-limited: slow down".into())], None, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+
+        let delivery = outbox
+            .record_attempt("m4", vec![], vec![("wss://a".into(), "blocked: not allowed".into())], None, 10)
+            .unwrap()
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Failed("blocked: not allowed".into()));
+    }
+
+    #[test]
+    fn test_sent_entries_are_pruned_after_retention() {
+        let group_id = GroupId::from_slice(&[5u8; 16]);
+        let mut outbox = Outbox::default();
+        outbox.enqueue(&group_id, "m5".into(), test_event()).unwrap();
+        outbox.enqueue(&group_id, "m6".into(), test_event()).unwrap();
+        outbox.record_attempt("m5", vec!["wss://a".into()], vec![], None, 100).unwrap();
+
+        // Still shown right after sending; pending messages are never pruned
+        assert_eq!(outbox.prune_sent(100 + SENT_RETENTION_SECS - 1), 0);
+        assert!(outbox.delivery("m5").is_some());
+        assert_eq!(outbox.prune_sent(100 + SENT_RETENTION_SECS), 1);
+        assert!(outbox.delivery("m5").is_none());
+        assert_eq!(outbox.pending_count(), 1);
+    }
+
+    #[test]
+    fn test_persists_to_file() {
+        let path = std::env::temp_dir().join(format!("dialog_outbox_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let group_id = GroupId::from_slice(&[3u8; 16]);
+
+        let mut outbox = Outbox::open(&path).unwrap();
+        outbox.enqueue(&group_id, "m3".into(), test_event()).unwrap();
+
+        let reopened = Outbox::open(&path).unwrap();
+        assert_eq!(reopened.pending_count(), 1);
+        let entry = &reopened.due(i64::MAX)[0];
+        assert_eq!(entry.mls_group_id(), Some(group_id));
+        assert!(entry.event.is_some());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
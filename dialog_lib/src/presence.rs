@@ -0,0 +1,149 @@
+//! Contact presence derived from observed activity.
+//!
+//! Nostr has no reliable online signal, so a contact counts as online when we
+//! saw them act recently: a group message (including receipts and typing
+//! signals), a profile update, or a NIP-38 user status event.
+
+use crate::types::Contact;
+use nostr_mls::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// A contact seen within this many seconds counts as online
+pub const ONLINE_WINDOW_SECS: i64 = 300;
+/// How often the service re-checks contact activity on the relay
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// NIP-38 user status
+pub const USER_STATUS_KIND: u16 = 30315;
+
+/// Whether activity at `last_seen` still counts as online at `now`
+pub fn is_online(last_seen: Option<i64>, now: i64) -> bool {
+    last_seen.is_some_and(|seen| now - seen <= ONLINE_WINDOW_SECS)
+}
+
+/// Record activity by `pubkey` at `timestamp` if they are a contact.
+/// Returns the updated contact when their online state changed.
+pub fn record_activity(
+    contacts: &mut HashMap<PublicKey, Contact>,
+    pubkey: &PublicKey,
+    timestamp: i64,
+    now: i64,
+) -> Option<Contact> {
+    let contact = contacts.get_mut(pubkey)?;
+    if contact.last_seen.is_some_and(|seen| seen >= timestamp) {
+        return None;
+    }
+    let was_online = contact.online;
+    contact.last_seen = Some(timestamp);
+    contact.online = is_online(contact.last_seen, now);
+    (contact.online != was_online).then(|| contact.clone())
+}
+
+/// Apply a profile update or NIP-38 status event from a contact.
+/// Returns the updated contact when their online state changed.
+pub fn apply_event(contacts: &mut HashMap<PublicKey, Contact>, event: &Event, now: i64) -> Option<Contact> {
+    if let Some(status) = general_status(event, now) {
+        if let Some(contact) = contacts.get_mut(&event.pubkey) {
+            contact.status = (!status.is_empty()).then_some(status);
+        }
+    }
+    record_activity(contacts, &event.pubkey, event.created_at.as_u64() as i64, now)
+}
+
+/// Recompute online flags as time passes; returns contacts whose state changed
+pub fn expire(contacts: &mut HashMap<PublicKey, Contact>, now: i64) -> Vec<Contact> {
+    contacts
+        .values_mut()
+        .filter_map(|contact| {
+            let online = is_online(contact.last_seen, now);
+            if online == contact.online {
+                return None;
+            }
+            contact.online = online;
+            Some(contact.clone())
+        })
+        .collect()
+}
+
+/// Filter for the activity events presence is derived from outside of groups
+pub fn activity_filter(pubkeys: impl IntoIterator<Item = PublicKey>) -> Filter {
+    Filter::new()
+        .authors(pubkeys)
+        .kinds([Kind::Metadata, Kind::Custom(USER_STATUS_KIND)])
+}
+
+/// The "general" status text of a NIP-38 event; `Some("")` when cleared or
+/// expired, `None` for other status types.
+pub fn general_status(event: &Event, now: i64) -> Option<String> {
+    if event.kind != Kind::Custom(USER_STATUS_KIND) {
+        return None;
+    }
+    let tag_value = |name: &str| {
+        event
+            .tags
+            .iter()
+            .map(|tag| tag.as_slice())
+            .find(|values| values.first().map(String::as_str) == Some(name))
+            .and_then(|values| values.get(1).cloned())
+    };
+    if tag_value("d").as_deref() != Some("general") {
+        return None;
+    }
+    let expired = tag_value("expiration")
+        .and_then(|value| value.parse::<i64>().ok())
+        .is_some_and(|expires_at| expires_at <= now);
+    Some(if expired { String::new() } else { event.content.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(pubkey: PublicKey) -> Contact {
+        Contact { name: "bob".into(), pubkey, online: false, last_seen: None, status: None }
+    }
+
+    fn status_event(keys: &Keys, content: &str, tags: Vec<Vec<&str>>) -> Event {
+        let tags: Vec<Tag> = tags.into_iter().map(|t| Tag::parse(t).unwrap()).collect();
+        EventBuilder::new(Kind::Custom(USER_STATUS_KIND), content)
+            .tags(tags)
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_activity_and_expiry() {
+        let bob = Keys::generate().public_key();
+        let stranger = Keys::generate().public_key();
+        let mut contacts = HashMap::from([(bob, contact(bob))]);
+
+        assert!(record_activity(&mut contacts, &stranger, 1000, 1000).is_none());
+
+        let changed = record_activity(&mut contacts, &bob, 1000, 1010).unwrap();
+        assert!(changed.online);
+        assert_eq!(changed.last_seen, Some(1000));
+
+        // Older activity neither moves last_seen back nor reports a change
+        assert!(record_activity(&mut contacts, &bob, 900, 1010).is_none());
+        assert_eq!(contacts[&bob].last_seen, Some(1000));
+
+        assert!(expire(&mut contacts, 1000 + ONLINE_WINDOW_SECS).is_empty());
+        let expired = expire(&mut contacts, 1001 + ONLINE_WINDOW_SECS);
+        assert_eq!(expired.len(), 1);
+        assert!(!contacts[&bob].online);
+    }
+
+    #[test]
+    fn test_general_status() {
+        let keys = Keys::generate();
+        let event = status_event(&keys, "Working", vec![vec!["d", "general"]]);
+        assert_eq!(general_status(&event, 0), Some("Working".to_string()));
+
+        let music = status_event(&keys, "Song", vec![vec!["d", "music"]]);
+        assert_eq!(general_status(&music, 0), None);
+
+        let expiring = status_event(&keys, "Lunch", vec![vec!["d", "general"], vec!["expiration", "100"]]);
+        assert_eq!(general_status(&expiring, 99), Some("Lunch".to_string()));
+        assert_eq!(general_status(&expiring, 100), Some(String::new()));
+    }
+}
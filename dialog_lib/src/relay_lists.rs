@@ -0,0 +1,209 @@
+//! Relay lists: where a user can be reached.
+//!
+//! We advertise the relays our key packages live on (kind 10051) and our
+//! general NIP-65 relay list (kind 10002), and read the same lists from people
+//! we invite so their key packages are fetched from wherever they publish them.
+
+use nostr::nips::nip65::{self, RelayMetadata};
+use nostr_mls::prelude::*;
+
+/// Key package relay list (MIP-00)
+pub const KEY_PACKAGE_RELAYS_KIND: u16 = 10051;
+
+/// Event advertising the relays our key packages are published to
+pub fn key_package_relays_event(relays: &[RelayUrl]) -> EventBuilder {
+    let tags = relays
+        .iter()
+        .map(|url| Tag::custom(TagKind::custom("relay"), [url.to_string()]));
+    EventBuilder::new(Kind::from(KEY_PACKAGE_RELAYS_KIND), "").tags(tags)
+}
+
+/// NIP-65 relay list; we read from and write to every relay we use
+pub fn nip65_event(relays: &[RelayUrl]) -> EventBuilder {
+    EventBuilder::relay_list(relays.iter().map(|url| (url.clone(), None)))
+}
+
+/// Filter for the relay lists of `pubkey`
+pub fn relay_lists_filter(pubkey: PublicKey) -> Filter {
+    Filter::new()
+        .author(pubkey)
+        .kinds([Kind::from(KEY_PACKAGE_RELAYS_KIND), Kind::RelayList])
+}
+
+/// The newest kind 10051 list and the newest NIP-65 list among `lists`
+fn newest_lists<'a>(lists: impl IntoIterator<Item = &'a Event>) -> (Option<&'a Event>, Option<&'a Event>) {
+    let mut key_package_list: Option<&Event> = None;
+    let mut relay_list: Option<&Event> = None;
+    for event in lists {
+        let slot = if event.kind == Kind::from(KEY_PACKAGE_RELAYS_KIND) {
+            &mut key_package_list
+        } else if event.kind == Kind::RelayList {
+            &mut relay_list
+        } else {
+            continue;
+        };
+        if slot.is_none_or(|newest| event.created_at > newest.created_at) {
+            *slot = Some(event);
+        }
+    }
+    (key_package_list, relay_list)
+}
+
+/// Relays named by the `relay` tags of a kind 10051 list
+fn listed_relays(list: &Event) -> impl Iterator<Item = RelayUrl> + '_ {
+    list.tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .filter(|values| values.first().map(String::as_str) == Some("relay"))
+        .filter_map(|values| values.get(1).and_then(|url| RelayUrl::parse(url).ok()))
+}
+
+/// NIP-65 relays, without those marked only for `skip`
+fn nip65_relays(list: &Event, skip: RelayMetadata) -> impl Iterator<Item = RelayUrl> + '_ {
+    nip65::extract_relay_list(list)
+        .filter(move |(_, metadata)| !matches!(metadata, Some(only) if *only == skip))
+        .map(|(url, _)| url.clone())
+}
+
+fn dedup(urls: impl IntoIterator<Item = RelayUrl>) -> Vec<RelayUrl> {
+    let mut relays = Vec::new();
+    for url in urls {
+        if !relays.contains(&url) {
+            relays.push(url);
+        }
+    }
+    relays
+}
+
+/// Relays to look for someone's key packages on, from their relay lists:
+/// the newest kind 10051 list if they have one, otherwise the write relays of
+/// their newest NIP-65 list.
+pub fn key_package_relays<'a>(lists: impl IntoIterator<Item = &'a Event>) -> Vec<RelayUrl> {
+    match newest_lists(lists) {
+        (Some(list), _) => dedup(listed_relays(list)),
+        (None, Some(list)) => dedup(nip65_relays(list, RelayMetadata::Read)),
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Relays to send someone's welcome to: the read relays of their newest
+/// NIP-65 list and the relays their key packages live on.
+pub fn inbox_relays<'a>(lists: impl IntoIterator<Item = &'a Event>) -> Vec<RelayUrl> {
+    let (key_package_list, relay_list) = newest_lists(lists);
+    let read = relay_list.into_iter().flat_map(|list| nip65_relays(list, RelayMetadata::Write));
+    let key_packages = key_package_list.into_iter().flat_map(listed_relays);
+    dedup(read.chain(key_packages))
+}
+
+/// Our lists with `relays` added to what we published before, leaving out
+/// any list that already names all of them. Other relays on an existing list
+/// (set up by another client, say) are kept.
+pub fn merged_lists<'a>(
+    existing: impl IntoIterator<Item = &'a Event>,
+    relays: &[RelayUrl],
+) -> Vec<EventBuilder> {
+    let (key_package_list, relay_list) = newest_lists(existing);
+    let mut updates = Vec::new();
+
+    let listed: Vec<RelayUrl> = key_package_list.into_iter().flat_map(listed_relays).collect();
+    if relays.iter().any(|url| !listed.contains(url)) {
+        updates.push(key_package_relays_event(&dedup(listed.into_iter().chain(relays.iter().cloned()))));
+    }
+
+    let entries: Vec<(RelayUrl, Option<RelayMetadata>)> = relay_list
+        .into_iter()
+        .flat_map(nip65::extract_relay_list)
+        .map(|(url, metadata)| (url.clone(), *metadata))
+        .collect();
+    let missing: Vec<RelayUrl> = relays
+        .iter()
+        .filter(|url| !entries.iter().any(|(listed, _)| listed == *url))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        updates.push(EventBuilder::relay_list(
+            entries.into_iter().chain(missing.into_iter().map(|url| (url, None))),
+        ));
+    }
+    updates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> RelayUrl {
+        RelayUrl::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_key_package_list_wins_over_nip65() {
+        let keys = Keys::generate();
+        let nip65 = nip65_event(&[url("wss://general.example")]).sign_with_keys(&keys).unwrap();
+        assert_eq!(key_package_relays([&nip65]), vec![url("wss://general.example")]);
+
+        let key_packages = key_package_relays_event(&[url("wss://kp-one.example"), url("wss://kp-two.example")])
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert_eq!(
+            key_package_relays([&nip65, &key_packages]),
+            vec![url("wss://kp-one.example"), url("wss://kp-two.example")]
+        );
+        assert!(key_package_relays([]).is_empty());
+    }
+
+    #[test]
+    fn test_nip65_read_relays_and_newest_list() {
+        let keys = Keys::generate();
+        let old = nip65_event(&[url("wss://old.example")])
+            .custom_created_at(Timestamp::from(1000))
+            .sign_with_keys(&keys)
+            .unwrap();
+        let new = EventBuilder::relay_list([
+            (url("wss://inbox.example"), Some(RelayMetadata::Read)),
+            (url("wss://outbox.example"), Some(RelayMetadata::Write)),
+            (url("wss://both.example"), None),
+        ])
+        .custom_created_at(Timestamp::from(2000))
+        .sign_with_keys(&keys)
+        .unwrap();
+
+        assert_eq!(
+            key_package_relays([&new, &old]),
+            vec![url("wss://outbox.example"), url("wss://both.example")]
+        );
+        assert_eq!(
+            inbox_relays([&new, &old]),
+            vec![url("wss://inbox.example"), url("wss://both.example")]
+        );
+    }
+
+    #[test]
+    fn test_merged_lists_keep_existing_relays() {
+        let keys = Keys::generate();
+        let ours = url("wss://ours.example");
+        assert_eq!(merged_lists([], std::slice::from_ref(&ours)).len(), 2);
+
+        let key_packages = key_package_relays_event(&[url("wss://elsewhere.example")])
+            .sign_with_keys(&keys)
+            .unwrap();
+        let nip65 = EventBuilder::relay_list([(url("wss://inbox.example"), Some(RelayMetadata::Read))])
+            .sign_with_keys(&keys)
+            .unwrap();
+        let merged: Vec<Event> = merged_lists([&key_packages, &nip65], std::slice::from_ref(&ours))
+            .into_iter()
+            .map(|builder| builder.sign_with_keys(&keys).unwrap())
+            .collect();
+        assert_eq!(
+            key_package_relays(merged.iter()),
+            vec![url("wss://elsewhere.example"), ours.clone()]
+        );
+        assert_eq!(
+            inbox_relays(merged.iter().filter(|e| e.kind == Kind::RelayList)),
+            vec![url("wss://inbox.example"), ours.clone()]
+        );
+
+        // Nothing to publish once every list names our relay
+        assert!(merged_lists(merged.iter(), &[ours]).is_empty());
+    }
+}
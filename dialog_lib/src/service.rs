@@ -1,8 +1,10 @@
-use crate::types::{Contact, Conversation, ConnectionStatus, Profile, InviteListResult, MessageFetchResult, UiUpdate};
+use crate::types::{Contact, Conversation, ConnectionStatus, Profile, GroupCreationReport, InviteListResult, Message, MessageFetchResult, SendReport, UiUpdate};
 use crate::errors::Result;
+use crate::group_ref::GroupRef;
+use crate::subscription::SubscriptionHandle;
 use nostr_mls::prelude::*;
 use std::any::Any;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 
 #[async_trait::async_trait]
 pub trait MlsService: Send + Sync + std::fmt::Debug {
@@ -10,10 +12,18 @@ pub trait MlsService: Send + Sync + std::fmt::Debug {
     async fn get_contacts(&self) -> Result<Vec<Contact>>;
     async fn get_conversations(&self) -> Result<Vec<Conversation>>;
     async fn get_connection_status(&self) -> Result<ConnectionStatus>;
-    async fn send_message(&self, group_id: &GroupId, content: &str) -> Result<()>;
+    async fn send_message(&self, group_id: &GroupId, content: &str) -> Result<SendReport>;
     async fn create_conversation(&self, name: &str, participants: Vec<PublicKey>) -> Result<String>;
+    // Create the group with every participant whose key package is usable; report the rest
+    async fn create_conversation_best_effort(&self, name: &str, participants: Vec<PublicKey>) -> Result<GroupCreationReport>;
+    // Add members to a group we administer, or re-send welcomes that never went out
+    async fn add_members(&self, group_id: &GroupId, participants: Vec<PublicKey>) -> Result<GroupCreationReport>;
+    // Find the one-to-one conversation with `pubkey`, creating it if there is none
+    async fn open_direct_conversation(&self, pubkey: &PublicKey) -> Result<Conversation>;
     async fn add_contact(&self, pubkey: &str) -> Result<()>;
     async fn switch_conversation(&self, conversation_id: &str) -> Result<()>;
+    // Resolve a group reference (ID, Nostr group ID, name or prefix) against stored groups
+    async fn resolve_group(&self, group: &GroupRef) -> Result<GroupId>;
     async fn get_active_conversation(&self) -> Result<Option<String>>;
     async fn get_pending_invites_count(&self) -> Result<usize>;
     async fn toggle_connection(&self) -> Result<ConnectionStatus>;
@@ -25,14 +35,33 @@ pub trait MlsService: Send + Sync + std::fmt::Debug {
     // New methods for group lifecycle
     async fn publish_key_packages(&self) -> Result<Vec<String>>; // Returns event IDs
     async fn list_pending_invites(&self) -> Result<InviteListResult>;
-    async fn accept_invite(&self, group_id: &str) -> Result<()>;
+    async fn accept_invite(&self, invite: &GroupRef) -> Result<()>;
     async fn fetch_and_process_group_events(&self, group_id: &GroupId) -> Result<()>;
     
     // Message fetching
     async fn fetch_messages(&self, group_id: &GroupId) -> Result<MessageFetchResult>;
     
-    // Real-time message subscription
-    async fn subscribe_to_groups(&self, ui_sender: mpsc::Sender<UiUpdate>) -> Result<()>;
+    // Read receipts: advance our read marker to the newest message from others.
+    // Returns whether the marker moved; a receipt is only sent when enabled in the config.
+    async fn mark_read(&self, group_id: &GroupId) -> Result<bool>;
+    
+    // Typing indicators: signal that we started (`true`) or stopped typing.
+    // Returns whether a signal was actually sent (starts are rate-limited).
+    async fn send_typing(&self, group_id: &GroupId, typing: bool) -> Result<bool>;
+    
+    // Pinned messages: admins pin or unpin a message for every member of the group
+    async fn set_pinned(&self, group_id: &GroupId, message_id: &str, pinned: bool) -> Result<()>;
+    async fn pinned_messages(&self, group_id: &GroupId) -> Result<Vec<Message>>;
+    
+    // Real-time message subscription; replaces any previous one
+    async fn subscribe_to_groups(&self, ui_sender: mpsc::Sender<UiUpdate>) -> Result<SubscriptionHandle>;
+    // Same, for consumers that only follow `events`
+    async fn subscribe(&self) -> Result<SubscriptionHandle>;
+    // A new receiver of every update, alongside the `subscribe_to_groups` sender
+    fn events(&self) -> broadcast::Receiver<UiUpdate>;
+    
+    // Stop the subscription and background tasks, flush the outbox and disconnect
+    async fn shutdown(&self) -> Result<()>;
     
     // Refresh subscriptions after group changes
     async fn refresh_subscriptions(&self) -> Result<()>;
@@ -0,0 +1,35 @@
+//! Signer helpers for the Nostr identity key.
+//!
+//! [`RealMlsService`](crate::RealMlsService) signs every Nostr event (key
+//! packages, welcomes, profiles) through a [`NostrSigner`], so the identity key
+//! can live in-process ([`Keys`]) or in a NIP-46 remote signer ("bunker").
+//! MLS key material is never handed to the signer.
+
+use crate::errors::{DialogError, Result};
+use nostr_connect::prelude::{NostrConnect, NostrConnectURI};
+use nostr_sdk::prelude::{IntoNostrSigner, Keys, NostrSigner};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default time to wait for a remote signer to answer a request
+pub const DEFAULT_BUNKER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Connect to a NIP-46 remote signer from a `bunker://` URI.
+///
+/// A fresh app key is generated for the session. The connection is verified by
+/// asking the bunker for the user's public key before returning.
+pub async fn connect_bunker(uri: &str, timeout: Duration) -> Result<Arc<dyn NostrSigner>> {
+    let uri = NostrConnectURI::parse(uri)
+        .map_err(|e| DialogError::General(format!("Invalid bunker URI: {}", e).into()))?;
+    let app_keys = Keys::generate();
+    let connect = NostrConnect::new(uri, app_keys, timeout, None)
+        .map_err(|e| DialogError::General(format!("Failed to set up remote signer: {}", e).into()))?;
+
+    // Fail early if the bunker is unreachable or rejects us
+    connect
+        .get_public_key()
+        .await
+        .map_err(|e| DialogError::General(format!("Remote signer did not respond: {}", e).into()))?;
+
+    Ok(connect.into_nostr_signer())
+}
@@ -1,5 +1,5 @@
 use nostr_mls::prelude::*;
-use nostr_mls::groups::{GroupResult, UpdateGroupResult};
+use nostr_mls::groups::{GroupResult, NostrGroupDataUpdate, UpdateGroupResult};
 use nostr_mls::messages::MessageProcessingResult;
 use nostr_mls_storage::groups::types as group_types;
 use nostr_mls_storage::messages::types as message_types;
@@ -7,9 +7,9 @@ use nostr_mls_storage::welcomes::types as welcome_types;
 use openmls::prelude::KeyPackage;
 use nostr_mls_memory_storage::NostrMlsMemoryStorage;
 use nostr_mls_sqlite_storage::NostrMlsSqliteStorage;
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use nostr_mls_storage::NostrMlsStorageProvider;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub enum StorageBackend {
@@ -23,137 +23,248 @@ impl Default for StorageBackend {
     }
 }
 
-pub enum NostrMlsStorage {
-    Memory(Arc<RwLock<NostrMls<NostrMlsMemoryStorage>>>),
-    Sqlite(Arc<tokio::sync::Mutex<NostrMls<NostrMlsSqliteStorage>>>),
+/// The NostrMls operations dialog uses. Object safe, so NostrMls over any
+/// storage provider fits behind [`NostrMlsStorage`].
+trait MlsEngine: Send {
+    fn get_groups(&self) -> Result<Vec<group_types::Group>, nostr_mls::Error>;
+    fn create_key_package_for_event(&self, pubkey: &PublicKey, relays: Vec<RelayUrl>) -> Result<(String, Vec<Tag>), nostr_mls::Error>;
+    fn parse_key_package(&self, event: &Event) -> Result<KeyPackage, nostr_mls::Error>;
+    fn create_group(
+        &self,
+        pubkey: &PublicKey,
+        key_packages: Vec<Event>,
+        admins: Vec<PublicKey>,
+        config: NostrGroupConfigData,
+    ) -> Result<GroupResult, nostr_mls::Error>;
+    fn process_message(&self, event: &Event) -> Result<MessageProcessingResult, nostr_mls::Error>;
+    fn process_welcome(&self, gift_wrap_id: &EventId, rumor: &UnsignedEvent) -> Result<welcome_types::Welcome, nostr_mls::Error>;
+    fn get_pending_welcomes(&self) -> Result<Vec<welcome_types::Welcome>, nostr_mls::Error>;
+    fn accept_welcome(&self, welcome: &welcome_types::Welcome) -> Result<(), nostr_mls::Error>;
+    fn create_message(&self, group_id: &GroupId, rumor: UnsignedEvent) -> Result<Event, nostr_mls::Error>;
+    fn get_messages(&self, group_id: &GroupId) -> Result<Vec<message_types::Message>, nostr_mls::Error>;
+    fn get_relays(&self, group_id: &GroupId) -> Result<BTreeSet<RelayUrl>, nostr_mls::Error>;
+    fn get_members(&self, group_id: &GroupId) -> Result<BTreeSet<PublicKey>, nostr_mls::Error>;
+    fn exporter_secret(&self, group_id: &GroupId) -> Result<group_types::GroupExporterSecret, nostr_mls::Error>;
+    fn add_members(&self, group_id: &GroupId, key_packages: &[Event]) -> Result<UpdateGroupResult, nostr_mls::Error>;
+    fn remove_members(&self, group_id: &GroupId, members: &[PublicKey]) -> Result<UpdateGroupResult, nostr_mls::Error>;
+    fn update_group_data(&self, group_id: &GroupId, update: NostrGroupDataUpdate) -> Result<UpdateGroupResult, nostr_mls::Error>;
+    fn merge_pending_commit(&self, group_id: &GroupId) -> Result<(), nostr_mls::Error>;
 }
 
-impl std::fmt::Debug for NostrMlsStorage {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Memory(_) => write!(f, "NostrMlsStorage::Memory"),
-            Self::Sqlite(_) => write!(f, "NostrMlsStorage::Sqlite"),
-        }
+impl<S> MlsEngine for NostrMls<S>
+where
+    S: NostrMlsStorageProvider + Send,
+{
+    fn get_groups(&self) -> Result<Vec<group_types::Group>, nostr_mls::Error> {
+        NostrMls::get_groups(self)
+    }
+
+    fn create_key_package_for_event(&self, pubkey: &PublicKey, relays: Vec<RelayUrl>) -> Result<(String, Vec<Tag>), nostr_mls::Error> {
+        let (content, tags) = NostrMls::create_key_package_for_event(self, pubkey, relays)?;
+        Ok((content, tags.to_vec()))
+    }
+
+    fn parse_key_package(&self, event: &Event) -> Result<KeyPackage, nostr_mls::Error> {
+        NostrMls::parse_key_package(self, event)
+    }
+
+    fn create_group(
+        &self,
+        pubkey: &PublicKey,
+        key_packages: Vec<Event>,
+        admins: Vec<PublicKey>,
+        config: NostrGroupConfigData,
+    ) -> Result<GroupResult, nostr_mls::Error> {
+        NostrMls::create_group(self, pubkey, key_packages, admins, config)
+    }
+
+    fn process_message(&self, event: &Event) -> Result<MessageProcessingResult, nostr_mls::Error> {
+        NostrMls::process_message(self, event)
+    }
+
+    fn process_welcome(&self, gift_wrap_id: &EventId, rumor: &UnsignedEvent) -> Result<welcome_types::Welcome, nostr_mls::Error> {
+        NostrMls::process_welcome(self, gift_wrap_id, rumor)
+    }
+
+    fn get_pending_welcomes(&self) -> Result<Vec<welcome_types::Welcome>, nostr_mls::Error> {
+        NostrMls::get_pending_welcomes(self)
+    }
+
+    fn accept_welcome(&self, welcome: &welcome_types::Welcome) -> Result<(), nostr_mls::Error> {
+        NostrMls::accept_welcome(self, welcome)
+    }
+
+    fn create_message(&self, group_id: &GroupId, rumor: UnsignedEvent) -> Result<Event, nostr_mls::Error> {
+        NostrMls::create_message(self, group_id, rumor)
+    }
+
+    fn get_messages(&self, group_id: &GroupId) -> Result<Vec<message_types::Message>, nostr_mls::Error> {
+        NostrMls::get_messages(self, group_id)
+    }
+
+    fn get_relays(&self, group_id: &GroupId) -> Result<BTreeSet<RelayUrl>, nostr_mls::Error> {
+        NostrMls::get_relays(self, group_id)
+    }
+
+    fn get_members(&self, group_id: &GroupId) -> Result<BTreeSet<PublicKey>, nostr_mls::Error> {
+        NostrMls::get_members(self, group_id)
+    }
+
+    fn exporter_secret(&self, group_id: &GroupId) -> Result<group_types::GroupExporterSecret, nostr_mls::Error> {
+        NostrMls::exporter_secret(self, group_id)
+    }
+
+    fn add_members(&self, group_id: &GroupId, key_packages: &[Event]) -> Result<UpdateGroupResult, nostr_mls::Error> {
+        NostrMls::add_members(self, group_id, key_packages)
+    }
+
+    fn remove_members(&self, group_id: &GroupId, members: &[PublicKey]) -> Result<UpdateGroupResult, nostr_mls::Error> {
+        NostrMls::remove_members(self, group_id, members)
+    }
+
+    fn update_group_data(&self, group_id: &GroupId, update: NostrGroupDataUpdate) -> Result<UpdateGroupResult, nostr_mls::Error> {
+        NostrMls::update_group_data(self, group_id, update)
+    }
+
+    fn merge_pending_commit(&self, group_id: &GroupId) -> Result<(), nostr_mls::Error> {
+        NostrMls::merge_pending_commit(self, group_id)
     }
 }
 
-// Macro to implement delegating methods to both storage types
-macro_rules! delegate_nostr_mls {
-    ($self:expr, $method:ident, $($arg:expr),*) => {
-        match $self {
-            NostrMlsStorage::Memory(mls) => {
-                let mls = mls.read().await;
-                mls.$method($($arg),*)
-            },
-            NostrMlsStorage::Sqlite(mls) => {
-                let mls = mls.lock().await;
-                mls.$method($($arg),*)
-            },
-        }
-    };
+/// MLS state on any storage provider: one of the built-in backends or one
+/// passed to [`crate::RealMlsServiceBuilder::storage_provider`].
+///
+/// Calls are synchronous and may block on disk; the storage actor
+/// ([`crate::store::MlsStore`]) owns it on its own thread.
+pub struct NostrMlsStorage {
+    mls: Box<dyn MlsEngine>,
+    provider: &'static str,
+    database_path: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for NostrMlsStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NostrMlsStorage")
+            .field("provider", &self.provider)
+            .field("database_path", &self.database_path)
+            .finish()
+    }
 }
 
 impl NostrMlsStorage {
     pub async fn new(backend: StorageBackend) -> Result<Self, crate::errors::DialogError> {
         match backend {
-            StorageBackend::Memory => {
-                let storage = NostrMlsMemoryStorage::default();
-                let nostr_mls = NostrMls::new(storage);
-                Ok(NostrMlsStorage::Memory(Arc::new(RwLock::new(nostr_mls))))
-            }
+            StorageBackend::Memory => Ok(Self::from_provider(NostrMlsMemoryStorage::default())),
             StorageBackend::Sqlite { path } => {
                 // Ensure parent directory exists
                 if let Some(parent) = path.parent() {
                     std::fs::create_dir_all(parent)?;
                 }
-                let storage = NostrMlsSqliteStorage::new(path)
+                let storage = NostrMlsSqliteStorage::new(path.clone())
                     .map_err(|e| crate::errors::DialogError::Storage(e.to_string()))?;
-                let nostr_mls = NostrMls::new(storage);
-                Ok(NostrMlsStorage::Sqlite(Arc::new(tokio::sync::Mutex::new(nostr_mls))))
+                Ok(Self {
+                    database_path: Some(path),
+                    ..Self::from_provider(storage)
+                })
             }
         }
     }
 
-    // Delegate all NostrMls methods
-    pub async fn get_groups(&self) -> Result<Vec<group_types::Group>, nostr_mls::Error> {
-        delegate_nostr_mls!(self, get_groups,)
+    /// MLS state kept by a custom storage provider (e.g. an encrypted or remote-backed store)
+    pub fn from_provider<S>(storage: S) -> Self
+    where
+        S: NostrMlsStorageProvider + Send + 'static,
+    {
+        Self {
+            mls: Box::new(NostrMls::new(storage)),
+            provider: std::any::type_name::<S>(),
+            database_path: None,
+        }
     }
 
-    pub async fn create_key_package_for_event(
+    /// The database file of the built-in SQLite backend, which backups copy
+    pub fn database_path(&self) -> Option<&Path> {
+        self.database_path.as_deref()
+    }
+
+    pub fn get_groups(&self) -> Result<Vec<group_types::Group>, nostr_mls::Error> {
+        self.mls.get_groups()
+    }
+
+    pub fn create_key_package_for_event(
         &self,
         pubkey: &PublicKey,
         relays: impl IntoIterator<Item = RelayUrl>,
     ) -> Result<(String, Vec<Tag>), nostr_mls::Error> {
-        match self {
-            NostrMlsStorage::Memory(mls) => {
-                let mls = mls.read().await;
-                let (content, tags) = mls.create_key_package_for_event(pubkey, relays)?;
-                Ok((content, tags.to_vec()))
-            },
-            NostrMlsStorage::Sqlite(mls) => {
-                let mls = mls.lock().await;
-                let (content, tags) = mls.create_key_package_for_event(pubkey, relays)?;
-                Ok((content, tags.to_vec()))
-            },
-        }
+        self.mls.create_key_package_for_event(pubkey, relays.into_iter().collect())
     }
 
-    pub async fn parse_key_package(&self, event: &Event) -> Result<KeyPackage, nostr_mls::Error> {
-        delegate_nostr_mls!(self, parse_key_package, event)
+    pub fn parse_key_package(&self, event: &Event) -> Result<KeyPackage, nostr_mls::Error> {
+        self.mls.parse_key_package(event)
     }
 
-    pub async fn create_group(
+    pub fn create_group(
         &self,
         pubkey: &PublicKey,
         key_packages: Vec<Event>,
         admins: Vec<PublicKey>,
         config: NostrGroupConfigData,
     ) -> Result<GroupResult, nostr_mls::Error> {
-        delegate_nostr_mls!(self, create_group, pubkey, key_packages, admins, config)
+        self.mls.create_group(pubkey, key_packages, admins, config)
     }
 
-    pub async fn process_message(&self, event: &Event) -> Result<MessageProcessingResult, nostr_mls::Error> {
-        delegate_nostr_mls!(self, process_message, event)
+    pub fn process_message(&self, event: &Event) -> Result<MessageProcessingResult, nostr_mls::Error> {
+        self.mls.process_message(event)
     }
 
-    pub async fn process_welcome(&self, gift_wrap_id: &EventId, rumor: &UnsignedEvent) -> Result<(), nostr_mls::Error> {
-        match self {
-            NostrMlsStorage::Memory(mls) => {
-                let mls = mls.read().await;
-                mls.process_welcome(gift_wrap_id, rumor).map(|_| ())
-            },
-            NostrMlsStorage::Sqlite(mls) => {
-                let mls = mls.lock().await;
-                mls.process_welcome(gift_wrap_id, rumor).map(|_| ())
-            },
-        }
+    pub fn process_welcome(&self, gift_wrap_id: &EventId, rumor: &UnsignedEvent) -> Result<welcome_types::Welcome, nostr_mls::Error> {
+        self.mls.process_welcome(gift_wrap_id, rumor)
     }
 
-    pub async fn get_pending_welcomes(&self) -> Result<Vec<welcome_types::Welcome>, nostr_mls::Error> {
-        delegate_nostr_mls!(self, get_pending_welcomes,)
+    pub fn get_pending_welcomes(&self) -> Result<Vec<welcome_types::Welcome>, nostr_mls::Error> {
+        self.mls.get_pending_welcomes()
     }
 
-    pub async fn accept_welcome(&self, welcome: &welcome_types::Welcome) -> Result<(), nostr_mls::Error> {
-        delegate_nostr_mls!(self, accept_welcome, welcome)
+    pub fn accept_welcome(&self, welcome: &welcome_types::Welcome) -> Result<(), nostr_mls::Error> {
+        self.mls.accept_welcome(welcome)
     }
 
-    pub async fn create_message(&self, group_id: &GroupId, rumor: UnsignedEvent) -> Result<Event, nostr_mls::Error> {
-        delegate_nostr_mls!(self, create_message, group_id, rumor)
+    pub fn create_message(&self, group_id: &GroupId, rumor: UnsignedEvent) -> Result<Event, nostr_mls::Error> {
+        self.mls.create_message(group_id, rumor)
     }
 
-    pub async fn get_messages(&self, group_id: &GroupId) -> Result<Vec<message_types::Message>, nostr_mls::Error> {
-        delegate_nostr_mls!(self, get_messages, group_id)
+    pub fn get_messages(&self, group_id: &GroupId) -> Result<Vec<message_types::Message>, nostr_mls::Error> {
+        self.mls.get_messages(group_id)
     }
 
-    pub async fn get_members(&self, group_id: &GroupId) -> Result<std::collections::BTreeSet<PublicKey>, nostr_mls::Error> {
-        delegate_nostr_mls!(self, get_members, group_id)
+    pub fn get_relays(&self, group_id: &GroupId) -> Result<BTreeSet<RelayUrl>, nostr_mls::Error> {
+        self.mls.get_relays(group_id)
     }
 
-    pub async fn add_members(&self, group_id: &GroupId, key_packages: Vec<Event>) -> Result<UpdateGroupResult, nostr_mls::Error> {
-        delegate_nostr_mls!(self, add_members, group_id, &key_packages)
+    pub fn get_members(&self, group_id: &GroupId) -> Result<BTreeSet<PublicKey>, nostr_mls::Error> {
+        self.mls.get_members(group_id)
     }
 
-    pub async fn remove_members(&self, group_id: &GroupId, members: Vec<PublicKey>) -> Result<UpdateGroupResult, nostr_mls::Error> {
-        delegate_nostr_mls!(self, remove_members, group_id, &members)
+    /// The group's exporter secret for the current epoch
+    pub fn exporter_secret(&self, group_id: &GroupId) -> Result<group_types::GroupExporterSecret, nostr_mls::Error> {
+        self.mls.exporter_secret(group_id)
     }
-}
\ No newline at end of file
+
+    pub fn add_members(&self, group_id: &GroupId, key_packages: Vec<Event>) -> Result<UpdateGroupResult, nostr_mls::Error> {
+        self.mls.add_members(group_id, &key_packages)
+    }
+
+    pub fn remove_members(&self, group_id: &GroupId, members: Vec<PublicKey>) -> Result<UpdateGroupResult, nostr_mls::Error> {
+        self.mls.remove_members(group_id, &members)
+    }
+
+    /// Commit a change to the group data (name, pinned messages, ...)
+    pub fn update_group_data(&self, group_id: &GroupId, update: NostrGroupDataUpdate) -> Result<UpdateGroupResult, nostr_mls::Error> {
+        self.mls.update_group_data(group_id, update)
+    }
+
+    /// Apply our own commit once it has been published
+    pub fn merge_pending_commit(&self, group_id: &GroupId) -> Result<(), nostr_mls::Error> {
+        self.mls.merge_pending_commit(group_id)
+    }
+}
@@ -0,0 +1,347 @@
+//! Storage actor: a single thread owns the MLS storage and runs requests from
+//! a queue, one at a time.
+//!
+//! OpenMLS and SQLite calls are synchronous and can take a while (a commit, a
+//! large fetch), so they run on that dedicated thread instead of a runtime
+//! worker; async callers only wait for the answer.
+//!
+//! Callers hold a cheap, cloneable [`MlsStore`] handle instead of a lock, so a
+//! slow relay fetch in one place never keeps storage from another (e.g. the
+//! real-time loop while the TUI loads a conversation). The actor also keeps a
+//! cache of the stored groups indexed by Nostr group ID, rebuilt after any
+//! request that changes groups (commits, welcomes, new groups); plain messages
+//! leave it alone. A request that panics fails on its own without taking the
+//! actor down. [`MlsStore::close`] stops the actor for every handle at once.
+
+use crate::errors::{DialogError, Result};
+use crate::storage::NostrMlsStorage;
+use nostr_mls::groups::{GroupResult, NostrGroupDataUpdate, UpdateGroupResult};
+use nostr_mls::messages::MessageProcessingResult;
+use nostr_mls::prelude::*;
+use nostr_mls_storage::groups::types as group_types;
+use nostr_mls_storage::messages::types as message_types;
+use nostr_mls_storage::welcomes::types as welcome_types;
+use openmls::prelude::KeyPackage;
+use std::collections::{BTreeSet, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use tokio::sync::{mpsc, oneshot};
+
+type Job = Box<dyn FnOnce(&mut StoreState) + Send>;
+
+enum Request {
+    Run(Job),
+    /// Stop taking requests and drop the storage, then answer
+    Close(oneshot::Sender<()>),
+}
+
+/// What the actor owns
+struct StoreState {
+    storage: NostrMlsStorage,
+    /// Stored groups in storage order, `None` until loaded or after a change.
+    /// Message bookkeeping on the groups (last message) may lag behind.
+    groups: Option<GroupIndex>,
+}
+
+struct GroupIndex {
+    groups: Vec<group_types::Group>,
+    by_nostr_id: HashMap<Vec<u8>, usize>,
+}
+
+impl GroupIndex {
+    fn new(groups: Vec<group_types::Group>) -> Self {
+        let by_nostr_id = groups
+            .iter()
+            .enumerate()
+            .map(|(i, g)| (g.nostr_group_id.as_slice().to_vec(), i))
+            .collect();
+        Self { groups, by_nostr_id }
+    }
+}
+
+impl StoreState {
+    fn index(&mut self) -> std::result::Result<&GroupIndex, nostr_mls::Error> {
+        if self.groups.is_none() {
+            self.groups = Some(GroupIndex::new(self.storage.get_groups()?));
+        }
+        Ok(self.groups.as_ref().expect("group index was just loaded"))
+    }
+}
+
+/// Handle to the storage actor. Clones share the same actor, which stops once
+/// every handle is dropped or one of them closes it.
+#[derive(Debug, Clone)]
+pub struct MlsStore {
+    jobs: mpsc::UnboundedSender<Request>,
+}
+
+impl MlsStore {
+    /// Start the actor thread owning `storage`
+    pub fn spawn(storage: NostrMlsStorage) -> Result<Self> {
+        let (jobs, mut queue) = mpsc::unbounded_channel::<Request>();
+        let actor = move || {
+            let mut state = StoreState { storage, groups: None };
+            while let Some(request) = queue.blocking_recv() {
+                match request {
+                    Request::Run(job) => {
+                        // The storage may be half way through a change; reload groups
+                        if panic::catch_unwind(AssertUnwindSafe(|| job(&mut state))).is_err() {
+                            state.groups = None;
+                        }
+                    }
+                    Request::Close(closed) => {
+                        // Requests still queued are dropped, so their callers fail
+                        queue.close();
+                        drop(state);
+                        let _ = closed.send(());
+                        return;
+                    }
+                }
+            }
+        };
+        std::thread::Builder::new()
+            .name("dialog-mls-store".into())
+            .spawn(actor)
+            .map_err(|e| DialogError::Storage(format!("Failed to start the storage thread: {}", e)))?;
+        Ok(Self { jobs })
+    }
+
+    /// Stop the actor once the requests before this one are done, and wait
+    /// until the storage is released. Later requests fail right away.
+    pub async fn close(&self) {
+        let (closed, done) = oneshot::channel();
+        if self.jobs.send(Request::Close(closed)).is_ok() {
+            let _ = done.await;
+        }
+    }
+
+    /// Queue a request and wait for its answer
+    async fn call<R, F>(&self, request: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut StoreState) -> R + Send + 'static,
+    {
+        let (reply, answer) = oneshot::channel();
+        let job: Job = Box::new(move |state| {
+            let _ = reply.send(request(state));
+        });
+        self.jobs.send(Request::Run(job)).map_err(|_| stopped())?;
+        answer.await.map_err(|_| {
+            if self.jobs.is_closed() {
+                stopped()
+            } else {
+                DialogError::Storage("Storage request panicked".into())
+            }
+        })
+    }
+
+    /// A request that leaves the stored groups as they are
+    async fn run<T, F>(&self, request: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&NostrMlsStorage) -> std::result::Result<T, nostr_mls::Error> + Send + 'static,
+    {
+        Ok(self.call(move |state| request(&state.storage)).await??)
+    }
+
+    /// A request that may add, remove or change groups; the index is rebuilt afterwards
+    async fn run_updating_groups<T, F>(&self, request: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&NostrMlsStorage) -> std::result::Result<T, nostr_mls::Error> + Send + 'static,
+    {
+        self.run_updating_groups_if(request, |_| true).await
+    }
+
+    /// A request whose result tells whether groups changed; the index is
+    /// rebuilt when `changed` says so
+    async fn run_updating_groups_if<T, F>(&self, request: F, changed: fn(&T) -> bool) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&NostrMlsStorage) -> std::result::Result<T, nostr_mls::Error> + Send + 'static,
+    {
+        let result = self
+            .call(move |state| {
+                let result = request(&state.storage);
+                if result.as_ref().is_ok_and(changed) {
+                    state.groups = None;
+                }
+                result
+            })
+            .await?;
+        Ok(result?)
+    }
+
+    pub async fn get_groups(&self) -> Result<Vec<group_types::Group>> {
+        let groups = self
+            .call(|state| state.index().map(|index| index.groups.clone()))
+            .await?;
+        Ok(groups?)
+    }
+
+    /// Look up a stored group by its Nostr group ID (the `h` tag of group messages)
+    pub async fn group_by_nostr_id(&self, nostr_group_id: &[u8]) -> Result<Option<group_types::Group>> {
+        let key = nostr_group_id.to_vec();
+        let group = self
+            .call(move |state| {
+                state
+                    .index()
+                    .map(|index| index.by_nostr_id.get(&key).map(|&i| index.groups[i].clone()))
+            })
+            .await?;
+        Ok(group?)
+    }
+
+    /// Look up a stored group by its MLS group ID
+    pub async fn group(&self, group_id: &GroupId) -> Result<Option<group_types::Group>> {
+        Ok(self
+            .get_groups()
+            .await?
+            .into_iter()
+            .find(|g| &g.mls_group_id == group_id))
+    }
+
+    pub async fn create_key_package_for_event(
+        &self,
+        pubkey: &PublicKey,
+        relays: Vec<RelayUrl>,
+    ) -> Result<(String, Vec<Tag>)> {
+        let pubkey = *pubkey;
+        self.run(move |s| s.create_key_package_for_event(&pubkey, relays))
+            .await
+    }
+
+    pub async fn parse_key_package(&self, event: &Event) -> Result<KeyPackage> {
+        let event = event.clone();
+        self.run(move |s| s.parse_key_package(&event)).await
+    }
+
+    pub async fn create_group(
+        &self,
+        pubkey: &PublicKey,
+        key_packages: Vec<Event>,
+        admins: Vec<PublicKey>,
+        config: NostrGroupConfigData,
+    ) -> Result<GroupResult> {
+        let pubkey = *pubkey;
+        self.run_updating_groups(move |s| s.create_group(&pubkey, key_packages, admins, config))
+            .await
+    }
+
+    /// Process a group event; only commits (and proposals we commit) change the group
+    pub async fn process_message(&self, event: &Event) -> Result<MessageProcessingResult> {
+        let event = event.clone();
+        self.run_updating_groups_if(
+            move |s| s.process_message(&event),
+            |result| matches!(result, MessageProcessingResult::Commit { .. } | MessageProcessingResult::Proposal { .. }),
+        )
+        .await
+    }
+
+    pub async fn process_welcome(&self, gift_wrap_id: &EventId, rumor: &UnsignedEvent) -> Result<welcome_types::Welcome> {
+        let (gift_wrap_id, rumor) = (*gift_wrap_id, rumor.clone());
+        self.run_updating_groups(move |s| s.process_welcome(&gift_wrap_id, &rumor))
+            .await
+    }
+
+    pub async fn get_pending_welcomes(&self) -> Result<Vec<welcome_types::Welcome>> {
+        self.run(|s| s.get_pending_welcomes()).await
+    }
+
+    pub async fn accept_welcome(&self, welcome: &welcome_types::Welcome) -> Result<()> {
+        let welcome = welcome.clone();
+        self.run_updating_groups(move |s| s.accept_welcome(&welcome))
+            .await
+    }
+
+    pub async fn create_message(&self, group_id: &GroupId, rumor: UnsignedEvent) -> Result<Event> {
+        let group_id = group_id.clone();
+        self.run(move |s| s.create_message(&group_id, rumor)).await
+    }
+
+    pub async fn get_messages(&self, group_id: &GroupId) -> Result<Vec<message_types::Message>> {
+        let group_id = group_id.clone();
+        self.run(move |s| s.get_messages(&group_id)).await
+    }
+
+    pub async fn get_members(&self, group_id: &GroupId) -> Result<BTreeSet<PublicKey>> {
+        let group_id = group_id.clone();
+        self.run(move |s| s.get_members(&group_id)).await
+    }
+
+    pub async fn exporter_secret(&self, group_id: &GroupId) -> Result<group_types::GroupExporterSecret> {
+        let group_id = group_id.clone();
+        self.run(move |s| s.exporter_secret(&group_id)).await
+    }
+
+    pub async fn get_relays(&self, group_id: &GroupId) -> Result<BTreeSet<RelayUrl>> {
+        let group_id = group_id.clone();
+        self.run(move |s| s.get_relays(&group_id)).await
+    }
+
+    pub async fn add_members(&self, group_id: &GroupId, key_packages: Vec<Event>) -> Result<UpdateGroupResult> {
+        let group_id = group_id.clone();
+        self.run_updating_groups(move |s| s.add_members(&group_id, key_packages))
+            .await
+    }
+
+    pub async fn remove_members(&self, group_id: &GroupId, members: Vec<PublicKey>) -> Result<UpdateGroupResult> {
+        let group_id = group_id.clone();
+        self.run_updating_groups(move |s| s.remove_members(&group_id, members))
+            .await
+    }
+
+    /// Commit a change to the group data (name, pinned messages, ...)
+    pub async fn update_group_data(&self, group_id: &GroupId, update: NostrGroupDataUpdate) -> Result<UpdateGroupResult> {
+        let group_id = group_id.clone();
+        self.run_updating_groups(move |s| s.update_group_data(&group_id, update))
+            .await
+    }
+
+    /// Apply our own commit once it has been published
+    pub async fn merge_pending_commit(&self, group_id: &GroupId) -> Result<()> {
+        let group_id = group_id.clone();
+        self.run_updating_groups(move |s| s.merge_pending_commit(&group_id))
+            .await
+    }
+}
+
+fn stopped() -> DialogError {
+    DialogError::Storage("Storage actor has stopped".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageBackend;
+
+    #[tokio::test]
+    async fn test_requests_are_served_in_order() {
+        let store = MlsStore::spawn(NostrMlsStorage::new(StorageBackend::Memory).await.unwrap()).unwrap();
+        assert!(store.get_groups().await.unwrap().is_empty());
+        assert!(store.group_by_nostr_id(&[0u8; 32]).await.unwrap().is_none());
+
+        let keys = Keys::generate();
+        let relay = RelayUrl::parse("wss://relay.example").unwrap();
+        let (content, tags) = store
+            .create_key_package_for_event(&keys.public_key(), vec![relay])
+            .await
+            .unwrap();
+        let event = EventBuilder::new(Kind::MlsKeyPackage, content)
+            .tags(tags)
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert!(store.parse_key_package(&event).await.is_ok());
+
+        // A panicking request fails alone; the actor keeps serving
+        assert!(store.call(|_| -> u8 { panic!("broken request") }).await.is_err());
+        assert!(store.get_groups().await.unwrap().is_empty());
+
+        // Handles share the actor
+        let clone = store.clone();
+
+        // Closing through one handle stops the actor for all of them
+        clone.close().await;
+        assert!(store.get_groups().await.is_err());
+        store.close().await;
+    }
+}
@@ -0,0 +1,55 @@
+//! Lifecycle of the real-time subscription task.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Handle to the task delivering real-time group events.
+///
+/// Dropping the handle leaves the task running; call [`SubscriptionHandle::stop`]
+/// (or shut the library down) to end it. Clones control the same task.
+#[derive(Debug, Clone)]
+pub struct SubscriptionHandle {
+    stop: Arc<watch::Sender<bool>>,
+}
+
+impl SubscriptionHandle {
+    /// A new handle and the receiver the task watches for cancellation
+    pub(crate) fn new() -> (Self, watch::Receiver<bool>) {
+        let (stop, stopped) = watch::channel(false);
+        (Self { stop: Arc::new(stop) }, stopped)
+    }
+
+    /// Ask the task to stop without waiting for it
+    pub fn cancel(&self) {
+        self.stop.send_replace(true);
+    }
+
+    /// Stop the task and wait until it has unsubscribed and exited
+    pub async fn stop(&self) {
+        self.cancel();
+        self.stop.closed().await;
+    }
+
+    /// Whether the task is still delivering events
+    pub fn is_running(&self) -> bool {
+        !*self.stop.borrow() && !self.stop.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_waits_for_the_task() {
+        let (handle, mut stopped) = SubscriptionHandle::new();
+        let task = tokio::spawn(async move {
+            let _ = stopped.wait_for(|stop| *stop).await;
+        });
+        assert!(handle.is_running());
+
+        handle.clone().stop().await;
+        assert!(!handle.is_running());
+        task.await.unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Membership and metadata changes per group, kept as system entries for the
+//! conversation timeline.
+//!
+//! MLS commits carry no human-readable description, so changes are derived by
+//! comparing a [`GroupSnapshot`] taken before and after processing an event.
+
+use crate::errors::Result;
+use crate::json_store::JsonStore;
+use crate::storage::StorageBackend;
+use crate::store::MlsStore;
+use crate::types::{SystemEvent, SystemEventKind};
+use nostr_mls::prelude::*;
+use nostr_mls_storage::groups::types as group_types;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Group state that system events are derived from
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupSnapshot {
+    pub epoch: u64,
+    pub name: String,
+    pub description: String,
+    pub admins: BTreeSet<PublicKey>,
+    pub members: BTreeSet<PublicKey>,
+    /// Pinned message IDs from the group data, most recently pinned first
+    pub pinned: Vec<EventId>,
+}
+
+impl GroupSnapshot {
+    pub fn new(group: &group_types::Group, members: BTreeSet<PublicKey>) -> Self {
+        Self {
+            epoch: group.epoch,
+            name: group.name.clone(),
+            description: group.description.clone(),
+            admins: group.admin_pubkeys.clone(),
+            members,
+            pinned: group.pinned.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Capture the current state of a stored group
+    pub async fn capture(store: &MlsStore, group: &group_types::Group) -> Result<Self> {
+        let members = store.get_members(&group.mls_group_id).await?;
+        Ok(Self::new(group, members))
+    }
+
+    /// System events describing how `after` differs from this snapshot
+    pub fn diff(&self, after: &GroupSnapshot, actor: Option<PublicKey>, timestamp: i64) -> Vec<SystemEvent> {
+        let mut kinds = Vec::new();
+
+        let added: Vec<PublicKey> = after.members.difference(&self.members).copied().collect();
+        if !added.is_empty() {
+            kinds.push(SystemEventKind::MembersAdded { actor, members: added });
+        }
+        let removed: Vec<PublicKey> = self.members.difference(&after.members).copied().collect();
+        if !removed.is_empty() {
+            kinds.push(SystemEventKind::MembersRemoved { actor, members: removed });
+        }
+        if after.name != self.name {
+            kinds.push(SystemEventKind::Renamed { name: after.name.clone() });
+        }
+        if after.description != self.description {
+            kinds.push(SystemEventKind::DescriptionChanged { description: after.description.clone() });
+        }
+        if after.admins != self.admins {
+            kinds.push(SystemEventKind::AdminsChanged { admins: after.admins.iter().copied().collect() });
+        }
+        if after.pinned != self.pinned {
+            kinds.push(SystemEventKind::PinsChanged);
+        }
+        if kinds.is_empty() && after.epoch != self.epoch {
+            kinds.push(SystemEventKind::EpochAdvanced);
+        }
+
+        kinds
+            .into_iter()
+            .map(|kind| SystemEvent { timestamp, epoch: after.epoch, kind })
+            .collect()
+    }
+}
+
+/// Persistent log of system events, keyed by hex MLS group ID
+#[derive(Debug, Default)]
+pub struct SystemEventLog {
+    store: JsonStore<BTreeMap<String, Vec<SystemEvent>>>,
+}
+
+impl SystemEventLog {
+    /// Open the log belonging to a storage backend
+    pub fn for_backend(backend: &StorageBackend) -> Result<Self> {
+        Ok(Self { store: JsonStore::for_backend(backend, "events.json")? })
+    }
+
+    /// Append events for a group
+    pub fn record(&mut self, group_id: &GroupId, events: Vec<SystemEvent>) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        self.store
+            .get_mut()
+            .entry(hex::encode(group_id.as_slice()))
+            .or_default()
+            .extend(events);
+        self.store.save()
+    }
+
+    /// All events recorded for a group, oldest first
+    pub fn events(&self, group_id: &GroupId) -> Vec<SystemEvent> {
+        self.store
+            .get()
+            .get(&hex::encode(group_id.as_slice()))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(epoch: u64, name: &str, members: &[PublicKey]) -> GroupSnapshot {
+        GroupSnapshot {
+            epoch,
+            name: name.to_string(),
+            description: String::new(),
+            admins: BTreeSet::new(),
+            members: members.iter().copied().collect(),
+            pinned: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_members_and_name() {
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+        let carol = Keys::generate().public_key();
+
+        let before = snapshot(3, "Team", &[alice, bob]);
+        let after = snapshot(4, "Team 2", &[alice, carol]);
+        let events = before.diff(&after, Some(alice), 100);
+
+        let kinds: Vec<SystemEventKind> = events.iter().map(|e| e.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            SystemEventKind::MembersAdded { actor: Some(alice), members: vec![carol] },
+            SystemEventKind::MembersRemoved { actor: Some(alice), members: vec![bob] },
+            SystemEventKind::Renamed { name: "Team 2".to_string() },
+        ]);
+        assert!(events.iter().all(|e| e.epoch == 4 && e.timestamp == 100));
+    }
+
+    #[test]
+    fn test_diff_epoch_only() {
+        let alice = Keys::generate().public_key();
+        let before = snapshot(6, "Team", &[alice]);
+        let after = snapshot(7, "Team", &[alice]);
+
+        let events = before.diff(&after, None, 0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, SystemEventKind::EpochAdvanced);
+        assert_eq!(events[0].describe(|pk| pk.to_hex()), "epoch 7");
+
+        assert!(before.diff(&before, None, 0).is_empty());
+    }
+
+    #[test]
+    fn test_diff_pins() {
+        let alice = Keys::generate().public_key();
+        let before = snapshot(2, "Team", &[alice]);
+        let mut after = snapshot(3, "Team", &[alice]);
+        after.pinned = vec![EventId::all_zeros()];
+
+        let kinds: Vec<SystemEventKind> = before.diff(&after, None, 0).into_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![SystemEventKind::PinsChanged]);
+    }
+
+    #[test]
+    fn test_log_records_per_group() {
+        let group_a = GroupId::from_slice(&[1u8; 16]);
+        let group_b = GroupId::from_slice(&[2u8; 16]);
+        let mut log = SystemEventLog::default();
+
+        log.record(&group_a, vec![SystemEvent { timestamp: 1, epoch: 1, kind: SystemEventKind::Joined }])
+            .unwrap();
+        assert_eq!(log.events(&group_a).len(), 1);
+        assert!(log.events(&group_b).is_empty());
+    }
+}
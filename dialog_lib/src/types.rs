@@ -1,3 +1,4 @@
+use crate::invite_policy::InviteDecision;
 use nostr_mls::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -25,11 +26,18 @@ impl ConnectionStatus {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contact {
     pub name: String,
     pub pubkey: PublicKey,
+    /// Seen within the presence window (see [`crate::presence`])
     pub online: bool,
+    /// Latest observed activity (Unix timestamp in seconds)
+    #[serde(default)]
+    pub last_seen: Option<i64>,
+    /// NIP-38 general status, if the contact published one
+    #[serde(default)]
+    pub status: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -114,6 +122,64 @@ pub enum AppResult {
     Exit,
 }
 
+/// How welcomes are delivered to members added to a group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WelcomeTransport {
+    /// NIP-59 gift wrap only; nobody watching the relay learns who invited whom
+    #[default]
+    GiftWrapOnly,
+    /// Gift wrap plus a plain kind 444 event, for clients that only read plain welcomes
+    Both,
+    /// Plain kind 444 event only (legacy); the invite is public
+    PlainOnly,
+}
+
+impl WelcomeTransport {
+    /// Whether a gift-wrapped welcome is sent
+    pub fn gift_wrap(self) -> bool {
+        matches!(self, WelcomeTransport::GiftWrapOnly | WelcomeTransport::Both)
+    }
+
+    /// Whether a plain kind 444 welcome is sent
+    pub fn plain(self) -> bool {
+        matches!(self, WelcomeTransport::Both | WelcomeTransport::PlainOnly)
+    }
+}
+
+/// Why a participant was left out of a new group
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    /// No key package from them on the relay
+    NoKeyPackage,
+    /// Their key package could not be used
+    InvalidKeyPackage(String),
+    /// Looking up their key package failed
+    FetchFailed(String),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::NoKeyPackage => write!(f, "No key package found"),
+            SkipReason::InvalidKeyPackage(e) => write!(f, "Invalid key package ({})", e),
+            SkipReason::FetchFailed(e) => write!(f, "Failed to fetch key packages ({})", e),
+        }
+    }
+}
+
+/// Outcome of creating a group, or adding members to one, with everyone reachable
+#[derive(Debug, Clone)]
+pub struct GroupCreationReport {
+    /// MLS group ID (hex) of the group
+    pub group_id: String,
+    /// Members added to the group
+    pub added: Vec<PublicKey>,
+    /// Participants left out, and why; they can be invited again later
+    pub skipped: Vec<(PublicKey, SkipReason)>,
+    /// Members added whose welcome could not be published, and the error
+    pub undelivered: Vec<(PublicKey, String)>,
+}
+
 /// Pending group invitation
 #[derive(Debug, Clone)]
 pub struct PendingInvite {
@@ -129,12 +195,267 @@ pub struct PendingInvite {
 pub struct Message {
     /// The sender's public key
     pub sender: PublicKey,
-    /// The message content
+    /// Raw content of the inner rumor
     pub content: String,
+    /// Typed content parsed from the rumor kind and tags
+    pub body: MessageBody,
     /// Timestamp when the message was sent (Unix timestamp in seconds)
     pub timestamp: i64,
     /// Message ID (event ID)
     pub id: Option<String>,
+    /// Delivery state for messages we sent; `None` for received messages
+    pub delivery: Option<DeliveryInfo>,
+    /// Members (other than the sender) whose read receipts cover this message
+    pub seen_by: Vec<PublicKey>,
+}
+
+/// NIP-42 authentication state with a relay
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthStatus {
+    /// AUTH sent, waiting for the relay's answer
+    Pending,
+    Authenticated,
+    /// The relay rejected our AUTH; its reason
+    Failed(String),
+}
+
+/// Typed content of a group message, parsed from the inner rumor.
+///
+/// Frontends should render this rather than `Message::content`; kinds we do not
+/// understand (e.g. from other MLS clients) become [`MessageBody::Unknown`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MessageBody {
+    Text { text: String },
+    Reaction { target: EventId, emoji: String },
+    Reply { in_reply_to: EventId, text: String },
+    Edit { target: EventId, text: String },
+    Deletion { targets: Vec<EventId>, reason: Option<String> },
+    Attachment { url: String, mime_type: Option<String>, caption: Option<String> },
+    System { text: String },
+    Unknown { kind: u16, content: String },
+}
+
+impl MessageBody {
+    /// Parse a rumor's kind, content and tags
+    pub fn parse(kind: Kind, content: &str, tags: &Tags) -> Self {
+        let text = content.to_string();
+        match kind.as_u16() {
+            nostr_kinds::TEXT_NOTE | nostr_kinds::CHAT_MESSAGE => {
+                if let Some((url, mime_type)) = imeta_attachment(tags) {
+                    let caption = content.trim();
+                    let caption = (!caption.is_empty() && caption != url).then(|| caption.to_string());
+                    return MessageBody::Attachment { url, mime_type, caption };
+                }
+                match tag_event_id(tags, "q").or_else(|| tags.event_ids().next().copied()) {
+                    Some(in_reply_to) => MessageBody::Reply { in_reply_to, text },
+                    None => MessageBody::Text { text },
+                }
+            }
+            nostr_kinds::REACTION => match tags.event_ids().last() {
+                Some(target) => MessageBody::Reaction {
+                    target: *target,
+                    emoji: if content.is_empty() { "+".to_string() } else { text },
+                },
+                None => MessageBody::Unknown { kind: kind.as_u16(), content: text },
+            },
+            nostr_kinds::DELETION => MessageBody::Deletion {
+                targets: tags.event_ids().copied().collect(),
+                reason: (!content.is_empty()).then_some(text),
+            },
+            nostr_kinds::EDIT => match tags.event_ids().next() {
+                Some(target) => MessageBody::Edit { target: *target, text },
+                None => MessageBody::Unknown { kind: kind.as_u16(), content: text },
+            },
+            nostr_kinds::FILE_METADATA => match tag_value(tags, "url") {
+                Some(url) => MessageBody::Attachment {
+                    url,
+                    mime_type: tag_value(tags, "m"),
+                    caption: (!content.is_empty()).then_some(text),
+                },
+                None => MessageBody::Unknown { kind: kind.as_u16(), content: text },
+            },
+            nostr_kinds::SYSTEM_NOTICE => MessageBody::System { text },
+            other => MessageBody::Unknown { kind: other, content: text },
+        }
+    }
+}
+
+impl std::fmt::Display for MessageBody {
+    /// One-line rendering for frontends without rich display
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageBody::Text { text } => write!(f, "{}", text),
+            MessageBody::Reaction { target, emoji } => write!(f, "reacted {} to {}", emoji, short_id(target)),
+            MessageBody::Reply { in_reply_to, text } => write!(f, "↪ {}: {}", short_id(in_reply_to), text),
+            MessageBody::Edit { target, text } => write!(f, "✎ {} (edit of {})", text, short_id(target)),
+            MessageBody::Deletion { targets, .. } => {
+                let ids: Vec<String> = targets.iter().map(short_id).collect();
+                write!(f, "deleted {}", ids.join(", "))
+            }
+            MessageBody::Attachment { url, caption, .. } => match caption {
+                Some(caption) => write!(f, "📎 {} {}", caption, url),
+                None => write!(f, "📎 {}", url),
+            },
+            MessageBody::System { text } => write!(f, "* {}", text),
+            MessageBody::Unknown { kind, content } if content.is_empty() => write!(f, "[unsupported message kind {}]", kind),
+            MessageBody::Unknown { kind, content } => write!(f, "[kind {}] {}", kind, content),
+        }
+    }
+}
+
+fn short_id(id: &EventId) -> String {
+    id.to_hex()[..8].to_string()
+}
+
+/// First value of a single-letter or named tag
+fn tag_value(tags: &Tags, name: &str) -> Option<String> {
+    tags.iter()
+        .map(|tag| tag.as_slice())
+        .find(|values| values.first().map(String::as_str) == Some(name))
+        .and_then(|values| values.get(1).cloned())
+}
+
+fn tag_event_id(tags: &Tags, name: &str) -> Option<EventId> {
+    tag_value(tags, name).and_then(|value| EventId::from_hex(&value).ok())
+}
+
+/// URL and MIME type from a NIP-92 `imeta` tag
+fn imeta_attachment(tags: &Tags) -> Option<(String, Option<String>)> {
+    let values = tags
+        .iter()
+        .map(|tag| tag.as_slice())
+        .find(|values| values.first().map(String::as_str) == Some("imeta"))?;
+    let field = |name: &str| {
+        values[1..]
+            .iter()
+            .find_map(|entry| entry.strip_prefix(name).and_then(|rest| rest.strip_prefix(' ')))
+            .map(str::to_string)
+    };
+    Some((field("url")?, field("m")))
+}
+
+/// Delivery state of an outgoing message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeliveryStatus {
+    /// Encrypted and queued in the outbox, not yet accepted by any relay
+    Pending,
+    /// Accepted by at least one relay
+    Sent,
+    /// Retries exhausted; the reason of the last failure
+    Failed(String),
+}
+
+/// Delivery details of an outgoing message, including per-relay acceptance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryInfo {
+    pub status: DeliveryStatus,
+    /// Number of publish attempts so far
+    pub attempts: u32,
+    /// Relays that accepted the event
+    pub accepted_by: Vec<String>,
+    /// Relays that rejected the event, with the reason they gave
+    pub rejected_by: Vec<(String, String)>,
+}
+
+impl DeliveryInfo {
+    pub fn pending() -> Self {
+        Self {
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            accepted_by: Vec::new(),
+            rejected_by: Vec::new(),
+        }
+    }
+}
+
+/// Machine-readable prefix of a relay `OK false` message (NIP-01, formerly NIP-20)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    Duplicate,
+    Pow,
+    Blocked,
+    RateLimited,
+    Invalid,
+    Restricted,
+    AuthRequired,
+    Error,
+    /// No recognised prefix (or a transport failure)
+    Other,
+}
+
+impl RejectReason {
+    /// Parse the prefix of a relay message such as `rate-limited: slow down`
+    pub fn from_message(message: &str) -> Self {
+        let prefix = message.split_once(':').map(|(prefix, _)| prefix.trim()).unwrap_or("");
+        match prefix {
+            "duplicate" => Self::Duplicate,
+            "pow" => Self::Pow,
+            "blocked" => Self::Blocked,
+            "rate-limited" => Self::RateLimited,
+            "invalid" => Self::Invalid,
+            "restricted" => Self::Restricted,
+            "auth-required" => Self::AuthRequired,
+            "error" => Self::Error,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether publishing the same event again may succeed later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited | Self::AuthRequired | Self::Error | Self::Other)
+    }
+}
+
+/// How a single relay answered a published event
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelayAck {
+    /// `OK true`
+    Accepted,
+    /// `OK false` (or no answer), with the relay's message
+    Rejected { reason: RejectReason, message: String },
+}
+
+/// Per-relay outcome of a publish
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayOutcome {
+    pub relay_url: String,
+    pub ack: RelayAck,
+}
+
+/// Report returned by `send_message`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendReport {
+    /// ID of the inner message (matches `Message::id`)
+    pub message_id: String,
+    /// ID of the published group message event
+    pub event_id: String,
+    /// Answers of the relays contacted in this attempt; empty when queued offline
+    pub relays: Vec<RelayOutcome>,
+    /// Delivery state after this attempt
+    pub status: DeliveryStatus,
+}
+
+impl SendReport {
+    /// Whether at least one relay accepted the message
+    pub fn is_sent(&self) -> bool {
+        self.status == DeliveryStatus::Sent
+    }
+
+    /// Relays that accepted the message
+    pub fn accepted(&self) -> impl Iterator<Item = &str> {
+        self.relays
+            .iter()
+            .filter(|r| r.ack == RelayAck::Accepted)
+            .map(|r| r.relay_url.as_str())
+    }
+
+    /// Relays that rejected the message, with their reasons
+    pub fn rejected(&self) -> impl Iterator<Item = (&str, RejectReason, &str)> {
+        self.relays.iter().filter_map(|r| match &r.ack {
+            RelayAck::Rejected { reason, message } => Some((r.relay_url.as_str(), *reason, message.as_str())),
+            RelayAck::Accepted => None,
+        })
+    }
 }
 
 /// Result of listing pending invites, includes both invites and any processing errors
@@ -144,16 +465,112 @@ pub struct InviteListResult {
     pub processing_errors: Vec<String>,
 }
 
+/// A change to group membership or metadata
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SystemEventKind {
+    /// Group created (by us)
+    Created { name: String },
+    /// We joined the group by accepting an invite
+    Joined,
+    /// Members were added. `actor` is set only for changes we made: the
+    /// processing result of someone else's commit does not name its sender.
+    MembersAdded { actor: Option<PublicKey>, members: Vec<PublicKey> },
+    /// Members were removed or left; `actor` as for [`SystemEventKind::MembersAdded`]
+    MembersRemoved { actor: Option<PublicKey>, members: Vec<PublicKey> },
+    Renamed { name: String },
+    DescriptionChanged { description: String },
+    AdminsChanged { admins: Vec<PublicKey> },
+    /// Admins pinned or unpinned messages
+    PinsChanged,
+    /// A commit without visible changes (e.g. key rotation)
+    EpochAdvanced,
+}
+
+/// A system entry in a conversation timeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemEvent {
+    /// When the change was processed (Unix timestamp in seconds)
+    pub timestamp: i64,
+    /// MLS epoch after the change
+    pub epoch: u64,
+    pub kind: SystemEventKind,
+}
+
+impl SystemEvent {
+    /// Human-readable description, resolving pubkeys with `name_of`
+    pub fn describe(&self, name_of: impl Fn(&PublicKey) -> String) -> String {
+        let names = |pubkeys: &[PublicKey]| pubkeys.iter().map(&name_of).collect::<Vec<_>>().join(", ");
+        match &self.kind {
+            SystemEventKind::Created { name } => format!("group \"{}\" created", name),
+            SystemEventKind::Joined => "you joined the group".to_string(),
+            SystemEventKind::MembersAdded { actor: Some(actor), members } => {
+                format!("{} added {}", name_of(actor), names(members))
+            }
+            SystemEventKind::MembersAdded { actor: None, members } => format!("{} joined", names(members)),
+            SystemEventKind::MembersRemoved { actor: Some(actor), members } => {
+                format!("{} removed {}", name_of(actor), names(members))
+            }
+            SystemEventKind::MembersRemoved { actor: None, members } => {
+                // The commit does not tell whether they left or were removed
+                format!("{} left or was removed", names(members))
+            }
+            SystemEventKind::Renamed { name } => format!("group renamed to \"{}\"", name),
+            SystemEventKind::DescriptionChanged { description } => {
+                format!("description changed to \"{}\"", description)
+            }
+            SystemEventKind::AdminsChanged { admins } => format!("admins are now {}", names(admins)),
+            SystemEventKind::PinsChanged => "pinned messages changed".to_string(),
+            SystemEventKind::EpochAdvanced => format!("epoch {}", self.epoch),
+        }
+    }
+}
+
+/// One entry of a conversation timeline
+#[derive(Debug, Clone)]
+pub enum TimelineEntry {
+    Message(Message),
+    System(SystemEvent),
+}
+
+impl TimelineEntry {
+    pub fn timestamp(&self) -> i64 {
+        match self {
+            TimelineEntry::Message(message) => message.timestamp,
+            TimelineEntry::System(event) => event.timestamp,
+        }
+    }
+}
+
 /// Result of fetching messages, includes both messages and any processing errors
 #[derive(Debug, Clone)]
 pub struct MessageFetchResult {
     pub messages: Vec<Message>,
+    /// Messages and system events interleaved by time (oldest first)
+    pub timeline: Vec<TimelineEntry>,
     pub processing_errors: Vec<String>,
 }
 
 /// Nostr event kinds
 pub mod nostr_kinds {
     pub const METADATA: u16 = 0;
+    pub const TEXT_NOTE: u16 = 1;
+    pub const DELETION: u16 = 5;
+    pub const REACTION: u16 = 7;
+    pub const CHAT_MESSAGE: u16 = 9;
+    pub const FILE_METADATA: u16 = 1063;
+    /// Ephemeral group signal that relays do not store, such as typing: a signed
+    /// event of this kind encrypted to the group's exporter secret, addressed
+    /// with an `h` tag like kind 445
+    pub const GROUP_SIGNAL: u16 = 20445;
+
+    // Application-specific rumor kinds, only ever sent encrypted inside MLS groups
+
+    /// Read receipt: `e` tag points at the newest message the sender has read
+    pub const READ_RECEIPT: u16 = 9901;
+    /// Edit: `e` tag points at the replaced message, content is the new text
+    pub const EDIT: u16 = 9903;
+    /// Client-generated notice shown as a system line
+    pub const SYSTEM_NOTICE: u16 = 9904;
 }
 
 /// UI update events for real-time messaging
@@ -167,6 +584,93 @@ pub enum UiUpdate {
     ConnectionStatus(ConnectionStatus),
     /// New invitation received
     NewInvite(PendingInvite),
-    /// Group has new messages (triggers a fetch)
+    /// The invite policy decided on an incoming welcome. Pending invites are
+    /// also announced as `NewInvite`; dropped ones carry no invite details
+    /// because they were never processed.
+    InviteDecided { inviter: PublicKey, invite: Option<PendingInvite>, decision: InviteDecision },
+    /// Group has new messages; sent right after the `NewMessage` it announces
+    #[deprecated(note = "use `NewMessage`, which carries the message")]
     GroupHasNewMessages { group_id: GroupId },
-}
\ No newline at end of file
+    /// A member's read marker advanced to `message_id`
+    ReadReceipt { group_id: GroupId, reader: PublicKey, message_id: String },
+    /// A member started typing in a group
+    Typing { group_id: GroupId, pubkey: PublicKey },
+    /// A member stopped typing (sent their message or cleared the input)
+    TypingStopped { group_id: GroupId, pubkey: PublicKey },
+    /// A contact came online or went offline
+    PresenceChanged { pubkey: PublicKey, online: bool, last_seen: Option<i64> },
+    /// The set of pinned messages in a group changed
+    PinsChanged { group_id: GroupId },
+    /// Delivery state of an outgoing message changed
+    DeliveryUpdate { group_id: GroupId, message_id: String, delivery: DeliveryInfo },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: Vec<Vec<&str>>) -> Tags {
+        Tags::new(tags.into_iter().map(|t| Tag::parse(t).unwrap()).collect())
+    }
+
+    #[test]
+    fn test_message_body_parsing() {
+        let target = EventId::all_zeros();
+        let e = vec!["e", "0000000000000000000000000000000000000000000000000000000000000000"];
+
+        assert_eq!(
+            MessageBody::parse(Kind::TextNote, "hi", &Tags::new(vec![])),
+            MessageBody::Text { text: "hi".into() }
+        );
+        assert_eq!(
+            MessageBody::parse(Kind::Custom(9), "yes", &tags(vec![vec!["q", &target.to_hex()]])),
+            MessageBody::Reply { in_reply_to: target, text: "yes".into() }
+        );
+        assert_eq!(
+            MessageBody::parse(Kind::Reaction, "🔥", &tags(vec![e.clone()])),
+            MessageBody::Reaction { target, emoji: "🔥".into() }
+        );
+        assert_eq!(
+            MessageBody::parse(Kind::Custom(nostr_kinds::EDIT), "fixed", &tags(vec![e.clone()])),
+            MessageBody::Edit { target, text: "fixed".into() }
+        );
+        assert_eq!(
+            MessageBody::parse(Kind::EventDeletion, "", &tags(vec![e])),
+            MessageBody::Deletion { targets: vec![target], reason: None }
+        );
+        assert_eq!(
+            MessageBody::parse(
+                Kind::Custom(9),
+                "look https://example.com/a.png",
+                &tags(vec![vec!["imeta", "url https://example.com/a.png", "m image/png"]])
+            ),
+            MessageBody::Attachment {
+                url: "https://example.com/a.png".into(),
+                mime_type: Some("image/png".into()),
+                caption: Some("look https://example.com/a.png".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unknown_kinds_degrade() {
+        let body = MessageBody::parse(Kind::Custom(4242), "whatever", &Tags::new(vec![]));
+        assert_eq!(body, MessageBody::Unknown { kind: 4242, content: "whatever".into() });
+        assert_eq!(body.to_string(), "[kind 4242] whatever");
+
+        // A reaction without a target cannot be shown as one
+        let body = MessageBody::parse(Kind::Reaction, "+", &Tags::new(vec![]));
+        assert!(matches!(body, MessageBody::Unknown { kind: 7, .. }));
+    }
+
+    #[test]
+    fn test_reject_reason_prefixes() {
+        assert_eq!(RejectReason::from_message("rate-limited: slow down"), RejectReason::RateLimited);
+        assert_eq!(RejectReason::from_message("auth-required: we only accept events from members"), RejectReason::AuthRequired);
+        assert_eq!(RejectReason::from_message("blocked: you are banned"), RejectReason::Blocked);
+        assert_eq!(RejectReason::from_message("duplicate: already have this event"), RejectReason::Duplicate);
+        assert_eq!(RejectReason::from_message("connection closed"), RejectReason::Other);
+        assert!(RejectReason::RateLimited.is_retryable());
+        assert!(!RejectReason::Invalid.is_retryable());
+    }
+}
@@ -0,0 +1,265 @@
+use dialog_lib::{AuthStatus, ConnectionStatus, DialogError, DialogLib, UiUpdate};
+use futures::{SinkExt, StreamExt};
+use nostr::prelude::*;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Duration};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+#[derive(Debug, Default)]
+struct RelayState {
+    events: Vec<Event>,
+    /// Identities whose AUTH is refused
+    refused: HashSet<PublicKey>,
+    /// Challenge only when a client asks for gift wraps, not on connect
+    challenge_on_req: bool,
+    gift_wraps_served: usize,
+    gift_wrap_reqs_closed: usize,
+}
+
+/// Local relay stand-in: challenges every connection (or only the first
+/// gift wrap REQ) and serves kind 1059 only to clients that authenticated as
+/// the recipient
+struct AuthRelay {
+    url: String,
+    state: Arc<Mutex<RelayState>>,
+}
+
+impl AuthRelay {
+    async fn start(refused: &[PublicKey]) -> Self {
+        Self::with_state(RelayState {
+            refused: refused.iter().copied().collect(),
+            ..Default::default()
+        })
+        .await
+    }
+
+    async fn challenging_on_req() -> Self {
+        Self::with_state(RelayState { challenge_on_req: true, ..Default::default() }).await
+    }
+
+    async fn with_state(state: RelayState) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(state));
+
+        let shared = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(stream, shared.clone()));
+            }
+        });
+
+        Self { url, state }
+    }
+}
+
+async fn serve_connection(stream: TcpStream, state: Arc<Mutex<RelayState>>) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut stream) = ws.split();
+    let challenge = Keys::generate().public_key().to_hex();
+    let mut authenticated: Option<PublicKey> = None;
+
+    if !state.lock().unwrap().challenge_on_req {
+        let _ = sink.send(WsMessage::text(json!(["AUTH", challenge]).to_string())).await;
+    }
+
+    while let Some(Ok(message)) = stream.next().await {
+        let WsMessage::Text(text) = message else {
+            continue;
+        };
+        let Ok(Value::Array(parts)) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+
+        let mut replies: Vec<Value> = Vec::new();
+        match parts.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                let Ok(event) = serde_json::from_value::<Event>(parts[1].clone()) else {
+                    continue;
+                };
+                let valid = event.verify().is_ok();
+                if valid {
+                    state.lock().unwrap().events.push(event.clone());
+                }
+                replies.push(json!(["OK", event.id.to_hex(), valid, if valid { "" } else { "invalid: bad signature" }]));
+            }
+            Some("AUTH") => {
+                let Ok(event) = serde_json::from_value::<Event>(parts[1].clone()) else {
+                    continue;
+                };
+                let answers_challenge = event.kind == Kind::Authentication
+                    && event.verify().is_ok()
+                    && event.tags.iter().any(|t| t.as_slice() == ["challenge".to_string(), challenge.clone()]);
+                let (accepted, reason) = if !answers_challenge {
+                    (false, "auth-required: invalid challenge response")
+                } else if state.lock().unwrap().refused.contains(&event.pubkey) {
+                    (false, "restricted: not on the allow list")
+                } else {
+                    authenticated = Some(event.pubkey);
+                    (true, "")
+                };
+                replies.push(json!(["OK", event.id.to_hex(), accepted, reason]));
+            }
+            Some("REQ") => {
+                let subscription_id = parts[1].clone();
+                let filters: Vec<Filter> = parts[2..]
+                    .iter()
+                    .filter_map(|f| serde_json::from_value(f.clone()).ok())
+                    .collect();
+                let wants_gift_wraps = filters
+                    .iter()
+                    .any(|f| f.kinds.as_ref().is_some_and(|kinds| kinds.contains(&Kind::GiftWrap)));
+
+                let mut state = state.lock().unwrap();
+                if wants_gift_wraps && authenticated.is_none() {
+                    if state.challenge_on_req {
+                        replies.push(json!(["AUTH", challenge]));
+                    }
+                    state.gift_wrap_reqs_closed += 1;
+                    replies.push(json!(["CLOSED", subscription_id, "auth-required: gift wraps are only served to their recipient"]));
+                } else {
+                    let matching: Vec<Event> = state
+                        .events
+                        .iter()
+                        .filter(|e| filters.iter().any(|f| matches(f, e)))
+                        .filter(|e| e.kind != Kind::GiftWrap || e.tags.public_keys().any(|pk| Some(*pk) == authenticated))
+                        .cloned()
+                        .collect();
+                    for event in matching {
+                        if event.kind == Kind::GiftWrap {
+                            state.gift_wraps_served += 1;
+                        }
+                        replies.push(json!(["EVENT", subscription_id, event]));
+                    }
+                    replies.push(json!(["EOSE", subscription_id]));
+                }
+            }
+            _ => {}
+        }
+
+        for reply in replies {
+            if sink.send(WsMessage::text(reply.to_string())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Kinds, authors and single-letter tag conditions of a filter
+fn matches(filter: &Filter, event: &Event) -> bool {
+    if filter.kinds.as_ref().is_some_and(|kinds| !kinds.contains(&event.kind)) {
+        return false;
+    }
+    if filter.authors.as_ref().is_some_and(|authors| !authors.contains(&event.pubkey)) {
+        return false;
+    }
+    filter.generic_tags.iter().all(|(letter, values)| {
+        let letter = letter.to_string();
+        event.tags.iter().any(|tag| {
+            let tag = tag.as_slice();
+            tag.len() >= 2 && tag[0] == letter && values.contains(&tag[1])
+        })
+    })
+}
+
+#[tokio::test]
+async fn test_gift_wraps_are_read_after_auth() {
+    let relay = AuthRelay::start(&[]).await;
+
+    let alice = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay.url).await.unwrap();
+    let bob = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay.url).await.unwrap();
+    alice.connect().await.expect("Alice should authenticate");
+    bob.connect().await.expect("Bob should authenticate");
+
+    let statuses = bob.auth_status().await;
+    assert!(!statuses.is_empty(), "The relay challenged us");
+    assert!(statuses.values().all(|s| *s == AuthStatus::Authenticated));
+
+    bob.publish_key_packages().await.unwrap();
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    alice.create_conversation("Private", vec![bob_pubkey]).await.unwrap();
+
+    let invites = bob.list_pending_invites().await.unwrap();
+    assert!(!invites.invites.is_empty());
+
+    let state = relay.state.lock().unwrap();
+    assert!(state.gift_wraps_served >= 1, "Bob's gift wrap should have been served");
+    assert_eq!(state.gift_wrap_reqs_closed, 0);
+}
+
+#[tokio::test]
+async fn test_refused_auth_is_a_distinct_error() {
+    let carol_keys = Keys::generate();
+    let relay = AuthRelay::start(&[carol_keys.public_key()]).await;
+
+    let carol = DialogLib::new_with_keys_and_relay(carol_keys, &relay.url).await.unwrap();
+    match carol.connect().await {
+        Err(DialogError::Auth(reason)) => assert!(reason.contains("restricted")),
+        other => panic!("Expected an auth error, got {:?}", other),
+    }
+    // A refused relay serves nothing private, so we are not connected
+    assert_eq!(carol.get_connection_status().await.unwrap(), ConnectionStatus::Disconnected);
+    assert!(matches!(
+        carol.auth_status().await.values().next(),
+        Some(AuthStatus::Failed(_))
+    ));
+
+    // Reads that need authentication report the same error instead of an empty result
+    assert!(matches!(carol.list_pending_invites().await, Err(DialogError::Auth(_))));
+}
+
+#[tokio::test]
+async fn test_subscription_closed_for_auth_is_resent() {
+    let relay = AuthRelay::challenging_on_req().await;
+
+    let alice = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay.url).await.unwrap();
+    let bob = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay.url).await.unwrap();
+    alice.connect().await.unwrap();
+    bob.connect().await.unwrap();
+    assert!(bob.auth_status().await.is_empty(), "Nothing was challenged yet");
+
+    bob.publish_key_packages().await.unwrap();
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    alice.create_conversation("Later", vec![bob_pubkey]).await.unwrap();
+
+    // The welcome REQ is closed with auth-required, answered, then sent again
+    let mut updates = bob.events();
+    bob.subscribe().await.unwrap();
+    let invited = timeout(Duration::from_secs(5), async {
+        loop {
+            match updates.next().await {
+                Some(UiUpdate::NewInvite(_)) | Some(UiUpdate::InviteDecided { .. }) => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+    })
+    .await
+    .unwrap_or(false);
+    assert!(invited, "Bob should get his welcome once authenticated");
+
+    assert!(bob.auth_status().await.values().all(|s| *s == AuthStatus::Authenticated));
+    let state = relay.state.lock().unwrap();
+    assert!(state.gift_wrap_reqs_closed >= 1);
+    assert!(state.gift_wraps_served >= 1);
+}
+
+#[tokio::test]
+async fn test_reconnect_answers_new_challenges() {
+    let relay = AuthRelay::start(&[]).await;
+
+    let bob = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay.url).await.unwrap();
+    bob.connect().await.unwrap();
+    bob.toggle_connection().await.unwrap();
+    assert!(bob.auth_status().await.is_empty());
+
+    bob.toggle_connection().await.expect("The new connection should authenticate");
+    let statuses = bob.auth_status().await;
+    assert!(!statuses.is_empty(), "The relay challenged the new connection");
+    assert!(statuses.values().all(|s| *s == AuthStatus::Authenticated));
+}
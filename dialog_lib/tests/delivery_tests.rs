@@ -0,0 +1,71 @@
+mod test_helpers;
+
+use dialog_lib::DeliveryStatus;
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_send_report_lists_accepting_relay() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Delivery").await;
+
+    let report = alice.send_message(&group_id, "hello")
+        .await
+        .expect("Failed to send message");
+
+    assert!(report.is_sent());
+    assert_eq!(report.accepted().count(), 1);
+    assert_eq!(report.rejected().count(), 0);
+
+    // The delivery state is attached to our own message when fetching
+    let fetched = alice.fetch_messages(&group_id)
+        .await
+        .expect("Failed to fetch messages");
+    let message = fetched.messages
+        .iter()
+        .find(|m| m.id.as_deref() == Some(report.message_id.as_str()))
+        .expect("Sent message should be in the timeline");
+    let delivery = message.delivery.as_ref().expect("Own message should carry delivery info");
+    assert_eq!(delivery.status, DeliveryStatus::Sent);
+    assert_eq!(delivery.accepted_by.len(), 1);
+}
+
+#[tokio::test]
+async fn test_offline_message_is_queued_and_retried() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Outbox").await;
+
+    // Go offline and compose
+    alice.toggle_connection().await.expect("Failed to disconnect");
+    let report = alice.send_message(&group_id, "written offline")
+        .await
+        .expect("Composing offline should succeed");
+    assert_eq!(report.status, DeliveryStatus::Pending);
+    assert!(report.relays.is_empty());
+    assert_eq!(alice.outbox_pending_count().await, 1);
+
+    // Reconnecting flushes the outbox in the background
+    alice.connect().await.expect("Failed to reconnect");
+    for _ in 0..50 {
+        if alice.outbox_pending_count().await == 0 {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(alice.outbox_pending_count().await, 0);
+
+    let fetched = bob.fetch_messages(&group_id)
+        .await
+        .expect("Failed to fetch messages");
+    assert!(fetched.messages.iter().any(|m| m.content == "written offline"));
+}
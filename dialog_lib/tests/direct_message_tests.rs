@@ -0,0 +1,67 @@
+mod test_helpers;
+
+use test_helpers::{connected_dialog, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_direct_conversation_is_reused() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let alice_pubkey = alice.get_own_pubkey().await.unwrap();
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let dm = alice.open_direct_conversation(&bob_pubkey).await.unwrap();
+    assert!(!dm.is_group);
+    assert_eq!(dm.participants.len(), 2);
+    assert!(dm.participants.contains(&bob_pubkey));
+
+    // Opening it again finds the same group instead of creating another one
+    let again = alice.open_direct_conversation(&bob_pubkey).await.unwrap();
+    assert_eq!(again.id, dm.id);
+    assert_eq!(alice.get_conversations().await.unwrap().len(), 1);
+
+    // Bob sees a direct conversation too, and reuses it from his side
+    sleep(Duration::from_millis(200)).await;
+    bob.list_pending_invites().await.unwrap();
+    bob.accept_invite(&dm.id).await.unwrap();
+    let from_bob = bob.open_direct_conversation(&alice_pubkey).await.unwrap();
+    assert_eq!(from_bob.id, dm.id);
+    assert!(!from_bob.is_group);
+
+    assert!(alice.open_direct_conversation(&alice_pubkey).await.is_err());
+}
+
+#[tokio::test]
+async fn test_named_groups_are_groups() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let group_hex = alice
+        .create_conversation("Weekend plans", vec![bob.get_own_pubkey().await.unwrap()])
+        .await
+        .unwrap();
+    let conversations = alice.get_conversations().await.unwrap();
+    let group = conversations.iter().find(|c| c.id == group_hex).unwrap();
+    assert!(group.is_group);
+    assert_eq!(group.name, "Weekend plans");
+    assert_eq!(group.participants.len(), 2);
+
+    // A named group with the same two people is not taken for their DM
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let dm = alice.open_direct_conversation(&bob.get_own_pubkey().await.unwrap()).await.unwrap();
+    assert_ne!(dm.id, group_hex);
+    assert!(!dm.is_group);
+}
@@ -0,0 +1,57 @@
+mod test_helpers;
+
+use dialog_lib::{DialogLib, StorageBackend};
+use test_helpers::{connected_dialog, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_processed_events_are_not_reprocessed_after_restart() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+    let db_path = std::env::temp_dir().join(format!("dialog_ledger_test_{}.db", std::process::id()));
+    let storage = StorageBackend::Sqlite { path: db_path.clone() };
+    let bob_keys = scenario.get_user("bob").unwrap().keys().clone();
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = DialogLib::new_with_storage(bob_keys.clone(), scenario.relay_url(), storage.clone())
+        .await
+        .unwrap();
+    bob.connect().await.unwrap();
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let group_hex = alice
+        .create_conversation("Ledger", vec![bob.get_own_pubkey().await.unwrap()])
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+    bob.list_pending_invites().await.unwrap();
+    bob.accept_invite(&group_hex).await.unwrap();
+
+    alice.send_message(&group_hex, "hello").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let first = bob.fetch_messages(&group_hex).await.unwrap();
+    assert!(first.processing_errors.is_empty(), "{:?}", first.processing_errors);
+    bob.send_message(&group_hex, "hi back").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    drop(bob);
+
+    // After a restart the relay returns the same events, including Bob's own
+    let bob = DialogLib::new_with_storage(bob_keys, scenario.relay_url(), storage)
+        .await
+        .unwrap();
+    bob.connect().await.unwrap();
+    let again = bob.fetch_messages(&group_hex).await.unwrap();
+    assert!(
+        again.processing_errors.is_empty(),
+        "Known events should be skipped, not reprocessed: {:?}",
+        again.processing_errors
+    );
+    let contents: Vec<&str> = again.messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, vec!["hello", "hi back"]);
+
+    for extension in ["db", "outbox.json", "events.json"] {
+        let _ = std::fs::remove_file(db_path.with_extension(extension));
+    }
+}
@@ -0,0 +1,31 @@
+mod test_helpers;
+
+use test_helpers::{connected_dialog, create_joined_group, next_message, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_every_stream_receives_decrypted_messages() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Streams").await;
+    let alice_pubkey = alice.get_own_pubkey().await.unwrap();
+
+    // e.g. the UI and a logger, without any mpsc sender
+    let mut ui = bob.events();
+    let mut logger = bob.events();
+    bob.subscribe().await.expect("Failed to subscribe");
+    sleep(Duration::from_millis(200)).await;
+
+    alice.send_message(&group_id, "hello streams").await.unwrap();
+
+    for stream in [&mut ui, &mut logger] {
+        let message = next_message(stream, &group_id).await;
+        assert_eq!(message.content, "hello streams");
+        assert_eq!(message.sender, alice_pubkey);
+        assert!(message.id.is_some());
+    }
+}
@@ -0,0 +1,86 @@
+mod test_helpers;
+
+use dialog_lib::SkipReason;
+use test_helpers::{connected_dialog, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_best_effort_creation_skips_unreachable_participants() {
+    let scenario = TestScenario::new(&["alice", "bob", "carol"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    // Carol never publishes a key package
+    let carol_pubkey = scenario.get_user("carol").unwrap().keys().public_key();
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    // The strict call still refuses to leave anyone out
+    let strict = alice.create_conversation("Team", vec![bob_pubkey, carol_pubkey]).await;
+    assert!(strict.is_err());
+    assert!(alice.get_conversations().await.unwrap().is_empty());
+
+    let report = alice
+        .create_conversation_best_effort("Team", vec![bob_pubkey, carol_pubkey])
+        .await
+        .expect("Group should be created with Bob");
+    assert_eq!(report.added, vec![bob_pubkey]);
+    assert_eq!(report.skipped, vec![(carol_pubkey, SkipReason::NoKeyPackage)]);
+    assert!(report.undelivered.is_empty());
+
+    sleep(Duration::from_millis(200)).await;
+    bob.list_pending_invites().await.unwrap();
+    bob.accept_invite(&report.group_id).await.expect("Bob should receive his welcome");
+
+    // With nobody reachable there is no group to create
+    let nobody = alice.create_conversation_best_effort("Empty", vec![carol_pubkey]).await;
+    assert!(nobody.unwrap_err().to_string().contains("No key package found"));
+}
+
+#[tokio::test]
+async fn test_skipped_participant_can_be_added_later() {
+    let scenario = TestScenario::new(&["alice", "bob", "carol"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let carol = connected_dialog(scenario.get_user("carol").unwrap(), scenario.relay_url()).await;
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    let carol_pubkey = carol.get_own_pubkey().await.unwrap();
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let report = alice
+        .create_conversation_best_effort("Later", vec![bob_pubkey, carol_pubkey])
+        .await
+        .unwrap();
+    assert_eq!(report.skipped, vec![(carol_pubkey, SkipReason::NoKeyPackage)]);
+    sleep(Duration::from_millis(200)).await;
+    bob.list_pending_invites().await.unwrap();
+    bob.accept_invite(&report.group_id).await.unwrap();
+
+    // Once Carol has a key package she can be invited into the existing group
+    carol.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let added = alice.add_members(&report.group_id, vec![carol_pubkey]).await.unwrap();
+    assert_eq!(added.added, vec![carol_pubkey]);
+    assert!(added.skipped.is_empty());
+    assert!(added.undelivered.is_empty());
+
+    sleep(Duration::from_millis(200)).await;
+    carol.list_pending_invites().await.unwrap();
+    carol.accept_invite(&report.group_id).await.expect("Carol should receive her welcome");
+    alice.send_message(&report.group_id, "welcome carol").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let fetched = carol.fetch_messages(&report.group_id).await.unwrap();
+    assert_eq!(fetched.messages.len(), 1);
+    assert_eq!(fetched.messages[0].content, "welcome carol");
+
+    // Only admins can add members
+    let carol_again = bob.add_members(&report.group_id, vec![carol_pubkey]).await;
+    assert!(carol_again.unwrap_err().to_string().contains("Only group admins"));
+}
@@ -0,0 +1,28 @@
+mod test_helpers;
+
+use dialog_lib::GroupRef;
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+
+#[tokio::test]
+async fn test_group_ref_forms_resolve_to_the_same_group() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Book Club").await;
+    let group_hex = dialog_lib::hex::encode(group_id.as_slice());
+
+    assert_eq!(alice.resolve_group(&group_id).await.unwrap(), group_id);
+    assert_eq!(alice.resolve_group(&group_hex).await.unwrap(), group_id);
+    assert_eq!(alice.resolve_group("book club").await.unwrap(), group_id);
+    assert_eq!(alice.resolve_group("Book").await.unwrap(), group_id);
+    assert_eq!(alice.resolve_group(&group_hex[..8]).await.unwrap(), group_id);
+    assert!(alice.resolve_group("Chess").await.is_err());
+
+    // Methods take any form of reference
+    alice.send_message("Book Club", "chapter 3 tonight").await.unwrap();
+    let fetched = bob.fetch_messages(GroupRef::parse("book")).await.unwrap();
+    assert_eq!(fetched.messages.len(), 1);
+}
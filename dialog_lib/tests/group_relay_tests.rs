@@ -0,0 +1,144 @@
+mod test_helpers;
+
+use dialog_lib::UiUpdate;
+use futures::StreamExt;
+use nostr_mls::prelude::*;
+use nostr_mls_memory_storage::NostrMlsMemoryStorage;
+use nostr_sdk::prelude::*;
+use test_helpers::{connected_dialog, create_joined_group, next_message, EphemeralRelay, TestScenario};
+use tokio::time::{sleep, timeout, Duration};
+
+/// Events of `kind` stored on `relay_url`
+async fn events_on(relay_url: &str, kind: Kind) -> usize {
+    let observer = Client::default();
+    observer.add_relay(relay_url).await.unwrap();
+    observer.connect().await;
+    observer
+        .fetch_events(Filter::new().kind(kind), Duration::from_secs(2))
+        .await
+        .unwrap()
+        .len()
+}
+
+#[tokio::test]
+async fn test_group_traffic_uses_the_groups_relays() {
+    let scenario = TestScenario::new(&["bob", "carol"])
+        .await
+        .expect("Failed to create test scenario");
+    let group_relay = EphemeralRelay::start().await.expect("Failed to start group relay");
+
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    // Carol uses another client, which puts the group on a relay Bob has never used
+    let carol_keys = scenario.get_user("carol").unwrap().keys().clone();
+    let carol_mls = NostrMls::new(NostrMlsMemoryStorage::default());
+    let carol = Client::new(carol_keys.clone());
+    carol.add_relay(scenario.relay_url()).await.unwrap();
+    carol.add_relay(group_relay.url()).await.unwrap();
+    carol.connect().await;
+    sleep(Duration::from_millis(200)).await;
+
+    let key_package = carol
+        .fetch_events_from(
+            [scenario.relay_url()],
+            Filter::new().kind(Kind::MlsKeyPackage).author(bob_pubkey),
+            Duration::from_secs(2),
+        )
+        .await
+        .unwrap()
+        .first()
+        .cloned()
+        .expect("Bob's key package");
+    let config = NostrGroupConfigData::new(
+        "Elsewhere".to_string(),
+        String::new(),
+        None,
+        None,
+        vec![RelayUrl::parse(group_relay.url()).unwrap()],
+    );
+    let created = carol_mls
+        .create_group(&carol_keys.public_key(), vec![key_package], vec![carol_keys.public_key()], config)
+        .unwrap();
+    let group_id = created.group.mls_group_id.clone();
+    let welcome = created.welcome_rumors.into_iter().next().unwrap();
+    let gift_wrap = EventBuilder::gift_wrap(&carol_keys, &bob_pubkey, welcome, None).await.unwrap();
+    carol.send_event_to([scenario.relay_url()], &gift_wrap).await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    bob.list_pending_invites().await.unwrap();
+    bob.accept_invite(&hex::encode(group_id.as_slice())).await.unwrap();
+
+    // Carol writes on the group relay only
+    let rumor = EventBuilder::new(Kind::TextNote, "over here").build(carol_keys.public_key());
+    let message = carol_mls.create_message(&group_id, rumor).unwrap();
+    carol.send_event_to([group_relay.url()], &message).await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    // Bob reads it from the group relay and answers there
+    let fetched = bob.fetch_messages(group_id.clone()).await.unwrap();
+    assert!(fetched.messages.iter().any(|m| m.content == "over here"));
+    let report = bob.send_message(group_id.clone(), "found you").await.unwrap();
+    assert!(!report.relays.is_empty());
+    assert!(report.relays.iter().all(|r| r.relay_url.starts_with(group_relay.url())));
+    sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(events_on(group_relay.url(), Kind::MlsGroupMessage).await, 2);
+    assert_eq!(events_on(scenario.relay_url(), Kind::MlsGroupMessage).await, 0);
+
+    // The real-time subscription listens on the group relay too
+    let mut updates = bob.events();
+    bob.subscribe().await.expect("Failed to subscribe");
+    sleep(Duration::from_millis(200)).await;
+    let rumor = EventBuilder::new(Kind::TextNote, "live").build(carol_keys.public_key());
+    let message = carol_mls.create_message(&group_id, rumor).unwrap();
+    carol.send_event_to([group_relay.url()], &message).await.unwrap();
+    let live = next_message(&mut updates, &group_id).await;
+    assert_eq!(live.content, "live");
+
+    // Having joined the group relay, Bob still publishes his own events only to his relay
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(events_on(group_relay.url(), Kind::MlsKeyPackage).await, 0);
+}
+
+#[tokio::test]
+async fn test_group_on_our_relay_keeps_its_subscription_beside_invites() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Home").await;
+
+    // The group and Bob's invites are both requested from the scenario relay
+    let mut updates = bob.events();
+    bob.subscribe().await.expect("Failed to subscribe");
+    sleep(Duration::from_millis(200)).await;
+
+    alice.send_message(&group_id, "still listening").await.unwrap();
+    assert_eq!(next_message(&mut updates, &group_id).await.content, "still listening");
+
+    // ... and the invite subscription was not replaced by the group's either
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    alice
+        .create_conversation("Second", vec![bob.get_own_pubkey().await.unwrap()])
+        .await
+        .unwrap();
+    let invited = timeout(Duration::from_secs(5), async {
+        loop {
+            match updates.next().await {
+                Some(UiUpdate::NewInvite(_)) | Some(UiUpdate::InviteDecided { .. }) => return true,
+                Some(_) => {}
+                None => return false,
+            }
+        }
+    })
+    .await
+    .unwrap_or(false);
+    assert!(invited, "Bob should still receive invites");
+}
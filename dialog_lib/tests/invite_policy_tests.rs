@@ -0,0 +1,77 @@
+mod test_helpers;
+
+use dialog_lib::{DropReason, InviteDecision, UiUpdate};
+use test_helpers::{connected_dialog, TestScenario};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+
+#[tokio::test]
+async fn test_invites_from_contacts_are_joined_automatically() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    bob.add_contact(scenario.get_user("alice").unwrap().pubkey_hex()).await.unwrap();
+
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let group_hex = alice
+        .create_conversation("Friends", vec![bob.get_own_pubkey().await.unwrap()])
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let invites = bob.list_pending_invites().await.unwrap();
+    assert!(invites.invites.is_empty(), "A contact's invite should not wait for approval");
+    let conversations = bob.get_conversations().await.unwrap();
+    assert!(conversations.iter().any(|c| c.id == group_hex), "Bob should already be in the group");
+}
+
+#[tokio::test]
+async fn test_stranger_invites_wait_and_blocked_invites_are_dropped() {
+    let scenario = TestScenario::new(&["alice", "bob", "mallory"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let mallory = connected_dialog(scenario.get_user("mallory").unwrap(), scenario.relay_url()).await;
+    let alice_pubkey = alice.get_own_pubkey().await.unwrap();
+    let mallory_pubkey = mallory.get_own_pubkey().await.unwrap();
+    bob.block_inviter(mallory_pubkey).await.unwrap();
+
+    let (tx, mut rx) = mpsc::channel(100);
+    bob.subscribe_to_groups(tx).await.expect("Failed to subscribe");
+
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    mallory.create_conversation("Free stuff", vec![bob_pubkey]).await.unwrap();
+    alice.create_conversation("Book club", vec![bob_pubkey]).await.unwrap();
+
+    let mut pending_from_alice = false;
+    let mut dropped_from_mallory = false;
+    let _ = timeout(Duration::from_secs(5), async {
+        while let Some(update) = rx.recv().await {
+            if let UiUpdate::InviteDecided { inviter, decision, .. } = update {
+                pending_from_alice |= inviter == alice_pubkey && decision == InviteDecision::Pending;
+                dropped_from_mallory |=
+                    inviter == mallory_pubkey && decision == InviteDecision::Dropped(DropReason::Blocked);
+            }
+            if pending_from_alice && dropped_from_mallory {
+                break;
+            }
+        }
+    })
+    .await;
+    assert!(pending_from_alice, "A stranger's invite should wait for approval");
+    assert!(dropped_from_mallory, "A blocked inviter's invite should be dropped");
+
+    let invites = bob.list_pending_invites().await.unwrap();
+    assert_eq!(invites.invites.len(), 1);
+    assert_eq!(invites.invites[0].group_name, "Book club");
+    assert_eq!(invites.invites[0].inviter, Some(alice_pubkey));
+    assert!(bob.get_conversations().await.unwrap().is_empty());
+}
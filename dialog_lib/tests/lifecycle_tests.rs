@@ -0,0 +1,85 @@
+mod test_helpers;
+
+use dialog_lib::{ConnectionStatus, UiUpdate};
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+
+#[tokio::test]
+async fn test_resubscribing_replaces_the_subscription_task() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Lifecycle").await;
+
+    let (first_tx, mut first_rx) = mpsc::channel(100);
+    let first = bob.subscribe_to_groups(first_tx).await.unwrap();
+    let (second_tx, mut second_rx) = mpsc::channel(100);
+    let second = bob.subscribe_to_groups(second_tx).await.unwrap();
+    assert!(!first.is_running());
+    assert!(second.is_running());
+
+    // The replaced task is gone, so its channel closes
+    let closed = timeout(Duration::from_secs(2), async { while first_rx.recv().await.is_some() {} }).await;
+    assert!(closed.is_ok(), "The first subscription task should have exited");
+
+    sleep(Duration::from_millis(200)).await;
+    alice.send_message(&group_id, "once").await.unwrap();
+    sleep(Duration::from_secs(1)).await;
+    let mut notified = 0;
+    while let Ok(update) = second_rx.try_recv() {
+        if matches!(update, UiUpdate::NewMessage { group_id: g, .. } if g == group_id) {
+            notified += 1;
+        }
+    }
+    assert_eq!(notified, 1, "Exactly one task should handle the message");
+
+    // Stopping through the handle ends the task too
+    second.stop().await;
+    assert!(!second.is_running());
+    assert!(timeout(Duration::from_secs(2), second_rx.recv()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_shutdown_stops_everything() {
+    let scenario = TestScenario::new(&["alice"])
+        .await
+        .expect("Failed to create test scenario");
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+
+    let (tx, _rx) = mpsc::channel(100);
+    let subscription = alice.subscribe_to_groups(tx).await.unwrap();
+
+    alice.shutdown().await.unwrap();
+    assert!(!subscription.is_running());
+    assert_eq!(alice.get_connection_status().await.unwrap(), ConnectionStatus::Disconnected);
+    assert!(alice.connect().await.is_err(), "A shut down instance does not reconnect");
+    assert!(alice.get_conversations().await.is_err(), "Storage is closed");
+}
+
+#[tokio::test]
+async fn test_stop_does_not_wait_for_a_full_channel() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Unread").await;
+
+    // A consumer that stopped reading: the task blocks on the second update
+    let (tx, _rx) = mpsc::channel(1);
+    let subscription = bob.subscribe_to_groups(tx).await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    for i in 0..3 {
+        alice.send_message(&group_id, &format!("unread {}", i)).await.unwrap();
+    }
+    sleep(Duration::from_secs(1)).await;
+
+    timeout(Duration::from_secs(2), subscription.stop())
+        .await
+        .expect("Stopping should not wait for the consumer");
+    assert!(!subscription.is_running());
+}
@@ -0,0 +1,47 @@
+mod test_helpers;
+
+use dialog_lib::{RealMlsService, SystemEventKind, TimelineEntry};
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+
+#[tokio::test]
+async fn test_admin_pins_are_shared_with_members() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Pins").await;
+
+    let first = alice.send_message(&group_id, "meeting at 10").await.unwrap();
+    alice.send_message(&group_id, "bring snacks").await.unwrap();
+
+    // Only the creator is an admin
+    bob.fetch_messages(&group_id).await.unwrap();
+    assert!(bob.set_pinned(&group_id, &first.message_id, true).await.is_err());
+
+    alice.set_pinned(&group_id, &first.message_id, true).await.expect("Admin should be able to pin");
+    let pins = alice.pinned_messages(&group_id).await.unwrap();
+    assert_eq!(pins.len(), 1);
+    assert_eq!(pins[0].content, "meeting at 10");
+
+    bob.fetch_messages(&group_id).await.unwrap();
+    let pins = bob.pinned_messages(&group_id).await.unwrap();
+    assert_eq!(pins.len(), 1, "Bob should see the same pinned set");
+    assert_eq!(pins[0].id.as_deref(), Some(first.message_id.as_str()));
+
+    // Pins are group data changed by a commit, never conversation messages
+    let bob_view = bob.fetch_messages(&group_id).await.unwrap();
+    assert_eq!(bob_view.messages.len(), 2);
+    assert!(bob_view
+        .timeline
+        .iter()
+        .any(|entry| matches!(entry, TimelineEntry::System(e) if e.kind == SystemEventKind::PinsChanged)));
+    let service = alice.service().as_any().downcast_ref::<RealMlsService>().unwrap();
+    let stored = service.store().get_messages(&group_id).await.unwrap();
+    assert_eq!(stored.len(), 2, "Only the two chat messages are stored");
+
+    alice.set_pinned(&group_id, &first.message_id, false).await.unwrap();
+    bob.fetch_messages(&group_id).await.unwrap();
+    assert!(bob.pinned_messages(&group_id).await.unwrap().is_empty());
+}
@@ -0,0 +1,91 @@
+mod test_helpers;
+
+use dialog_lib::{DialogLib, StorageBackend};
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_read_receipt_marks_message_seen() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Receipts").await;
+    bob.set_read_receipts(true).await.unwrap();
+
+    alice.send_message(&group_id, "first").await.unwrap();
+    alice.send_message(&group_id, "second").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    // Bob reads both messages; a single receipt covers them
+    bob.fetch_messages(&group_id).await.unwrap();
+    assert!(bob.mark_read(&group_id).await.unwrap());
+    assert!(!bob.mark_read(&group_id).await.unwrap(), "Marker should not move without new messages");
+    sleep(Duration::from_millis(200)).await;
+
+    let fetched = alice.fetch_messages(&group_id).await.unwrap();
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    assert_eq!(fetched.messages.len(), 2, "Receipts must not show up as messages");
+    for message in &fetched.messages {
+        assert_eq!(message.seen_by, vec![bob_pubkey]);
+    }
+}
+
+#[tokio::test]
+async fn test_no_receipt_when_disabled() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Private").await;
+
+    alice.send_message(&group_id, "hello").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    bob.fetch_messages(&group_id).await.unwrap();
+    assert!(bob.mark_read(&group_id).await.unwrap());
+    sleep(Duration::from_millis(200)).await;
+
+    let fetched = alice.fetch_messages(&group_id).await.unwrap();
+    assert!(fetched.messages.iter().all(|m| m.seen_by.is_empty()));
+}
+
+#[tokio::test]
+async fn test_read_marker_survives_restart() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+    let db_path = std::env::temp_dir().join(format!("dialog_receipt_test_{}.db", std::process::id()));
+    let storage = StorageBackend::Sqlite { path: db_path.clone() };
+    let bob_keys = scenario.get_user("bob").unwrap().keys().clone();
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = DialogLib::new_with_storage(bob_keys.clone(), scenario.relay_url(), storage.clone())
+        .await
+        .unwrap();
+    bob.connect().await.unwrap();
+    let group_id = create_joined_group(&alice, &[&bob], "Restart").await;
+
+    alice.send_message(&group_id, "read me").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    bob.fetch_messages(&group_id).await.unwrap();
+    assert!(bob.mark_read(&group_id).await.unwrap());
+    bob.shutdown().await.unwrap();
+    drop(bob);
+
+    // The marker is remembered, so no second receipt goes out for the same message
+    let bob = DialogLib::new_with_storage(bob_keys, scenario.relay_url(), storage)
+        .await
+        .unwrap();
+    bob.connect().await.unwrap();
+    assert!(!bob.mark_read(&group_id).await.unwrap());
+
+    bob.shutdown().await.unwrap();
+    for extension in ["db", "outbox.json", "events.json", "contacts.json", "read.json"] {
+        let _ = std::fs::remove_file(db_path.with_extension(extension));
+    }
+}
@@ -0,0 +1,104 @@
+mod test_helpers;
+
+use dialog_lib::relay_lists;
+use nostr_sdk::prelude::*;
+use test_helpers::{connected_dialog, EphemeralRelay, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_key_packages_are_fetched_from_advertised_relays() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+    let bobs_relay = EphemeralRelay::start().await.expect("Failed to start second relay");
+
+    // Alice and Bob use different relays; Bob's key packages never reach Alice's
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), bobs_relay.url()).await;
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+    bob.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let report = alice.create_conversation_best_effort("Unreachable", vec![bob_pubkey]).await;
+    assert!(report.unwrap_err().to_string().contains("No key package found"));
+
+    // Bob's key package relay list, as found on Alice's relay
+    let publisher = Client::new(scenario.get_user("bob").unwrap().keys().clone());
+    publisher.add_relay(scenario.relay_url()).await.unwrap();
+    publisher.connect().await;
+    sleep(Duration::from_millis(200)).await;
+    let bobs_relay_url = RelayUrl::parse(bobs_relay.url()).unwrap();
+    publisher
+        .send_event_builder(relay_lists::key_package_relays_event(&[bobs_relay_url]))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let report = alice
+        .create_conversation_best_effort("Reachable", vec![bob_pubkey])
+        .await
+        .expect("Bob's key package should be found on his relay");
+    assert_eq!(report.added, vec![bob_pubkey]);
+    assert!(report.skipped.is_empty());
+    assert!(report.undelivered.is_empty(), "{:?}", report.undelivered);
+
+    // The welcome went to Bob's relay too, so he can join without using Alice's
+    sleep(Duration::from_millis(200)).await;
+    let invites = bob.list_pending_invites().await.unwrap();
+    let invite = invites
+        .invites
+        .iter()
+        .find(|invite| hex::encode(invite.group_id.as_slice()) == report.group_id)
+        .expect("Bob should receive the welcome on his relay");
+    assert_eq!(invite.inviter, Some(alice.get_own_pubkey().await.unwrap()));
+    bob.accept_invite(&report.group_id).await.unwrap();
+    let conversations = bob.get_conversations().await.unwrap();
+    assert!(conversations.iter().any(|c| c.id == report.group_id));
+}
+
+#[tokio::test]
+async fn test_publishing_key_packages_publishes_relay_lists() {
+    let scenario = TestScenario::new(&["alice"])
+        .await
+        .expect("Failed to create test scenario");
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let alice_pubkey = alice.get_own_pubkey().await.unwrap();
+
+    // A relay list Alice set up elsewhere must survive publishing
+    let elsewhere = RelayUrl::parse("wss://elsewhere.example").unwrap();
+    let other_client = Client::new(scenario.get_user("alice").unwrap().keys().clone());
+    other_client.add_relay(scenario.relay_url()).await.unwrap();
+    other_client.connect().await;
+    sleep(Duration::from_millis(200)).await;
+    other_client
+        .send_event_builder(relay_lists::nip65_event(std::slice::from_ref(&elsewhere)))
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(1100)).await;
+
+    alice.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let observer = Client::default();
+    observer.add_relay(scenario.relay_url()).await.unwrap();
+    observer.connect().await;
+    let lists = observer
+        .fetch_events(relay_lists::relay_lists_filter(alice_pubkey), Duration::from_secs(2))
+        .await
+        .unwrap();
+    assert!(lists.iter().any(|e| e.kind == Kind::from(relay_lists::KEY_PACKAGE_RELAYS_KIND)));
+    assert!(lists.iter().any(|e| e.kind == Kind::RelayList));
+    let own_relay = RelayUrl::parse(scenario.relay_url()).unwrap();
+    assert_eq!(relay_lists::key_package_relays(lists.iter()), vec![own_relay.clone()]);
+    assert_eq!(relay_lists::inbox_relays(lists.iter()), vec![elsewhere, own_relay]);
+
+    // Publishing again leaves the lists alone
+    alice.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let again = observer
+        .fetch_events(relay_lists::relay_lists_filter(alice_pubkey), Duration::from_secs(2))
+        .await
+        .unwrap();
+    let ids = |events: &Events| events.iter().map(|e| e.id).collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(ids(&again), ids(&lists));
+}
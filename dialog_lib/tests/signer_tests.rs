@@ -0,0 +1,89 @@
+mod test_helpers;
+
+use dialog_lib::{connect_bunker, DialogLib, StorageBackend};
+use nostr_connect::prelude::*;
+use test_helpers::TestScenario;
+use tokio::time::{sleep, Duration};
+
+/// Local stand-in for a bunker that approves every request
+#[derive(Debug)]
+struct ApproveAll;
+
+impl NostrConnectSignerActions for ApproveAll {
+    fn approve(&self, _public_key: &PublicKey, _req: &NostrConnectRequest) -> bool {
+        true
+    }
+}
+
+/// Start a NIP-46 remote signer for `user` on the scenario relay and return its bunker URI
+async fn start_bunker(user: &Keys, relay_url: &str) -> String {
+    let keys = NostrConnectKeys {
+        signer: Keys::generate(),
+        user: user.clone(),
+    };
+    let remote_signer = NostrConnectRemoteSigner::new(keys, [relay_url], None, None)
+        .expect("Failed to create remote signer");
+    let uri = remote_signer.bunker_uri().to_string();
+
+    tokio::spawn(async move {
+        let _ = remote_signer.serve(ApproveAll).await;
+    });
+
+    // Give the signer time to subscribe on the relay
+    sleep(Duration::from_millis(300)).await;
+    uri
+}
+
+#[tokio::test]
+async fn test_service_signs_with_remote_signer() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = scenario.get_user("alice").unwrap();
+    let bob = scenario.get_user("bob").unwrap();
+
+    let uri = start_bunker(alice.keys(), scenario.relay_url()).await;
+    let signer = connect_bunker(&uri, Duration::from_secs(10))
+        .await
+        .expect("Failed to connect to bunker");
+
+    let alice_dialog = DialogLib::new_with_signer(signer, scenario.relay_url(), StorageBackend::Memory)
+        .await
+        .expect("Failed to create Alice's DialogLib");
+
+    // The identity comes from the bunker, not from local keys
+    assert_eq!(alice_dialog.get_own_pubkey().await.unwrap(), alice.keys().public_key());
+
+    alice_dialog.connect()
+        .await
+        .expect("Failed to connect Alice to relay");
+
+    // Key packages are signed remotely while the MLS keys stay local
+    let event_ids = alice_dialog.publish_key_packages()
+        .await
+        .expect("Failed to publish key packages via remote signer");
+    assert!(!event_ids.is_empty());
+
+    alice_dialog.publish_simple_profile("Alice Remote")
+        .await
+        .expect("Failed to publish profile via remote signer");
+
+    sleep(Duration::from_millis(200)).await;
+
+    let bob_dialog = DialogLib::new_with_keys_and_relay(bob.keys().clone(), scenario.relay_url())
+        .await
+        .expect("Failed to create Bob's DialogLib");
+    bob_dialog.connect()
+        .await
+        .expect("Failed to connect Bob to relay");
+
+    let profile = bob_dialog.load_profile(&alice.keys().public_key())
+        .await
+        .expect("Failed to load profile")
+        .expect("Alice's profile should be on the relay");
+    assert_eq!(profile.display_name, Some("Alice Remote".to_string()));
+
+    // Backups need the secret key, which a remote signer never exposes
+    assert!(alice_dialog.export_backup("passphrase").await.is_err());
+}
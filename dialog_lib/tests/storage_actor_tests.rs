@@ -0,0 +1,51 @@
+mod test_helpers;
+
+use std::sync::Arc;
+use test_helpers::{connected_dialog, create_joined_group, next_message, TestScenario};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test]
+async fn test_fetching_history_does_not_stall_live_messages() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = Arc::new(connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await);
+    let busy = create_joined_group(&alice, &[&*bob], "Busy").await;
+    let group_id = create_joined_group(&alice, &[&*bob], "Live").await;
+    for i in 0..5 {
+        alice.send_message(&busy, &format!("history {}", i)).await.unwrap();
+    }
+
+    let mut updates = bob.events();
+    bob.subscribe().await.expect("Failed to subscribe");
+    sleep(Duration::from_millis(200)).await;
+
+    // Keep bob loading another conversation, as the TUI does when it is opened
+    let fetcher = {
+        let bob = bob.clone();
+        tokio::spawn(async move {
+            for _ in 0..5 {
+                bob.fetch_messages(&busy).await.expect("Failed to fetch messages");
+            }
+        })
+    };
+
+    for i in 0..3 {
+        alice.send_message(&group_id, &format!("live {}", i)).await.unwrap();
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        received.push(next_message(&mut updates, &group_id).await.content);
+    }
+    fetcher.await.unwrap();
+
+    received.sort();
+    assert_eq!(received, vec!["live 0", "live 1", "live 2"]);
+
+    let fetched = bob.fetch_messages(&group_id).await.unwrap();
+    assert!(fetched.processing_errors.is_empty());
+    assert_eq!(fetched.messages.len(), 3);
+}
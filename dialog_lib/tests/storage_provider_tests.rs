@@ -0,0 +1,71 @@
+mod test_helpers;
+
+use dialog_lib::{DialogLib, Keys, RealMlsService, StorageBackend};
+use nostr_mls_sqlite_storage::NostrMlsSqliteStorage;
+use std::path::Path;
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+use tokio::time::{sleep, Duration};
+
+/// A service keeping its MLS state in a provider the caller opened, and the
+/// rest in `side_path`
+async fn with_provider(keys: &Keys, relay_url: &str, db_path: &Path, side_path: &Path) -> DialogLib {
+    let dialog = DialogLib::from_service(
+        RealMlsService::builder()
+            .keys(keys.clone())
+            .relay_url(relay_url)
+            .storage_provider(NostrMlsSqliteStorage::new(db_path.to_path_buf()).unwrap())
+            .storage_backend(StorageBackend::Sqlite { path: side_path.to_path_buf() })
+            .build()
+            .await
+            .unwrap(),
+    );
+    dialog.connect().await.unwrap();
+    dialog
+}
+
+#[tokio::test]
+async fn test_custom_storage_provider_keeps_mls_state() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+    let dir = std::env::temp_dir().join(format!("dialog_provider_test_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let db_path = dir.join("provider.db");
+    let side_path = dir.join("dialog.db");
+    let bob_keys = scenario.get_user("bob").unwrap().keys().clone();
+
+    // Without a place for the outbox, ledger and system events the build fails
+    let missing_backend = RealMlsService::builder()
+        .keys(bob_keys.clone())
+        .relay_url(scenario.relay_url())
+        .storage_provider(NostrMlsSqliteStorage::new(db_path.clone()).unwrap())
+        .build()
+        .await;
+    assert!(missing_backend.is_err());
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = with_provider(&bob_keys, scenario.relay_url(), &db_path, &side_path).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Provided").await;
+
+    alice.send_message(&group_id, "stored elsewhere").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    let fetched = bob.fetch_messages(&group_id).await.unwrap();
+    assert!(fetched.processing_errors.is_empty(), "{:?}", fetched.processing_errors);
+    assert_eq!(fetched.messages.len(), 1);
+
+    // Backups only snapshot the built-in SQLite database; a provider keeps its own state
+    assert!(bob.export_backup("passphrase").await.is_err());
+    bob.shutdown().await.unwrap();
+    drop(bob);
+
+    // Reopening the provider brings the group back
+    let bob = with_provider(&bob_keys, scenario.relay_url(), &db_path, &side_path).await;
+    let conversations = bob.get_conversations().await.unwrap();
+    assert_eq!(conversations.len(), 1);
+    assert_eq!(conversations[0].group_id.as_ref(), Some(&group_id));
+    assert_eq!(conversations[0].name, "Provided");
+
+    bob.shutdown().await.unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,82 @@
+mod test_helpers;
+
+use dialog_lib::{SystemEventKind, TimelineEntry};
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+
+fn system_kinds(timeline: &[TimelineEntry]) -> Vec<SystemEventKind> {
+    timeline
+        .iter()
+        .filter_map(|entry| match entry {
+            TimelineEntry::System(event) => Some(event.kind.clone()),
+            TimelineEntry::Message(_) => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_membership_changes_appear_in_timeline() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Timeline").await;
+    let alice_pubkey = alice.get_own_pubkey().await.unwrap();
+    let bob_pubkey = bob.get_own_pubkey().await.unwrap();
+
+    alice.send_message(&group_id, "hello").await.expect("Failed to send message");
+
+    let alice_view = alice.fetch_messages(&group_id).await.unwrap();
+    assert_eq!(
+        system_kinds(&alice_view.timeline),
+        vec![
+            SystemEventKind::Created { name: "Timeline".to_string() },
+            SystemEventKind::MembersAdded { actor: Some(alice_pubkey), members: vec![bob_pubkey] },
+        ]
+    );
+    assert!(
+        matches!(alice_view.timeline.last(), Some(TimelineEntry::Message(m)) if m.content == "hello"),
+        "System events should come before the later message"
+    );
+
+    let bob_view = bob.fetch_messages(&group_id).await.unwrap();
+    assert_eq!(system_kinds(&bob_view.timeline), vec![SystemEventKind::Joined]);
+    assert_eq!(bob_view.messages.len(), 1);
+    assert_eq!(bob_view.timeline.len(), 2);
+}
+
+#[tokio::test]
+async fn test_commit_and_message_in_one_fetch_record_one_event() {
+    let scenario = TestScenario::new(&["alice", "bob", "carol"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let carol = connected_dialog(scenario.get_user("carol").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Growing").await;
+    let carol_pubkey = carol.get_own_pubkey().await.unwrap();
+
+    // Bob is offline while Carol joins and Alice writes; he then sees both at once
+    carol.publish_key_packages().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    alice.add_members(&group_id, vec![carol_pubkey]).await.expect("Failed to add Carol");
+    alice.send_message(&group_id, "hi carol").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let bob_view = bob.fetch_messages(&group_id).await.unwrap();
+    assert!(bob_view.processing_errors.is_empty(), "{:?}", bob_view.processing_errors);
+    assert_eq!(
+        system_kinds(&bob_view.timeline),
+        vec![
+            SystemEventKind::Joined,
+            SystemEventKind::MembersAdded { actor: None, members: vec![carol_pubkey] },
+        ]
+    );
+    assert_eq!(bob_view.messages.len(), 1);
+
+    // Fetching again finds nothing new to record
+    let again = bob.fetch_messages(&group_id).await.unwrap();
+    assert_eq!(system_kinds(&again.timeline).len(), 2);
+}
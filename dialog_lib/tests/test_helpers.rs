@@ -148,6 +148,64 @@ impl TestScenario {
     }
 }
 
+/// Create a DialogLib for a test user and connect it to the relay
+pub async fn connected_dialog(user: &TestUser, relay_url: &str) -> dialog_lib::DialogLib {
+    let dialog = dialog_lib::DialogLib::new_with_keys_and_relay(user.keys().clone(), relay_url)
+        .await
+        .expect("Failed to create DialogLib");
+    dialog.connect().await.expect("Failed to connect to relay");
+    dialog
+}
+
+/// Create a group owned by `creator` and have every member accept the invite
+pub async fn create_joined_group(
+    creator: &dialog_lib::DialogLib,
+    members: &[&dialog_lib::DialogLib],
+    name: &str,
+) -> dialog_lib::GroupId {
+    let mut pubkeys = Vec::new();
+    for member in members {
+        member.publish_key_packages().await.expect("Failed to publish key packages");
+        pubkeys.push(member.get_own_pubkey().await.unwrap());
+    }
+    sleep(Duration::from_millis(200)).await;
+
+    let group_hex = creator
+        .create_conversation(name, pubkeys)
+        .await
+        .expect("Failed to create group");
+    sleep(Duration::from_millis(200)).await;
+
+    for member in members {
+        member.list_pending_invites().await.expect("Failed to list invites");
+        member.accept_invite(&group_hex).await.expect("Failed to accept invite");
+    }
+
+    dialog_lib::GroupId::from_slice(&dialog_lib::hex::decode(&group_hex).unwrap())
+}
+
+/// Wait up to 5 seconds for the next message in `group_id` on an event stream
+pub async fn next_message<S>(stream: &mut S, group_id: &dialog_lib::GroupId) -> dialog_lib::Message
+where
+    S: futures::Stream<Item = dialog_lib::UiUpdate> + Unpin,
+{
+    use futures::StreamExt;
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(update) = stream.next().await {
+            if let dialog_lib::UiUpdate::NewMessage { group_id: g, message } = update {
+                if &g == group_id {
+                    return Some(message);
+                }
+            }
+        }
+        None
+    })
+    .await
+    .expect("No message within 5 seconds")
+    .expect("Stream ended")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
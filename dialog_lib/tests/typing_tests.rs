@@ -0,0 +1,56 @@
+mod test_helpers;
+
+use dialog_lib::{RealMlsService, UiUpdate};
+use test_helpers::{connected_dialog, create_joined_group, TestScenario};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, timeout, Duration};
+
+#[tokio::test]
+async fn test_typing_signals_reach_members_but_not_timeline() {
+    let scenario = TestScenario::new(&["alice", "bob"])
+        .await
+        .expect("Failed to create test scenario");
+
+    let alice = connected_dialog(scenario.get_user("alice").unwrap(), scenario.relay_url()).await;
+    let bob = connected_dialog(scenario.get_user("bob").unwrap(), scenario.relay_url()).await;
+    let group_id = create_joined_group(&alice, &[&bob], "Typing").await;
+    let alice_pubkey = alice.get_own_pubkey().await.unwrap();
+
+    let (tx, mut rx) = mpsc::channel(100);
+    bob.subscribe_to_groups(tx).await.expect("Failed to subscribe");
+    sleep(Duration::from_millis(200)).await;
+
+    // Repeated starts are rate-limited on the sender side
+    assert!(alice.send_typing(&group_id, true).await.unwrap());
+    assert!(!alice.send_typing(&group_id, true).await.unwrap());
+    assert!(alice.send_typing(&group_id, false).await.unwrap());
+    assert!(!alice.send_typing(&group_id, false).await.unwrap());
+
+    let mut started = false;
+    let mut stopped = false;
+    let _ = timeout(Duration::from_secs(5), async {
+        while let Some(update) = rx.recv().await {
+            match update {
+                UiUpdate::Typing { group_id: g, pubkey } if g == group_id && pubkey == alice_pubkey => started = true,
+                UiUpdate::TypingStopped { group_id: g, pubkey } if g == group_id && pubkey == alice_pubkey => stopped = true,
+                _ => {}
+            }
+            if started && stopped {
+                break;
+            }
+        }
+    })
+    .await;
+    assert!(started, "Bob should see Alice typing");
+    assert!(stopped, "Bob should see Alice stop typing");
+
+    let fetched = bob.fetch_messages(&group_id).await.unwrap();
+    assert!(fetched.messages.is_empty(), "Typing signals must not appear as messages");
+
+    // Typing is ephemeral: it never reaches the MLS message store on either side
+    for dialog in [&alice, &bob] {
+        let service = dialog.service().as_any().downcast_ref::<RealMlsService>().unwrap();
+        let stored = service.store().get_messages(&group_id).await.unwrap();
+        assert!(stored.is_empty(), "Typing signals must not be stored");
+    }
+}
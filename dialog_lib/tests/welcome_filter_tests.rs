@@ -0,0 +1,108 @@
+use dialog_lib::{DialogConfig, DialogLib, StorageBackend};
+use futures::{SinkExt, StreamExt};
+use nostr::prelude::*;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// Local relay stand-in that matches kinds and authors but ignores tag
+/// conditions, like relays that do not index `#p`
+async fn start_loose_relay() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let events: Arc<Mutex<Vec<Event>>> = Arc::default();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve_connection(stream, events.clone()));
+        }
+    });
+    url
+}
+
+async fn serve_connection(stream: TcpStream, events: Arc<Mutex<Vec<Event>>>) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut stream) = ws.split();
+
+    while let Some(Ok(message)) = stream.next().await {
+        let WsMessage::Text(text) = message else {
+            continue;
+        };
+        let Ok(Value::Array(parts)) = serde_json::from_str::<Value>(&text) else {
+            continue;
+        };
+
+        let mut replies: Vec<Value> = Vec::new();
+        match parts.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                let Ok(event) = serde_json::from_value::<Event>(parts[1].clone()) else {
+                    continue;
+                };
+                replies.push(json!(["OK", event.id.to_hex(), true, ""]));
+                events.lock().unwrap().push(event);
+            }
+            Some("REQ") => {
+                let subscription_id = parts[1].clone();
+                let filters: Vec<Filter> = parts[2..]
+                    .iter()
+                    .filter_map(|f| serde_json::from_value(f.clone()).ok())
+                    .collect();
+                let events = events.lock().unwrap();
+                for event in events.iter().filter(|e| {
+                    filters.iter().any(|f| {
+                        f.kinds.as_ref().is_none_or(|kinds| kinds.contains(&e.kind))
+                            && f.authors.as_ref().is_none_or(|authors| authors.contains(&e.pubkey))
+                    })
+                }) {
+                    replies.push(json!(["EVENT", subscription_id, event]));
+                }
+                replies.push(json!(["EOSE", subscription_id]));
+            }
+            _ => {}
+        }
+
+        for reply in replies {
+            if sink.send(WsMessage::text(reply.to_string())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_foreign_welcomes_are_skipped_and_counted() {
+    let relay_url = start_loose_relay().await;
+
+    let alice = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay_url).await.unwrap();
+    let carol = DialogLib::new_with_keys_and_relay(Keys::generate(), &relay_url).await.unwrap();
+    let mut config = DialogConfig::with_relay_url(&relay_url);
+    config.plain_welcomes = true;
+    let bob = DialogLib::new_with_config(Keys::generate(), &relay_url, StorageBackend::Memory, config)
+        .await
+        .unwrap();
+    for dialog in [&alice, &bob, &carol] {
+        dialog.connect().await.unwrap();
+    }
+
+    // Alice invites Carol; the relay hands Carol's welcomes to Bob as well
+    carol.publish_key_packages().await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    alice
+        .create_conversation("Not for Bob", vec![carol.get_own_pubkey().await.unwrap()])
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let invites = bob.list_pending_invites().await.unwrap();
+    assert!(invites.invites.is_empty());
+    assert!(
+        invites.processing_errors.is_empty(),
+        "Foreign welcomes should be skipped, not attempted: {:?}",
+        invites.processing_errors
+    );
+    // Carol's gift wrap and her plain welcome
+    assert_eq!(bob.foreign_welcomes_skipped().await, 2);
+}
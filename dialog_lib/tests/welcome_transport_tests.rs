@@ -0,0 +1,101 @@
+mod test_helpers;
+
+use dialog_lib::{DialogConfig, DialogLib, RealMlsService, StorageBackend, WelcomeTransport};
+use nostr_sdk::prelude::*;
+use test_helpers::TestScenario;
+use tokio::time::{sleep, Duration};
+
+struct Delivery {
+    /// Invites seen by a receiver that only reads gift wraps (the default)
+    gift_wrap_reader: usize,
+    /// Invites seen by a receiver that also reads plain welcomes
+    plain_reader: usize,
+    /// Plain kind 444 events visible to anyone on the relay
+    public_welcomes: usize,
+}
+
+/// Alice invites Bob (default settings) and Carol (plain welcomes enabled)
+/// using `transport`, then reports what arrived where
+async fn deliver(transport: WelcomeTransport) -> Delivery {
+    let scenario = TestScenario::new(&["alice", "bob", "carol"])
+        .await
+        .expect("Failed to create test scenario");
+    let relay_url = scenario.relay_url();
+
+    let alice = DialogLib::from_service(
+        RealMlsService::builder()
+            .keys(scenario.get_user("alice").unwrap().keys().clone())
+            .relay_url(relay_url)
+            .welcome_transport(transport)
+            .build()
+            .await
+            .unwrap(),
+    );
+    let bob = DialogLib::new_with_keys_and_relay(scenario.get_user("bob").unwrap().keys().clone(), relay_url)
+        .await
+        .unwrap();
+    let mut config = DialogConfig::with_relay_url(relay_url);
+    config.plain_welcomes = true;
+    let carol = DialogLib::new_with_config(
+        scenario.get_user("carol").unwrap().keys().clone(),
+        relay_url,
+        StorageBackend::Memory,
+        config,
+    )
+    .await
+    .unwrap();
+
+    let mut members = Vec::new();
+    for dialog in [&alice, &bob, &carol] {
+        dialog.connect().await.unwrap();
+    }
+    for dialog in [&bob, &carol] {
+        dialog.publish_key_packages().await.unwrap();
+        members.push(dialog.get_own_pubkey().await.unwrap());
+    }
+    sleep(Duration::from_millis(200)).await;
+    alice.create_conversation("Transport", members).await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    let observer = Client::default();
+    observer.add_relay(relay_url).await.unwrap();
+    observer.connect().await;
+    let public_welcomes = observer
+        .fetch_events(Filter::new().kind(Kind::MlsWelcome), Duration::from_secs(2))
+        .await
+        .unwrap()
+        .len();
+
+    Delivery {
+        gift_wrap_reader: bob.list_pending_invites().await.unwrap().invites.len(),
+        plain_reader: carol.list_pending_invites().await.unwrap().invites.len(),
+        public_welcomes,
+    }
+}
+
+#[tokio::test]
+async fn test_gift_wrap_only_is_the_private_default() {
+    assert_eq!(WelcomeTransport::default(), WelcomeTransport::GiftWrapOnly);
+
+    let delivery = deliver(WelcomeTransport::GiftWrapOnly).await;
+    assert_eq!(delivery.gift_wrap_reader, 1);
+    assert_eq!(delivery.plain_reader, 1);
+    assert_eq!(delivery.public_welcomes, 0, "No public trace of the invite");
+}
+
+#[tokio::test]
+async fn test_both_reaches_everyone_once() {
+    let delivery = deliver(WelcomeTransport::Both).await;
+    assert_eq!(delivery.gift_wrap_reader, 1);
+    // Carol receives two copies but keeps a single invite
+    assert_eq!(delivery.plain_reader, 1);
+    assert_eq!(delivery.public_welcomes, 2);
+}
+
+#[tokio::test]
+async fn test_plain_only_needs_plain_welcome_support() {
+    let delivery = deliver(WelcomeTransport::PlainOnly).await;
+    assert_eq!(delivery.gift_wrap_reader, 0);
+    assert_eq!(delivery.plain_reader, 1);
+    assert_eq!(delivery.public_welcomes, 2);
+}
@@ -2,9 +2,14 @@ use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use tui_textarea::TextArea;
 use tokio::sync::mpsc;
 use ratatui::widgets::ListState;
-use dialog_lib::{DialogLib, Contact, Conversation, ConnectionStatus, AppMode, AppResult, ToBech32, hex, GroupId, UiUpdate, PendingInvite};
+use dialog_lib::{DialogLib, Contact, Conversation, ConnectionStatus, AppMode, AppResult, ToBech32, hex, GroupId, UiUpdate, PendingInvite, DeliveryInfo, DeliveryStatus, TimelineEntry, InviteDecision, PublicKey};
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
 use chrono::{DateTime, Local};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long a typing indicator stays visible without a fresh signal
+const TYPING_DISPLAY_DURATION: Duration = Duration::from_secs(6);
 
 /// Helper function to format current timestamp in IRC style
 fn format_timestamp() -> String {
@@ -12,6 +17,36 @@ fn format_timestamp() -> String {
     format!("[{}]", now.format("%H:%M"))
 }
 
+/// Suffix showing a delivery state
+fn status_marker(status: &DeliveryStatus) -> &'static str {
+    match status {
+        DeliveryStatus::Pending => " ⏳",
+        DeliveryStatus::Sent => " ✓",
+        DeliveryStatus::Failed(_) => " ✗",
+    }
+}
+
+/// Suffix showing the delivery state of one of our own messages
+fn delivery_marker(delivery: Option<&DeliveryInfo>) -> &'static str {
+    delivery.map(|d| status_marker(&d.status)).unwrap_or("")
+}
+
+/// Short presence text for a contact, e.g. "online" or "seen 5m ago"
+pub fn presence_label(contact: &Contact) -> String {
+    if contact.online {
+        return "online".to_string();
+    }
+    let Some(last_seen) = contact.last_seen else {
+        return "offline".to_string();
+    };
+    let ago = (Local::now().timestamp() - last_seen).max(0);
+    match ago {
+        0..=3599 => format!("seen {}m ago", ago / 60),
+        3600..=86399 => format!("seen {}h ago", ago / 3600),
+        _ => format!("seen {}d ago", ago / 86400),
+    }
+}
+
 
 #[derive(Debug, Clone)]
 pub enum MessageType {
@@ -65,6 +100,7 @@ pub struct App {
     pub contact_count: usize,
     pub pending_invites: usize,
     pub pending_invites_list: Vec<PendingInvite>,
+    pub outbox_pending: usize,
     pub messages: Vec<StatusMessage>,
     pub scroll_offset: usize,
     pub contacts: Vec<Contact>,
@@ -92,6 +128,12 @@ pub struct App {
     // Sidebar state
     pub show_sidebar: bool,
     pub sidebar_selection: usize,
+    
+    // Where per-identity settings are saved (None in ephemeral mode)
+    pub config_path: Option<PathBuf>,
+    
+    // Members currently typing: (group, member, last signal)
+    pub typing: Vec<(GroupId, dialog_lib::PublicKey, Instant)>,
 }
 
 impl App {
@@ -110,6 +152,7 @@ impl App {
         let connection_status = dialog_lib.get_connection_status().await.unwrap_or(ConnectionStatus::Disconnected);
         let pending_invites = dialog_lib.get_pending_invites_count().await.unwrap_or(0);
         let pending_invites_list = Vec::new(); // Will be populated when needed
+        let outbox_pending = dialog_lib.outbox_pending_count().await;
         let active_conversation = dialog_lib.get_active_conversation().await.unwrap_or(None);
 
         // Don't auto-start subscription - let user connect manually
@@ -122,6 +165,7 @@ impl App {
             contact_count: contacts.len(),
             pending_invites,
             pending_invites_list,
+            outbox_pending,
             messages: Vec::new(),
             scroll_offset: 0,
             contacts,
@@ -149,6 +193,9 @@ impl App {
             // Sidebar state
             show_sidebar: false,
             sidebar_selection: 0,
+            
+            config_path: None,
+            typing: Vec::new(),
         };
 
         // Add welcome messages
@@ -195,6 +242,9 @@ impl App {
         if let Ok(invites) = self.dialog_lib.get_pending_invites_count().await {
             self.pending_invites = invites;
         }
+
+        // Refresh outgoing messages waiting for a relay
+        self.outbox_pending = self.dialog_lib.outbox_pending_count().await;
     }
 
     pub async fn handle_key(&mut self, key: KeyEvent) -> AppResult {
@@ -228,6 +278,9 @@ impl App {
             }
             KeyCode::Esc => {
                 if self.mode != AppMode::Normal {
+                    if self.mode == AppMode::MessageInput {
+                        self.notify_typing(false).await;
+                    }
                     self.mode = AppMode::Normal;
                     self.text_area.delete_line_by_head();
                     self.text_area.delete_line_by_end();
@@ -339,12 +392,31 @@ impl App {
             // Check for @ search in message input mode
             if self.mode == AppMode::MessageInput {
                 self.detect_at_search(&current_text);
+                self.notify_typing(true).await;
+            } else if current_text.is_empty() {
+                self.notify_typing(false).await;
             }
         }
 
         AppResult::Continue
     }
 
+    /// Group ID of the active conversation, if any
+    fn active_group_id(&self) -> Option<GroupId> {
+        let active_id = self.active_conversation.as_ref()?;
+        self.conversations
+            .iter()
+            .find(|c| c.id == *active_id)
+            .and_then(|c| c.group_id.clone())
+    }
+
+    /// Let the active conversation know we are (or stopped) typing; the library rate-limits
+    async fn notify_typing(&mut self, typing: bool) {
+        if let Some(group_id) = self.active_group_id() {
+            let _ = self.dialog_lib.send_typing(&group_id, typing).await;
+        }
+    }
+
     fn detect_at_search(&mut self, input: &str) {
         // Find the last @ in the current input
         if let Some(at_pos) = input.rfind('@') {
@@ -660,11 +732,27 @@ impl App {
                             ));
                         }
                         
-                        match self.dialog_lib.create_conversation(&group_name, selected_contacts).await {
-                            Ok(group_id) => {
+                        match self.dialog_lib.create_conversation_best_effort(&group_name, selected_contacts).await {
+                            Ok(report) => {
+                                let group_id = report.group_id;
                                 self.add_message_with_type(&format!("✅ Group '{}' created successfully!", group_name), MessageType::Success);
                                 self.add_message(&format!("Group ID: {}", group_id));
-                                self.add_message_with_type("✅ Welcome messages sent to all participants", MessageType::Success);
+                                if report.skipped.is_empty() && report.undelivered.is_empty() {
+                                    self.add_message_with_type("✅ Welcome messages sent to all participants", MessageType::Success);
+                                } else {
+                                    self.add_message_with_type(
+                                        &format!("✅ Welcome messages sent to {} participant(s)", report.added.len() - report.undelivered.len()),
+                                        MessageType::Success,
+                                    );
+                                    for (pubkey, reason) in &report.skipped {
+                                        let name = self.contact_name(pubkey);
+                                        self.add_message_with_type(&format!("⚠️  Skipped {}: {}", name, reason), MessageType::Warning);
+                                    }
+                                    for (pubkey, error) in &report.undelivered {
+                                        let name = self.contact_name(pubkey);
+                                        self.add_message_with_type(&format!("⚠️  Added {} but their welcome was not sent: {}", name, error), MessageType::Warning);
+                                    }
+                                }
                                 self.add_message("");
                                 self.add_message("⚠️  EPHEMERAL MODE: Participants must accept invites during THIS session");
                                 self.add_message("    (Their key packages are only valid until they restart)");
@@ -697,7 +785,7 @@ impl App {
                             // Accept the invite
                             let group_id = hex::encode(invite.group_id.as_slice());
                             self.add_message(&format!("Accepting invite for group {}...", invite.group_name));
-                            match self.dialog_lib.accept_invite(&group_id).await {
+                            match self.dialog_lib.accept_invite(&invite.group_id).await {
                                 Ok(()) => {
                                     self.add_message_with_type("✅ Successfully joined group!", MessageType::Success);
                                     self.add_message("The group should now appear in your conversations.");
@@ -784,12 +872,17 @@ impl App {
                 self.add_message("/keypackage - Publish your key package (required for receiving invites)");
                 self.add_message("/refresh-keys - Publish fresh key packages (replaces old ones)");
                 self.add_message("/create <name> - Create a group (with interactive contact selection)");
+                self.add_message("/dm <contact or pubkey> - Open your direct conversation with someone");
                 self.add_message("/invites - Open sidebar to view and accept pending invitations");
+                self.add_message("/block <pubkey> - Ignore all invitations from someone; /unblock to undo");
                 self.add_message("");
                 self.add_message("Conversations:");
                 self.add_message("/switch - Switch to a conversation (interactive)");
                 self.add_message("/info - Show details about the current conversation");
                 self.add_message("/fetch - Fetch and display messages in the active conversation");
+                self.add_message("/receipts [on|off] - Show or change whether you send read receipts");
+                self.add_message("/pins - Show pinned messages in the active conversation");
+                self.add_message("/pin <id or text> - Pin a message for everyone (admins only); /unpin to remove");
                 self.add_message("");
                 self.add_message("Features:");
                 self.add_message("  @ search - Type '@' followed by contact name for fuzzy search");
@@ -888,7 +981,10 @@ impl App {
                     // Clone contacts to avoid borrowing issues
                     let contacts = self.contacts.clone();
                     for (i, contact) in contacts.iter().enumerate() {
-                        let status = if contact.online { "online" } else { "offline" };
+                        let mut status = presence_label(contact);
+                        if let Some(text) = &contact.status {
+                            status.push_str(&format!(", \"{}\"", text));
+                        }
                         let pubkey_display = contact.pubkey.to_bech32().unwrap_or_else(|_| contact.pubkey.to_hex()[..16].to_string());
                         self.add_message(&format!("  {}: {} ({}) - {}", i + 1, contact.name, status, pubkey_display));
                     }
@@ -1052,6 +1148,9 @@ impl App {
                 self.add_message(&format!("  Contacts: {}", self.contacts.len()));
                 self.add_message(&format!("  Conversations: {}", self.conversations.len()));
                 self.add_message(&format!("  Pending invites: {}", self.pending_invites));
+                self.add_message(&format!("  Unsent messages: {}", self.outbox_pending));
+                let receipts = if self.dialog_lib.config().await.read_receipts { "on" } else { "off" };
+                self.add_message(&format!("  Read receipts: {}", receipts));
                 self.add_message(&format!("  Total messages: {}", self.messages.len()));
                 
                 // Add pubkey information
@@ -1181,6 +1280,150 @@ impl App {
                     self.add_message_with_type("No active conversation. Use /switch to select one.", MessageType::Warning);
                 }
             }
+            "/pins" => {
+                let Some(group_id) = self.active_group_id() else {
+                    self.add_message_with_type("No active conversation. Use /switch to select one.", MessageType::Error);
+                    return;
+                };
+                match self.dialog_lib.pinned_messages(&group_id).await {
+                    Ok(pins) if pins.is_empty() => self.add_message("No pinned messages in this conversation."),
+                    Ok(pins) => {
+                        self.add_message(&format!("📌 {} pinned message(s):", pins.len()));
+                        for msg in pins {
+                            let id = msg.id.as_deref().map(|id| &id[..8]).unwrap_or("--------");
+                            let sender = self.contact_name(&msg.sender);
+                            self.add_message(&format!("  [{}] {}: {}", id, sender, msg.body));
+                        }
+                    }
+                    Err(e) => self.add_message_with_type(&format!("❌ Failed to load pins: {}", e), MessageType::Error),
+                }
+            }
+            "/pin" | "/unpin" => {
+                let pinned = parts[0] == "/pin";
+                let query = parts[1..].join(" ");
+                if query.is_empty() {
+                    self.add_message_with_type(&format!("Usage: {} <message id prefix or text>", parts[0]), MessageType::Error);
+                    return;
+                }
+                let Some(group_id) = self.active_group_id() else {
+                    self.add_message_with_type("No active conversation. Use /switch to select one.", MessageType::Error);
+                    return;
+                };
+
+                // Match an ID prefix first, otherwise the newest message containing the text
+                let messages = match self.dialog_lib.fetch_messages(&group_id).await {
+                    Ok(result) => result.messages,
+                    Err(e) => {
+                        self.add_message_with_type(&format!("❌ Failed to fetch messages: {}", e), MessageType::Error);
+                        return;
+                    }
+                };
+                let target = messages
+                    .iter()
+                    .find(|m| m.id.as_deref().is_some_and(|id| id.starts_with(&query)))
+                    .or_else(|| messages.iter().rev().find(|m| m.content.contains(&query)))
+                    .and_then(|m| m.id.clone());
+                let Some(message_id) = target else {
+                    self.add_message_with_type(&format!("No message matches '{}'", query), MessageType::Error);
+                    return;
+                };
+
+                match self.dialog_lib.set_pinned(&group_id, &message_id, pinned).await {
+                    Ok(()) if pinned => self.add_message_with_type(&format!("📌 Pinned message {}", &message_id[..8]), MessageType::Success),
+                    Ok(()) => self.add_message_with_type(&format!("Unpinned message {}", &message_id[..8]), MessageType::Success),
+                    Err(e) => self.add_message_with_type(&format!("❌ {}", e), MessageType::Error),
+                }
+            }
+            "/receipts" => {
+                let enabled = match parts.get(1).copied() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    None => {
+                        let receipts = if self.dialog_lib.config().await.read_receipts { "on" } else { "off" };
+                        self.add_message(&format!("Read receipts are {}. Use /receipts on|off to change.", receipts));
+                        return;
+                    }
+                    Some(_) => {
+                        self.add_message_with_type("Usage: /receipts [on|off]", MessageType::Error);
+                        return;
+                    }
+                };
+
+                if let Err(e) = self.dialog_lib.set_read_receipts(enabled).await {
+                    self.add_message_with_type(&format!("❌ Failed to change read receipts: {}", e), MessageType::Error);
+                    return;
+                }
+                if let Some(path) = self.config_path.clone() {
+                    if let Err(e) = self.dialog_lib.config().await.save(&path) {
+                        self.add_message_with_type(&format!("⚠️  Setting applied but not saved: {}", e), MessageType::Warning);
+                    }
+                }
+
+                if enabled {
+                    self.add_message_with_type("Read receipts on - group members will see which messages you have read", MessageType::Success);
+                } else {
+                    self.add_message_with_type("Read receipts off - you no longer tell others what you have read", MessageType::Success);
+                }
+            }
+            "/dm" => {
+                let Some(target) = parts.get(1).copied() else {
+                    self.add_message_with_type("Usage: /dm <contact name|pubkey>", MessageType::Error);
+                    return;
+                };
+                let pubkey = self
+                    .contacts
+                    .iter()
+                    .find(|c| c.name.eq_ignore_ascii_case(target))
+                    .map(|c| c.pubkey)
+                    .or_else(|| PublicKey::parse(target).ok());
+                let Some(pubkey) = pubkey else {
+                    self.add_message_with_type(&format!("❌ No contact or pubkey '{}'", target), MessageType::Error);
+                    return;
+                };
+
+                match self.dialog_lib.open_direct_conversation(&pubkey).await {
+                    Ok(conv) => {
+                        self.refresh_data().await;
+                        if let Ok(()) = self.dialog_lib.switch_conversation(&conv.id).await {
+                            self.active_conversation = Some(conv.id.clone());
+                            self.add_message(&format!("📍 Talking to {}", conv.name));
+                        }
+                    }
+                    Err(e) => self.add_message_with_type(&format!("❌ {}", e), MessageType::Error),
+                }
+            }
+            "/block" | "/unblock" => {
+                let block = parts[0] == "/block";
+                let Some(pubkey) = parts.get(1).and_then(|arg| PublicKey::parse(arg).ok()) else {
+                    self.add_message_with_type(&format!("Usage: {} <pubkey>", parts[0]), MessageType::Error);
+                    return;
+                };
+
+                let result = if block {
+                    self.dialog_lib.block_inviter(pubkey).await
+                } else {
+                    self.dialog_lib.unblock_inviter(pubkey).await
+                };
+                if let Err(e) = result {
+                    self.add_message_with_type(&format!("❌ Failed to change invite policy: {}", e), MessageType::Error);
+                    return;
+                }
+                if let Some(path) = self.config_path.clone() {
+                    if let Err(e) = self.dialog_lib.config().await.save(&path) {
+                        self.add_message_with_type(&format!("⚠️  Setting applied but not saved: {}", e), MessageType::Warning);
+                    }
+                }
+
+                let name = self.contact_name(&pubkey);
+                if block {
+                    self.add_message_with_type(&format!("🚫 Invitations from {} will be ignored", name), MessageType::Success);
+                    if let Ok(count) = self.dialog_lib.get_pending_invites_count().await {
+                        self.pending_invites = count;
+                    }
+                } else {
+                    self.add_message_with_type(&format!("Invitations from {} are allowed again", name), MessageType::Success);
+                }
+            }
             "/fetch" => {
                 // Check if we're connected first
                 if self.connection_status != ConnectionStatus::Connected {
@@ -1205,13 +1448,35 @@ impl App {
             if let Some(conv) = self.conversations.iter().find(|c| c.id == *active_id).cloned() {
                 // Show user message immediately with timestamp
                 self.add_message(&format!("{} You: {}", format_timestamp(), message));
+                let message_line = self.messages.len() - 1;
                 
                 // Send the message via the dialog library
                 if let Ok(bytes) = hex::decode(&conv.id) {
                     let group_id = GroupId::from_slice(&bytes);
                     match self.dialog_lib.send_message(&group_id, message).await {
-                        Ok(()) => {
-                            // Message sent successfully - no need to display confirmation
+                        Ok(report) => {
+                            // Mark the line with its delivery state
+                            if let Some(line) = self.messages.get_mut(message_line) {
+                                line.content.push_str(status_marker(&report.status));
+                            }
+
+                            for (relay, _, reason) in report.rejected() {
+                                self.add_message_with_type(&format!("  {} rejected the message: {}", relay, reason), MessageType::Warning);
+                            }
+
+                            self.outbox_pending = self.dialog_lib.outbox_pending_count().await;
+                            match &report.status {
+                                DeliveryStatus::Pending if report.relays.is_empty() => {
+                                    self.add_message_with_type("⏳ Offline - message queued and will be sent when connected", MessageType::Info);
+                                }
+                                DeliveryStatus::Pending => {
+                                    self.add_message_with_type("⏳ No relay accepted the message yet, retrying in the background", MessageType::Info);
+                                }
+                                DeliveryStatus::Failed(reason) => {
+                                    self.add_message_with_type(&format!("✗ Message not delivered: {}", reason), MessageType::Error);
+                                }
+                                DeliveryStatus::Sent => {}
+                            }
                         }
                         Err(e) => {
                             self.add_message(&format!("Error sending message: {}", e));
@@ -1228,6 +1493,14 @@ impl App {
         }
     }
 
+    /// Contact name for a pubkey, or a truncated pubkey for strangers
+    fn contact_name(&self, pubkey: &dialog_lib::PublicKey) -> String {
+        match self.contacts.iter().find(|c| &c.pubkey == pubkey) {
+            Some(contact) => contact.name.clone(),
+            None => format!("{}...", &pubkey.to_hex()[0..8]),
+        }
+    }
+
     pub fn add_message(&mut self, message: &str) {
         self.add_message_with_type(message, MessageType::Normal);
     }
@@ -1362,28 +1635,46 @@ impl App {
                             }
                             
                             // Then show the messages
-                            if result.messages.is_empty() {
+                            if result.timeline.is_empty() {
                                 self.add_message("No messages in this conversation yet.");
                             } else {
                                 self.add_message(&format!("Fetched {} messages:", result.messages.len()));
                                 self.add_message("");
                                 
-                                for msg in result.messages {
+                                let own_pubkey = self.dialog_lib.get_own_pubkey().await.ok();
+                                for entry in result.timeline {
+                                    let msg = match entry {
+                                        TimelineEntry::Message(msg) => msg,
+                                        TimelineEntry::System(event) => {
+                                            let text = event.describe(|pk| {
+                                                if own_pubkey.as_ref() == Some(pk) { "You".to_string() } else { self.contact_name(pk) }
+                                            });
+                                            self.add_message_with_type(&format!("{} * {}", format_timestamp(), text), MessageType::Info);
+                                            continue;
+                                        }
+                                    };
                                     // Get sender name from contacts or use truncated pubkey
-                                    let own_pubkey = self.dialog_lib.get_own_pubkey().await.ok();
                                     let sender_name = if own_pubkey.as_ref() == Some(&msg.sender) {
                                         "You".to_string()
-                                    } else if let Some(contact) = self.contacts.iter().find(|c| c.pubkey == msg.sender) {
-                                        contact.name.clone()
                                     } else {
-                                        format!("{}...", &msg.sender.to_hex()[0..8])
+                                        self.contact_name(&msg.sender)
+                                    };
+                                    
+                                    let seen = if sender_name == "You" && !msg.seen_by.is_empty() {
+                                        let names: Vec<String> = msg.seen_by.iter().map(|pk| self.contact_name(pk)).collect();
+                                        format!(" (seen by {})", names.join(", "))
+                                    } else {
+                                        String::new()
                                     };
                                     
-                                    self.add_message(&format!("{} {}: {}", format_timestamp(), sender_name, msg.content));
+                                    self.add_message(&format!("{} {}: {}{}{}", format_timestamp(), sender_name, msg.body, delivery_marker(msg.delivery.as_ref()), seen));
                                 }
                                 
                                 self.add_message("");
                                 self.add_message("--- End of messages ---");
+                                
+                                // Displaying the conversation counts as reading it
+                                let _ = self.dialog_lib.mark_read(&group_id).await;
                             }
                             Ok(())
                         }
@@ -1463,6 +1754,9 @@ impl App {
         for update in updates {
             match update {
                 UiUpdate::NewMessage { group_id, message } => {
+                    // A new message usually ends whoever was typing it
+                    self.typing.retain(|(g, pk, _)| !(g == &group_id && pk == &message.sender));
+
                     // Check if this message is for the active conversation
                     if let Some(ref active_id) = self.active_conversation {
                         if let Some(conv) = self.conversations.iter().find(|c| c.id == *active_id) {
@@ -1479,7 +1773,7 @@ impl App {
                                     };
                                     
                                     // Add the message to the display with timestamp
-                                    self.add_message(&format!("{} {}: {}", format_timestamp(), sender_name, message.content));
+                                    self.add_message(&format!("{} {}: {}", format_timestamp(), sender_name, message.body));
                                 }
                             }
                         }
@@ -1492,25 +1786,82 @@ impl App {
                         self.add_message("📨 New group invitation received! Use /invites to view.");
                     }
                 }
+                UiUpdate::InviteDecided { inviter, invite, decision } => {
+                    // Pending invites are announced through NewInvite
+                    let name = self.contact_name(&inviter);
+                    match (decision, invite) {
+                        (InviteDecision::Accepted, Some(invite)) => {
+                            self.refresh_data().await;
+                            self.add_message_with_type(
+                                &format!("✅ Joined '{}' - invited by your contact {}", invite.group_name, name),
+                                MessageType::Success,
+                            );
+                        }
+                        (decision @ InviteDecision::Dropped(_), _) => {
+                            self.add_message_with_type(&format!("🚫 Invitation from {} {}", name, decision), MessageType::Warning);
+                        }
+                        _ => {}
+                    }
+                }
                 UiUpdate::ConnectionStatus(status) => {
                     self.connection_status = status;
                 }
-                UiUpdate::GroupStateChange { .. } => {
-                    // Could refresh conversations here if needed
+                UiUpdate::GroupStateChange { group_id, .. } => {
+                    // Membership or metadata changed; names and member counts may be stale
+                    self.refresh_data().await;
+                    let is_active = self.active_conversation.as_ref().is_some_and(|active_id| {
+                        self.conversations.iter().any(|c| c.id == *active_id && c.group_id.as_ref() == Some(&group_id))
+                    });
+                    if is_active {
+                        self.add_message_with_type("* Group membership or details changed. Use /fetch to see the timeline.", MessageType::Info);
+                    }
                 }
-                UiUpdate::GroupHasNewMessages { group_id } => {
-                    // Check if this is for the active conversation
-                    if let Some(ref active_id) = self.active_conversation {
-                        if let Some(conv) = self.conversations.iter().find(|c| c.id == *active_id) {
-                            if let Some(ref conv_group_id) = conv.group_id {
-                                if conv_group_id == &group_id {
-                                    // Fetch messages for the active conversation
-                                    if let Err(e) = self.fetch_active_conversation_messages().await {
-                                        self.add_message_with_type(&format!("❌ Error fetching messages: {}", e), MessageType::Error);
-                                    }
-                                }
-                            }
+                UiUpdate::ReadReceipt { group_id, reader, .. } => {
+                    let is_active = self.active_conversation.as_ref().is_some_and(|active_id| {
+                        self.conversations.iter().any(|c| c.id == *active_id && c.group_id.as_ref() == Some(&group_id))
+                    });
+                    if is_active {
+                        let name = self.contact_name(&reader);
+                        self.add_message_with_type(&format!("👁 Seen by {}", name), MessageType::Info);
+                    }
+                }
+                UiUpdate::Typing { group_id, pubkey } => {
+                    self.typing.retain(|(g, pk, _)| !(g == &group_id && pk == &pubkey));
+                    self.typing.push((group_id, pubkey, Instant::now()));
+                }
+                UiUpdate::TypingStopped { group_id, pubkey } => {
+                    self.typing.retain(|(g, pk, _)| !(g == &group_id && pk == &pubkey));
+                }
+                UiUpdate::PresenceChanged { pubkey, online, last_seen } => {
+                    if let Some(contact) = self.contacts.iter_mut().find(|c| c.pubkey == pubkey) {
+                        contact.online = online;
+                        contact.last_seen = last_seen;
+                    }
+                }
+                UiUpdate::PinsChanged { group_id } => {
+                    if self.active_group_id().as_ref() == Some(&group_id) {
+                        self.add_message_with_type("📌 Pinned messages changed. Use /pins to view them.", MessageType::Info);
+                    }
+                }
+                // Handled through the `NewMessage` it follows
+                #[allow(deprecated)]
+                UiUpdate::GroupHasNewMessages { .. } => {}
+                UiUpdate::DeliveryUpdate { delivery, .. } => {
+                    self.outbox_pending = self.dialog_lib.outbox_pending_count().await;
+                    match &delivery.status {
+                        DeliveryStatus::Sent if delivery.attempts > 1 => {
+                            self.add_message_with_type(
+                                &format!("✓ Queued message delivered to {} relay(s)", delivery.accepted_by.len()),
+                                MessageType::Success,
+                            );
                         }
+                        DeliveryStatus::Failed(reason) => {
+                            self.add_message_with_type(
+                                &format!("✗ Message could not be delivered after {} attempts: {}", delivery.attempts, reason),
+                                MessageType::Error,
+                            );
+                        }
+                        _ => {}
                     }
                 }
             }
@@ -1583,6 +1934,28 @@ impl App {
             String::new()
         };
 
+        let typing_info = match self.active_group_id() {
+            Some(group_id) => {
+                let names: Vec<String> = self.typing
+                    .iter()
+                    .filter(|(g, _, at)| g == &group_id && at.elapsed() < TYPING_DISPLAY_DURATION)
+                    .map(|(_, pk, _)| self.contact_name(pk))
+                    .collect();
+                match names.len() {
+                    0 => String::new(),
+                    1 => format!("{} is typing...", names[0]),
+                    _ => format!("{} are typing...", names.join(", ")),
+                }
+            }
+            None => String::new(),
+        };
+
+        let outbox_info = if self.outbox_pending > 0 {
+            format!("{} unsent", self.outbox_pending)
+        } else {
+            String::new()
+        };
+
         let connection_info = match self.connection_status {
             ConnectionStatus::Connected => "Connected",
             ConnectionStatus::Connecting => "Connecting...",
@@ -1592,8 +1965,10 @@ impl App {
         let parts: Vec<&str> = vec![
             input_context,
             &conversation_info,
+            &typing_info,
             &contact_info,
             &pending_info,
+            &outbox_info,
             connection_info,
         ].into_iter().filter(|s| !s.is_empty()).collect();
 
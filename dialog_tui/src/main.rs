@@ -19,7 +19,7 @@ mod ui;
 mod theme;
 
 use app::App;
-use dialog_lib::{AppResult, Keys};
+use dialog_lib::{AppResult, Keys, Keystore};
 
 fn find_and_load_env() {
     // First try the standard dotenv() which looks for .env in current dir
@@ -56,6 +56,15 @@ fn get_data_dir() -> Result<PathBuf> {
     }
 }
 
+/// Read a passphrase from DIALOG_PASSPHRASE, or prompt for it (without echo) before the UI starts
+fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = env::var("DIALOG_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+
+    Ok(rpassword::prompt_password(format!("{}: ", prompt))?)
+}
+
 fn get_secret_key(key_arg: &str) -> Result<String> {
     match key_arg {
         "bob" => {
@@ -68,13 +77,22 @@ fn get_secret_key(key_arg: &str) -> Result<String> {
             env::var("ALICE_SK_HEX")
                 .map_err(|_| anyhow::anyhow!("ALICE_SK_HEX not found in environment variables"))
         }
-        hex_key => {
-            // Validate that it looks like a hex string
-            if hex_key.len() == 64 && hex_key.chars().all(|c| c.is_ascii_hexdigit()) {
-                Ok(hex_key.to_string())
-            } else {
-                Err(anyhow::anyhow!("Key must be either 'bob', 'alice', or a 64-character hex string"))
+        hex_key if hex_key.len() == 64 && hex_key.chars().all(|c| c.is_ascii_hexdigit()) => Ok(hex_key.to_string()),
+        name => {
+            // Anything else names an identity in the keystore
+            let keystore = Keystore::open_default()
+                .map_err(|e| anyhow::anyhow!("Failed to open keystore: {}", e))?;
+            if !keystore.contains(name) {
+                return Err(anyhow::anyhow!(
+                    "No identity named '{}' in {} (key must be a keystore name, 'bob', 'alice', or a 64-character hex string)",
+                    name,
+                    keystore.path().display()
+                ));
             }
+            let passphrase = read_passphrase(&format!("Passphrase for '{}'", name))?;
+            let keys = keystore.unlock(name, &passphrase)
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            Ok(keys.secret_key().to_secret_hex())
         }
     }
 }
@@ -94,7 +112,7 @@ async fn main() -> Result<()> {
             Arg::new("key")
                 .long("key")
                 .value_name("KEY")
-                .help("Secret key for identity: 'bob', 'alice', or hex string")
+                .help("Identity: keystore name, 'bob', 'alice', or hex secret key")
                 .required(true),
         )
         .arg(
@@ -124,31 +142,40 @@ async fn main() -> Result<()> {
         .map_err(|e| anyhow::anyhow!("Failed to create terminal: {}", e))?;
 
     // Create app with MLS service using provided keys and storage backend
-    let dialog_lib = if use_ephemeral {
+    let (dialog_lib, config_path) = if use_ephemeral {
         info!("Using ephemeral (memory) storage");
-        dialog_lib::DialogLib::new_with_keys(keys).await
-            .map_err(|e| anyhow::anyhow!("Failed to initialize MLS service: {}", e))?
+        let dialog_lib = dialog_lib::DialogLib::new_with_keys(keys).await
+            .map_err(|e| anyhow::anyhow!("Failed to initialize MLS service: {}", e))?;
+        if dialog_lib::DialogConfig::from_env().read_receipts {
+            let _ = dialog_lib.set_read_receipts(true).await;
+        }
+        (dialog_lib, None)
     } else {
         let data_dir = get_data_dir()?;
         let db_path = data_dir.join(format!("{}.db", key_arg));
         info!("Using SQLite storage at: {:?}", db_path);
         
-        // Get relay URL from config
-        let config = dialog_lib::DialogConfig::new();
+        // Per-identity settings live next to the database
+        let config_path = data_dir.join(format!("{}.config.json", key_arg));
+        let config = dialog_lib::DialogConfig::load(&config_path)
+            .map_err(|e| anyhow::anyhow!("Failed to load config: {}", e))?;
         let relay_url = config.relay_urls.first()
             .ok_or_else(|| anyhow::anyhow!("No relay URLs configured"))?
             .clone();
         
-        dialog_lib::DialogLib::new_with_storage(
+        let dialog_lib = dialog_lib::DialogLib::new_with_config(
             keys,
             relay_url,
-            StorageBackend::Sqlite { path: db_path }
+            StorageBackend::Sqlite { path: db_path },
+            config,
         ).await
-        .map_err(|e| anyhow::anyhow!("Failed to initialize MLS service with SQLite: {}", e))?
+        .map_err(|e| anyhow::anyhow!("Failed to initialize MLS service with SQLite: {}", e))?;
+        (dialog_lib, Some(config_path))
     };
     
     let mut app = App::new_with_service(dialog_lib).await
         .map_err(|e| anyhow::anyhow!("Failed to create app: {}", e))?;
+    app.config_path = config_path;
     
     // Autoconnect on startup
     app.add_message("");
@@ -224,6 +251,9 @@ async fn main() -> Result<()> {
     // Run app
     let res = run_app(&mut terminal, &mut app).await;
 
+    // Stop background work and give queued messages a last chance to go out
+    let _ = app.dialog_lib.shutdown().await;
+
     // Restore terminal
     disable_raw_mode()?;
     execute!(
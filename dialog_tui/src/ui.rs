@@ -7,7 +7,7 @@ use ratatui::{
 };
 
 use crate::{
-    app::{App, SelectionMode, MessageType},
+    app::{App, SelectionMode, MessageType, presence_label},
     theme::Theme,
 };
 
@@ -137,7 +137,12 @@ fn draw_sidebar(f: &mut Frame, area: Rect, app: &App, theme: &Theme) {
             };
             
             let online_indicator = if contact.online { "●" } else { "○" };
-            items.push(ListItem::new(format!("  {} {}", online_indicator, contact.name)).style(style));
+            let line = if contact.online || contact.last_seen.is_none() {
+                format!("  {} {}", online_indicator, contact.name)
+            } else {
+                format!("  {} {} ({})", online_indicator, contact.name, presence_label(contact))
+            };
+            items.push(ListItem::new(line).style(style));
             current_idx += 1;
         }
     }
@@ -383,7 +388,7 @@ fn draw_contact_selection(f: &mut Frame, group_name: &str, contacts: &[dialog_li
     
     let items: Vec<ListItem> = contacts.iter().zip(selections.iter()).map(|(contact, selected)| {
         let checkbox = if *selected { "[x]" } else { "[ ]" };
-        let status = if contact.online { "(online)" } else { "(offline)" };
+        let status = format!("({})", presence_label(contact));
         
         ListItem::new(vec![
             Line::from(vec![